@@ -1,18 +1,28 @@
 //! Module for handling command line arguments.
 
 use std::env;
+use std::error::Error;
 use std::ffi::OsString;
+use std::fmt;
 use std::io;
 use std::iter::IntoIterator;
+use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use clap::{self, AppSettings, Arg, ArgMatches, ArgSettings, Shell, SubCommand};
 use conv::TryFrom;
 use conv::errors::Unrepresentable;
 use exitcode;
+use regex::Regex;
+use time;
 use url;
+use url::percent_encoding::percent_decode;
 
+use encoding::Encoding;
+use hosts::{DEFAULT_HOST_ID, HOSTS};
+use util::{self, cpu_count, decode_base64};
 use super::{gist, NAME, VERSION};
 
 
@@ -113,11 +123,34 @@ pub struct Options {
     /// Corresponds to the number of times the -v flag has been passed.
     /// If -q has been used instead, this will be negative.
     pub verbosity: isize,
+    /// When to use colors in the program's output.
+    pub color: ColorMode,
     /// Gist locality flag.
     ///
     /// Depending on its value, this flag. may optionally
     /// e.g. prohibit the app from downloading gists from remote hosts.
     pub locality: Option<Locality>,
+    /// Encoding to convert newly fetched gists from, into UTF-8.
+    /// If None, gists are stored exactly as they've been downloaded.
+    pub encoding: Option<Encoding>,
+    /// Whether a shortened gist URL should be expanded
+    /// by following the redirect it points to.
+    pub follow_redirect: bool,
+    /// Git ref (branch, tag, or commit) of the gist to fetch,
+    /// for hosts that store gists as Git repositories.
+    /// If None, the default branch is used.
+    pub git_ref: Option<String>,
+    /// Whether gist binaries should be copies of gist files rather than symlinks to them
+    /// (e.g. on file systems that don't support symlinks).
+    pub no_symlink: bool,
+    /// Configuration file to use on top of the default ones, if any.
+    pub config: Option<PathBuf>,
+    /// Timeout for establishing HTTP connections to gist hosts.
+    /// If None, the one from configuration is used (e.g. $GISHT_TIMEOUT), if any.
+    pub connect_timeout: Option<Duration>,
+    /// Timeout for reading HTTP responses from gist hosts.
+    /// If None, the one from configuration is used (e.g. $GISHT_TIMEOUT), if any.
+    pub read_timeout: Option<Duration>,
     /// Gist command that's been issued.
     pub command: Command,
     /// Gist to operate on, if any.
@@ -125,6 +158,112 @@ pub struct Options {
     /// Arguments to the gist, if any.
     /// This is only used if command == Command::Run.
     pub gist_args: Option<Vec<String>>,
+    /// Whether the script to run should be read from standard input
+    /// instead of being a gist. This is only used if command == Command::Run.
+    pub run_from_stdin: bool,
+    /// Query to find the gist to run by, among the descriptions
+    /// of the authenticated user's gists. This is only used if command == Command::Run.
+    pub run_find: Option<String>,
+    /// Language to assume for the gist when guessing its interpreter,
+    /// overriding the one reported by its host. This is only used if command == Command::Run.
+    pub run_language: Option<String>,
+    /// Whether to fail rather than guess an interpreter for a gist that cannot be ran directly.
+    /// This is only used if command == Command::Run.
+    pub run_no_guess: bool,
+    /// Whether to look for gists with similar names if the given one cannot be found.
+    /// This is only used if command == Command::Run.
+    pub run_fuzzy: bool,
+    /// Whether to show the beginning of the gist and ask for confirmation before running it.
+    /// This is only used if command == Command::Run.
+    pub run_confirm: bool,
+    /// How deep to follow the gists that the gist declares as required (with `gisht-requires:`)
+    /// when fetching them before it's ran. This is only used if command == Command::Run.
+    pub run_requires_depth: usize,
+    /// Additional environment variables for the gist (or script) process.
+    /// This is only used if command == Command::Run.
+    pub run_env: Vec<(String, String)>,
+    /// Whether the gist's binary path should be output as JSON.
+    /// This is only used if command == Command::Which.
+    pub which_json: bool,
+    /// Whether the gist should be printed straight from its remote host,
+    /// without storing it locally. This is only used if command == Command::Print.
+    pub print_remote: bool,
+    /// Whether all the gist's files should be printed (each with a header line),
+    /// rather than just its binary. This is only used if command == Command::Print.
+    pub print_all_files: bool,
+    /// Whether the URL to gist's raw content should be opened, rather than its HTML page.
+    /// This is only used if command == Command::Open.
+    pub open_raw: bool,
+    /// Pieces of gist information to show.
+    /// This is only used if command == Command::Info,
+    /// and an empty vector means all the available information.
+    pub info_fields: Option<Vec<gist::Datum>>,
+    /// Pieces of gist information whose values should be masked in the output.
+    /// This is only used if command == Command::Info.
+    pub info_redact: Vec<gist::Datum>,
+    /// Whether dates & times in gist information should be shown relative to now
+    /// (like "3 days ago"). This is the default if stdout is a terminal,
+    /// unless --absolute-time is given. This is only used if command == Command::Info.
+    pub info_relative_time: bool,
+    /// Directory to copy the gist's files into.
+    /// This is only used if command == Command::Export.
+    pub export_dir: Option<PathBuf>,
+    /// Whether the gist should be exported even if its target directory isn't empty.
+    /// This is only used if command == Command::Export.
+    pub export_force: bool,
+    /// File to save the gist's content into.
+    /// This is only used if command == Command::Download.
+    pub download_path: Option<PathBuf>,
+    /// Whether the file to save the gist into should be overwritten if it exists.
+    /// This is only used if command == Command::Download.
+    pub download_force: bool,
+    /// Whether the gist should also be deleted from its remote host.
+    /// This is only used if command == Command::Purge.
+    pub purge_remote: bool,
+    /// Whether the paths to remove should only be output rather than actually removed.
+    /// This is only used if command == Command::Purge.
+    pub purge_dry_run: bool,
+    /// Whether the list of gists should be output as JSON.
+    /// This is only used if command == Command::List.
+    pub list_json: bool,
+    /// Regular expression to search the local gists for.
+    /// This is only used if command == Command::Search.
+    pub search_pattern: Option<String>,
+    /// Whether only the URIs of matching gists should be output.
+    /// This is only used if command == Command::Search.
+    pub search_name_only: bool,
+    /// Number of gists to search at once.
+    /// This is only used if command == Command::Search.
+    pub search_jobs: usize,
+    /// Whether entries (gist URIs or paths) should be separated by NUL characters
+    /// rather than newlines. This is only used if command == Command::Which,
+    /// Command::List, or Command::Search (with search_name_only).
+    pub print0: bool,
+    /// Whether gist URIs should be output without their `host:` prefix.
+    /// This is only used if command == Command::List, Command::Search,
+    /// or Command::OwnerGists.
+    pub no_host_prefix: bool,
+    /// Owner whose remote gists should be listed.
+    /// This is only used if command == Command::OwnerGists.
+    pub owner: Option<OwnerArg>,
+    /// Maximum number of result pages to fetch when listing owner's gists.
+    /// This is only used if command == Command::OwnerGists.
+    pub owner_max_pages: Option<usize>,
+    /// Only the owner's gists updated after this time should be listed.
+    /// This is only used if command == Command::OwnerGists.
+    pub owner_since: Option<SystemTime>,
+    /// File to create the new gist from.
+    /// This is only used if command == Command::Create.
+    pub create_file: Option<PathBuf>,
+    /// Whether the new gist should be public rather than secret.
+    /// This is only used if command == Command::Create.
+    pub create_public: bool,
+    /// Description of the new gist, if any.
+    /// This is only used if command == Command::Create.
+    pub create_description: Option<String>,
+    /// Whether the interpreters used for gists should be listed instead of running the probes.
+    /// This is only used if command == Command::Doctor.
+    pub doctor_list_interpreters: bool,
 }
 
 #[allow(dead_code)]
@@ -142,6 +281,10 @@ impl<'a> TryFrom<ArgMatches<'a>> for Options {
         let verbose_count = matches.occurrences_of(OPT_VERBOSE) as isize;
         let quiet_count = matches.occurrences_of(OPT_QUIET) as isize;
         let verbosity = verbose_count - quiet_count;
+        // (Validated by the parser already).
+        let color = matches.value_of(OPT_COLOR)
+            .map(|c| ColorMode::from_str(c).unwrap())
+            .unwrap_or_default();
 
         let locality = if matches.is_present(OPT_LOCAL) {
             Some(Locality::Local)
@@ -150,6 +293,18 @@ impl<'a> TryFrom<ArgMatches<'a>> for Options {
         } else {
             None
         };
+        // (Validated by the parser already).
+        let encoding = matches.value_of(OPT_ENCODING)
+            .map(|e| Encoding::from_str(e).unwrap());
+        let follow_redirect = matches.is_present(OPT_FOLLOW);
+        let git_ref = matches.value_of(OPT_REF).map(String::from);
+        let no_symlink = matches.is_present(OPT_NO_SYMLINK);
+        let config = matches.value_of_os(OPT_CONFIG).map(PathBuf::from);
+        // (Validated by the parser already).
+        let connect_timeout = matches.value_of(OPT_CONNECT_TIMEOUT)
+            .map(|t| Duration::from_secs(t.parse().unwrap()));
+        let read_timeout = matches.value_of(OPT_READ_TIMEOUT)
+            .map(|t| Duration::from_secs(t.parse().unwrap()));
 
         // Command may be optionally provided.
         // If it isn't, it means the "run"  default was used, and so all the arguments
@@ -158,25 +313,206 @@ impl<'a> TryFrom<ArgMatches<'a>> for Options {
         let cmd_matches = cmd_matches.unwrap_or(&matches);
         let command = Command::from_str(cmd).unwrap_or(Command::Run);
 
+        // `which --fetch` guarantees a fresh download of the gist before its path is printed,
+        // which obviously cannot be done when we're limited to local gists.
+        let locality = if command == Command::Which && cmd_matches.is_present(OPT_WHICH_FETCH) {
+            if locality == Some(Locality::Local) {
+                return Err(ArgsError::Parse(clap::Error::with_description(
+                    "`which --fetch` cannot be used together with --cached",
+                    clap::ErrorKind::ArgumentConflict)));
+            }
+            Some(Locality::Remote)
+        } else {
+            locality
+        };
+
+        // When running a script from stdin, there is no gist
+        // and all the positional arguments are passed to the script.
+        let run_from_stdin = command == Command::Run && cmd_matches.is_present(OPT_FROM_STDIN);
+        // The same goes for a gist that's found by its description.
+        let run_find = if command == Command::Run {
+            cmd_matches.value_of(OPT_FIND).map(String::from)
+        } else {
+            None
+        };
+        let gist_in_args = !run_from_stdin && run_find.is_none();
+        let run_no_guess = command == Command::Run && cmd_matches.is_present(OPT_NO_GUESS);
+        let run_fuzzy = command == Command::Run && cmd_matches.is_present(OPT_FUZZY);
+        let run_confirm = command == Command::Run && cmd_matches.is_present(OPT_CONFIRM);
+        // (Validated by the parser already).
+        let run_requires_depth = match cmd_matches.value_of(OPT_DEPTH) {
+            Some(n) if command == Command::Run => n.parse().unwrap(),
+            _ => DEFAULT_REQUIRES_DEPTH,
+        };
+        let run_language = if command == Command::Run {
+            cmd_matches.value_of(OPT_LANGUAGE).map(String::from)
+        } else {
+            None
+        };
+        // (Validated by the parser already).
+        let run_env = if command == Command::Run {
+            cmd_matches.values_of(OPT_ENV)
+                .map(|vars| vars.map(|v| parse_env_var(v).unwrap()).collect())
+                .unwrap_or_else(Vec::new)
+        } else {
+            vec![]
+        };
+
         // Parse out the gist argument.
         let gist = match cmd_matches.value_of(ARG_GIST) {
-            Some(g) => Some(try!(GistArg::from_str(g))),
-            None => None,
+            Some(g) if gist_in_args => Some(try!(GistArg::from_str(g))),
+            _ => None,
         };
 
         // For the "run" command, arguments may be provided.
-        let mut gist_args = cmd_matches.values_of(ARG_GIST_ARGV)
+        let mut gist_args: Option<Vec<String>> = cmd_matches.values_of(ARG_GIST_ARGV)
             .map(|argv| argv.map(|v| v.to_owned()).collect());
         if command == Command::Run && gist_args.is_none() {
             gist_args = Some(vec![]);
         }
+        if !gist_in_args {
+            if let Some(first_arg) = cmd_matches.value_of(ARG_GIST) {
+                gist_args.as_mut().unwrap().insert(0, first_arg.to_owned());
+            }
+        }
+
+        // For the "info" command, the pieces of information may be selected.
+        // (Those have been validated by the parser already).
+        let info_fields = if command == Command::Info {
+            Some(cmd_matches.values_of(OPT_FIELDS)
+                .map(|fs| fs.map(|f| gist::Datum::from_str(f).unwrap()).collect())
+                .unwrap_or_else(Vec::new))
+        } else {
+            None
+        };
+        let info_redact = if command == Command::Info {
+            cmd_matches.values_of(OPT_REDACT)
+                .map(|fs| fs.map(|f| gist::Datum::from_str(f).unwrap()).collect())
+                .unwrap_or_else(Vec::new)
+        } else {
+            vec![]
+        };
+        let info_relative_time = command == Command::Info
+            && !cmd_matches.is_present(OPT_ABSOLUTE_TIME) && util::stdout_isatty();
+        let export_dir = if command == Command::Export {
+            cmd_matches.value_of_os(OPT_OUTPUT_DIR).map(PathBuf::from)
+        } else {
+            None
+        };
+        let export_force = command == Command::Export && cmd_matches.is_present(OPT_FORCE);
+        let download_path = if command == Command::Download {
+            cmd_matches.value_of_os(OPT_OUTPUT_FILE).map(PathBuf::from)
+        } else {
+            None
+        };
+        let download_force = command == Command::Download && cmd_matches.is_present(OPT_FORCE);
+        let which_json = command == Command::Which && cmd_matches.is_present(OPT_JSON);
+        let print_remote = command == Command::Print
+            && cmd_matches.is_present(OPT_PRINT_REMOTE);
+        let print_all_files = command == Command::Print
+            && cmd_matches.is_present(OPT_ALL_FILES);
+        let open_raw = command == Command::Open
+            && cmd_matches.is_present(OPT_OPEN_RAW);
+        let purge_remote = command == Command::Purge
+            && cmd_matches.is_present(OPT_PURGE_REMOTE);
+        let purge_dry_run = command == Command::Purge
+            && cmd_matches.is_present(OPT_DRY_RUN);
+        let list_json = command == Command::List && cmd_matches.is_present(OPT_JSON);
+        let print0 = [Command::Which, Command::List, Command::Search].contains(&command)
+            && cmd_matches.is_present(OPT_PRINT0);
+        let no_host_prefix = [Command::List, Command::Search, Command::OwnerGists]
+            .contains(&command) && cmd_matches.is_present(OPT_NO_HOST_PREFIX);
+        let search_pattern = if command == Command::Search {
+            cmd_matches.value_of(ARG_PATTERN).map(String::from)
+        } else {
+            None
+        };
+        let search_name_only = command == Command::Search
+            && cmd_matches.is_present(OPT_NAME_ONLY);
+        // (Validated by the parser already).
+        let search_jobs = match cmd_matches.value_of(OPT_JOBS) {
+            Some(n) if command == Command::Search => n.parse().unwrap(),
+            _ => cpu_count(),
+        };
+        let owner = match cmd_matches.value_of(ARG_OWNER) {
+            Some(o) if command == Command::OwnerGists => Some(try!(OwnerArg::from_str(o))),
+            _ => None,
+        };
+        // (Validated by the parser already).
+        let owner_max_pages = if command == Command::OwnerGists {
+            cmd_matches.value_of(OPT_MAX_PAGES).map(|n| n.parse().unwrap())
+        } else {
+            None
+        };
+        // (Validated by the parser already).
+        let owner_since = if command == Command::OwnerGists {
+            cmd_matches.value_of(OPT_SINCE).map(|s| parse_since(s, SystemTime::now()).unwrap())
+        } else {
+            None
+        };
+
+        let create_file = if command == Command::Create {
+            cmd_matches.value_of_os(ARG_FILE).map(PathBuf::from)
+        } else {
+            None
+        };
+        let create_public = command == Command::Create && cmd_matches.is_present(OPT_PUBLIC);
+        let create_description = if command == Command::Create {
+            cmd_matches.value_of(OPT_DESCRIPTION).map(String::from)
+        } else {
+            None
+        };
+        let doctor_list_interpreters = command == Command::Doctor
+            && cmd_matches.is_present(OPT_LIST_INTERPRETERS);
 
         Ok(Options{
             verbosity: verbosity,
+            color: color,
             locality: locality,
+            encoding: encoding,
+            follow_redirect: follow_redirect,
+            git_ref: git_ref,
+            no_symlink: no_symlink,
+            config: config,
+            connect_timeout: connect_timeout,
+            read_timeout: read_timeout,
             command: command,
             gist: gist,
             gist_args: gist_args,
+            run_from_stdin: run_from_stdin,
+            run_find: run_find,
+            run_language: run_language,
+            run_no_guess: run_no_guess,
+            run_fuzzy: run_fuzzy,
+            run_confirm: run_confirm,
+            run_requires_depth: run_requires_depth,
+            run_env: run_env,
+            which_json: which_json,
+            print_remote: print_remote,
+            print_all_files: print_all_files,
+            open_raw: open_raw,
+            info_fields: info_fields,
+            info_redact: info_redact,
+            info_relative_time: info_relative_time,
+            export_dir: export_dir,
+            export_force: export_force,
+            download_path: download_path,
+            download_force: download_force,
+            purge_remote: purge_remote,
+            purge_dry_run: purge_dry_run,
+            list_json: list_json,
+            search_pattern: search_pattern,
+            search_name_only: search_name_only,
+            search_jobs: search_jobs,
+            print0: print0,
+            no_host_prefix: no_host_prefix,
+            owner: owner,
+            owner_max_pages: owner_max_pages,
+            owner_since: owner_since,
+            create_file: create_file,
+            create_public: create_public,
+            create_description: create_description,
+            doctor_list_interpreters: doctor_list_interpreters,
         })
     }
 }
@@ -190,6 +526,8 @@ macro_attr! {
         Parse(clap::Error),
         /// Error while parsing the gist URI.
         Gist(GistError),
+        /// Error while parsing the gist owner.
+        Owner(gist::UriError),
     }
 }
 
@@ -201,6 +539,8 @@ pub enum GistArg {
     Uri(gist::Uri),
     /// A URL to a gist's browser page (that we hopefully recognize).
     BrowserUrl(url::Url),
+    /// Content of an inline script provided as a `data:` URI.
+    Data(DataArg),
 }
 
 impl FromStr for GistArg {
@@ -212,7 +552,10 @@ impl FromStr for GistArg {
         let is_browser_url = ["http://", "https://", "www."].iter()
             .any(|p| s.starts_with(p));
 
-        if is_browser_url {
+        if s.starts_with("data:") {
+            let data = try!(DataArg::from_str(input.trim()));
+            Ok(GistArg::Data(data))
+        } else if is_browser_url {
             let gist_url = try!(url::Url::from_str(input));
             Ok(GistArg::BrowserUrl(gist_url))
         } else {
@@ -222,6 +565,109 @@ impl FromStr for GistArg {
     }
 }
 
+/// Type holding the content of a `data:` URI given as the GIST argument,
+/// like "data:text/x-python;base64,cHJpbnQoNDIpCg==".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataArg {
+    /// Declared MIME type of the content, without any parameters (like charset=).
+    pub mime_type: String,
+    /// The decoded content.
+    pub content: Vec<u8>,
+}
+
+impl DataArg {
+    /// Language of the content, as implied by its MIME type.
+    /// For example, this is "python" for "text/x-python".
+    pub fn language(&self) -> Option<&str> {
+        let subtype = try_opt!(self.mime_type.splitn(2, '/').nth(1));
+        let language = subtype.trim_left_matches("x-");
+        if language.is_empty() || language == "plain" || language == "octet-stream" {
+            None
+        } else {
+            Some(language)
+        }
+    }
+}
+
+impl FromStr for DataArg {
+    type Err = DataError;
+
+    /// Parse a `data:` URI, as per RFC 2397.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let malformed = || DataError::Malformed(input.to_owned());
+        if !input.to_lowercase().starts_with("data:") {
+            return Err(malformed());
+        }
+        let comma = try!(input.find(',').ok_or_else(&malformed));
+        let (header, payload) = (&input["data:".len()..comma], &input[comma + 1..]);
+
+        let mut params = header.split(';').map(|p| p.trim().to_lowercase());
+        let mime_type = match params.next() {
+            Some(ref m) if !m.is_empty() => m.clone(),
+            _ => "text/plain".to_owned(),
+        };
+        let content = if params.any(|p| p == "base64") {
+            try!(decode_base64(payload).ok_or(DataError::Base64))
+        } else {
+            percent_decode(payload.as_bytes()).collect()
+        };
+        Ok(DataArg{mime_type: mime_type, content: content})
+    }
+}
+
+/// An error that occurred when parsing a `data:` URI.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataError {
+    /// The URI was completely malformed (e.g. lacked the comma before data).
+    /// Argument is the entire alleged URI string.
+    Malformed(String),
+    /// The data was declared as base64 but wasn't valid base64.
+    Base64,
+}
+
+impl Error for DataError {
+    fn description(&self) -> &str { "data URI error" }
+    fn cause(&self) -> Option<&Error> { None }
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DataError::Malformed(ref s) => write!(fmt, "malformed data: URI `{}`", s),
+            DataError::Base64 => write!(fmt, "invalid base64 content of a data: URI"),
+        }
+    }
+}
+
+
+/// Type holding the value of the OWNER argument,
+/// like "Octocat" or "gh:Octocat".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnerArg {
+    /// ID of the gist host, possibly the default one.
+    pub host_id: String,
+    /// Name of the gist owner (user) on that host.
+    pub owner: String,
+}
+
+impl FromStr for OwnerArg {
+    type Err = gist::UriError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^((?P<host>\w+):)?(?P<owner>[\w-]+)$").unwrap();
+        }
+        let parsed = try!(RE.captures(input.trim())
+            .ok_or_else(|| gist::UriError::Malformed(input.to_owned())));
+
+        let host_id = parsed.name("host").map(|h| h.as_str()).unwrap_or(DEFAULT_HOST_ID);
+        if !HOSTS.contains_key(host_id) {
+            return Err(gist::UriError::UnknownHost(host_id.to_owned()));
+        }
+        Ok(OwnerArg{host_id: host_id.to_owned(), owner: parsed["owner"].to_owned()})
+    }
+}
+
 macro_attr! {
     /// Error that can occur while parsing of the GIST argument.
     #[derive(Debug, PartialEq,
@@ -231,6 +677,8 @@ macro_attr! {
         Uri(gist::UriError),
         /// Error while parsing gist's browser URL.
         BrowserUrl(url::ParseError),
+        /// Error while parsing a `data:` URI.
+        Data(DataError),
     }
 }
 
@@ -249,6 +697,68 @@ macro_attr! {
 }
 
 
+macro_attr! {
+    /// Enum describing when to use colors in the program's output.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash,
+             IterVariants!(ColorModes))]
+    pub enum ColorMode {
+        /// Always use colors.
+        Always,
+        /// Use colors only when outputting to a terminal.
+        Auto,
+        /// Never use colors.
+        Never,
+    }
+}
+
+impl ColorMode {
+    /// Canonical name of the color mode, as used in command line arguments.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ColorMode::Always => "always",
+            ColorMode::Auto => "auto",
+            ColorMode::Never => "never",
+        }
+    }
+
+    /// Whether colors should be used when writing to stderr.
+    pub fn for_stderr(&self) -> bool {
+        match *self {
+            ColorMode::Always => true,
+            ColorMode::Auto => cfg!(unix) && util::stderr_isatty(),
+            ColorMode::Never => false,
+        }
+    }
+
+    /// Whether colors should be used when writing to stdout.
+    pub fn for_stdout(&self) -> bool {
+        match *self {
+            ColorMode::Always => true,
+            ColorMode::Auto => cfg!(unix) && util::stdout_isatty(),
+            ColorMode::Never => false,
+        }
+    }
+}
+
+impl Default for ColorMode {
+    #[inline]
+    fn default() -> Self { ColorMode::Auto }
+}
+
+impl FromStr for ColorMode {
+    type Err = Unrepresentable<String>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        for mode in ColorMode::iter_variants() {
+            if mode.name() == s {
+                return Ok(mode);
+            }
+        }
+        Err(Unrepresentable(s.to_owned()))
+    }
+}
+
+
 macro_attr! {
     /// Gist command issued to the application, along with its arguments.
     #[derive(Clone, Debug, Eq, PartialEq, Hash,
@@ -264,9 +774,25 @@ macro_attr! {
         Open,
         /// Display summary information about the gist.
         Info,
-
+        /// Copy all the files of the gist into a directory.
+        Export,
+        /// Save the gist's content into a file, as-is.
+        Download,
+        /// Remove the local copy of the gist (and optionally the remote one, too).
+        Purge,
+
+        /// List the gists that are available locally.
+        List,
+        /// Search the source code of local gists.
+        Search,
+        /// List the gists of a particular owner on a remote host.
+        OwnerGists,
+        /// Create a new gist on a remote host from a local file.
+        Create,
         /// List the information about available gist hosts.
         Hosts,
+        /// Check the environment for common problems.
+        Doctor,
     }
 }
 
@@ -280,7 +806,15 @@ impl Command {
             Command::Print => "print",
             Command::Open => "open",
             Command::Info => "info",
+            Command::Export => "export",
+            Command::Download => "download",
+            Command::Purge => "purge",
+            Command::List => "list",
+            Command::Search => "search",
+            Command::OwnerGists => "owner-gists",
+            Command::Create => "create",
             Command::Hosts => "hosts",
+            Command::Doctor => "doctor",
         }
     }
 
@@ -292,6 +826,10 @@ impl Command {
             Command::Print => &["cat"],
             Command::Open => &["show"],
             Command::Info => &["stat"],
+            Command::List => &["ls"],
+            Command::Search => &["grep"],
+            Command::Download => &["save"],
+            Command::Create => &["paste"],
             Command::Hosts => &["services"],
             _ => &[],
         }
@@ -300,7 +838,8 @@ impl Command {
     /// Whether the command takes a gist as an argument.
     pub fn takes_gist(&self) -> bool {
         match *self {
-            Command::Hosts => false,
+            Command::List | Command::Search | Command::OwnerGists | Command::Create |
+            Command::Hosts | Command::Doctor => false,
             _ => true,
         }
     }
@@ -334,14 +873,66 @@ type Parser<'p> = clap::App<'p, 'p>;
 
 lazy_static! {
     static ref ABOUT: &'static str = option_env!("CARGO_PKG_DESCRIPTION").unwrap_or("");
+
+    static ref COLOR_MODES: Vec<&'static str> =
+        ColorMode::iter_variants().map(|m| m.name()).collect();
+    static ref DEFAULT_COLOR_MODE: &'static str = ColorMode::default().name();
+
+    static ref DEPTH_HELP: String = format!(
+        "Fetch gists required by the gist (via `gisht-requires:` comments) up to this depth, \
+         or 0 to not fetch them (defaults to {})", DEFAULT_REQUIRES_DEPTH);
 }
 
+/// Default depth of fetching the gists that a gist being ran requires.
+const DEFAULT_REQUIRES_DEPTH: usize = 3;
+
 const ARG_GIST: &'static str = "gist";
 const ARG_GIST_ARGV: &'static str = "argv";
+const OPT_ENCODING: &'static str = "encoding";
 const OPT_VERBOSE: &'static str = "verbose";
 const OPT_QUIET: &'static str = "quiet";
+const OPT_COLOR: &'static str = "color";
 const OPT_LOCAL: &'static str = "local";
 const OPT_REMOTE: &'static str = "remote";
+const OPT_FOLLOW: &'static str = "follow";
+const OPT_REF: &'static str = "ref";
+const OPT_NO_SYMLINK: &'static str = "no_symlink";
+const OPT_CONFIG: &'static str = "config";
+const OPT_CONNECT_TIMEOUT: &'static str = "connect_timeout";
+const OPT_READ_TIMEOUT: &'static str = "read_timeout";
+const OPT_FROM_STDIN: &'static str = "from_stdin";
+const OPT_FIND: &'static str = "find";
+const OPT_LANGUAGE: &'static str = "language";
+const OPT_NO_GUESS: &'static str = "no_guess";
+const OPT_FUZZY: &'static str = "fuzzy";
+const OPT_CONFIRM: &'static str = "confirm";
+const OPT_DEPTH: &'static str = "depth";
+const OPT_ENV: &'static str = "env";
+const OPT_PRINT_REMOTE: &'static str = "print_remote";
+const OPT_ALL_FILES: &'static str = "all_files";
+const OPT_OPEN_RAW: &'static str = "open_raw";
+const OPT_FIELDS: &'static str = "fields";
+const OPT_REDACT: &'static str = "redact";
+const OPT_ABSOLUTE_TIME: &'static str = "absolute_time";
+const OPT_OUTPUT_DIR: &'static str = "output_dir";
+const OPT_OUTPUT_FILE: &'static str = "output_file";
+const OPT_FORCE: &'static str = "force";
+const OPT_PURGE_REMOTE: &'static str = "purge_remote";
+const OPT_DRY_RUN: &'static str = "dry_run";
+const OPT_WHICH_FETCH: &'static str = "which_fetch";
+const OPT_JSON: &'static str = "json";
+const OPT_PRINT0: &'static str = "print0";
+const OPT_NO_HOST_PREFIX: &'static str = "no_host_prefix";
+const ARG_PATTERN: &'static str = "pattern";
+const OPT_NAME_ONLY: &'static str = "name_only";
+const OPT_JOBS: &'static str = "jobs";
+const ARG_OWNER: &'static str = "owner";
+const OPT_MAX_PAGES: &'static str = "max_pages";
+const OPT_SINCE: &'static str = "since";
+const ARG_FILE: &'static str = "file";
+const OPT_PUBLIC: &'static str = "public";
+const OPT_DESCRIPTION: &'static str = "description";
+const OPT_LIST_INTERPRETERS: &'static str = "list_interpreters";
 
 
 /// Create the full argument parser.
@@ -355,22 +946,172 @@ fn create_full_parser<'p>() -> Parser<'p> {
 
         .subcommand(configure_run_gist_parser(
             subcommand_for(Command::Run)
-                .about("Run the specified gist")))
+                .about("Run the specified gist")
+                .after_help(
+                    "The gist can locate itself through these environment variables:\n\n\
+                    \tGISHT_GIST_DIR   directory with the gist's files\n\
+                    \tGISHT_GIST_PATH  path to the gist's binary\n\
+                    \tGISHT_GIST_URI   URI of the gist (e.g. gh:Octocat/greet)")))
         .subcommand(subcommand_for(Command::Which)
             .about("Output the path to gist's binary")
+            .arg(Arg::with_name(OPT_WHICH_FETCH)
+                .long("fetch")
+                .help("Download the latest version of the gist before outputting its path"))
+            .arg(Arg::with_name(OPT_JSON)
+                .long("json")
+                .help("Output the path as JSON object, along with the gist's directory"))
+            .arg(print0_arg("Terminate the path with NUL character instead of newline")
+                .conflicts_with(OPT_JSON))
             .arg(gist_arg("Gist to locate")))
         .subcommand(subcommand_for(Command::Print)
             .about("Print the source code of gist's binary")
-            .arg(gist_arg("Gist to print")))
+            .arg(Arg::with_name(OPT_PRINT_REMOTE)
+                .long("remote")
+                .help("Read the gist from its remote host without storing it locally"))
+            .arg(Arg::with_name(OPT_ALL_FILES)
+                .long("all-files")
+                .conflicts_with(OPT_PRINT_REMOTE)
+                .help("Print all files of a multi-file gist, each preceded by its name"))
+            .arg(gist_arg("Gist to print"))
+            .after_help(
+                "When printing to a terminal, the output is paged through $PAGER if it's set."))
         .subcommand(subcommand_for(Command::Open)
             .about("Open the gist's webpage")
+            .arg(Arg::with_name(OPT_OPEN_RAW)
+                .long("raw")
+                .help("Open the gist's raw content rather than its webpage"))
             .arg(gist_arg("Gist to open")))
         .subcommand(subcommand_for(Command::Info)
             .about("Display summary information about the gist")
+            .arg(Arg::with_name(OPT_FIELDS)
+                .long("fields")
+                .takes_value(true).use_delimiter(true)
+                .validator(|f| gist::Datum::from_str(&f).map(|_| ())
+                    .map_err(|_| format!("unknown gist info field: {}", f)))
+                .value_name("FIELD,...")
+                .help("Only show selected pieces of gist information (e.g. id,owner,language)"))
+            .arg(Arg::with_name(OPT_REDACT)
+                .long("redact")
+                .takes_value(true).use_delimiter(true)
+                .validator(|f| gist::Datum::from_str(&f).map(|_| ())
+                    .map_err(|_| format!("unknown gist info field: {}", f)))
+                .value_name("FIELD,...")
+                .help("Mask the values of selected pieces of gist information \
+                       (e.g. owner,description)"))
+            .arg(Arg::with_name(OPT_ABSOLUTE_TIME)
+                .long("absolute-time")
+                .help("Show dates & times as they are, rather than relative to now \
+                       (like \"3 days ago\")"))
             .arg(gist_arg("Gist to display info on")))
-
+        .subcommand(subcommand_for(Command::Export)
+            .about("Copy the gist's files into a directory")
+            .arg(Arg::with_name(OPT_OUTPUT_DIR)
+                .long("output-dir").short("o")
+                .takes_value(true)
+                .required(true)
+                .value_name("DIR")
+                .help("Directory to copy the files into (created if it doesn't exist)"))
+            .arg(Arg::with_name(OPT_FORCE)
+                .long("force")
+                .help("Export the gist even if the directory isn't empty, \
+                       overwriting any files with the same names"))
+            .arg(gist_arg("Gist to export")))
+        .subcommand(subcommand_for(Command::Download)
+            .about("Save the gist's content into a file, without making it executable")
+            .arg(Arg::with_name(OPT_OUTPUT_FILE)
+                .long("output").short("o")
+                .takes_value(true)
+                .required(true)
+                .value_name("FILE")
+                .help("File to save the gist into"))
+            .arg(Arg::with_name(OPT_FORCE)
+                .long("force")
+                .help("Overwrite the file if it exists"))
+            .arg(gist_arg("Gist to download")))
+        .subcommand(subcommand_for(Command::Purge)
+            .about("Remove the local copy of the gist")
+            .arg(Arg::with_name(OPT_PURGE_REMOTE)
+                .long("remote")
+                .help("Delete the gist from its remote host, too (needs confirmation)"))
+            .arg(Arg::with_name(OPT_DRY_RUN)
+                .long("dry-run")
+                .help("Only output what would be removed, without removing anything"))
+            .arg(gist_arg("Gist to remove")))
+
+        .subcommand(subcommand_for(Command::List)
+            .about("List gists that have been downloaded locally")
+            .arg(Arg::with_name(OPT_JSON)
+                .long("json")
+                .help("Output the list as JSON array of objects"))
+            .arg(print0_arg("Separate gist URIs with NUL characters instead of newlines")
+                .conflicts_with(OPT_JSON))
+            .arg(no_host_prefix_arg()
+                .conflicts_with(OPT_JSON)))
+        .subcommand(subcommand_for(Command::Search)
+            .about("Search the source code of local gists")
+            .arg(Arg::with_name(OPT_NAME_ONLY)
+                .long("name-only").short("l")
+                .help("Only output the URIs of matching gists"))
+            .arg(print0_arg("Separate gist URIs with NUL characters instead of newlines")
+                .requires(OPT_NAME_ONLY))
+            .arg(no_host_prefix_arg())
+            .arg(Arg::with_name(OPT_JOBS)
+                .long("jobs").short("j")
+                .takes_value(true)
+                .validator(|n| match n.parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(()),
+                    _ => Err(format!("invalid number of jobs: {}", n)),
+                })
+                .value_name("N")
+                .help("Search this many gists at once (defaults to the number of CPUs)"))
+            .arg(Arg::with_name(ARG_PATTERN)
+                .required(true)
+                .validator(|p| Regex::new(&p).map(|_| ()).map_err(|e| e.to_string()))
+                .help("Regular expression to search for")
+                .value_name("PATTERN")))
+        .subcommand(subcommand_for(Command::OwnerGists)
+            .about("List gists of given owner on a remote host")
+            .arg(Arg::with_name(OPT_MAX_PAGES)
+                .long("max-pages")
+                .takes_value(true)
+                .validator(|n| n.parse::<usize>().map(|_| ())
+                    .map_err(|_| format!("invalid number of pages: {}", n)))
+                .value_name("N")
+                .help("Fetch at most this many pages of results from the host"))
+            .arg(Arg::with_name(OPT_SINCE)
+                .long("since")
+                .takes_value(true)
+                .validator(|s| parse_since(&s, SystemTime::now()).map(|_| ()))
+                .value_name("WHEN")
+                .help("Only list gists updated after this date (like 2017-07-14) \
+                       or this long ago (like 7d, 12h, or 2w)"))
+            .arg(no_host_prefix_arg())
+            .arg(Arg::with_name(ARG_OWNER)
+                .required(true)
+                .help("Owner of the gists, optionally with the host (e.g. gh:Octocat)")
+                .value_name("OWNER")))
+        .subcommand(subcommand_for(Command::Create)
+            .about("Create a new GitHub gist from a file (requires GITHUB_TOKEN)")
+            .arg(Arg::with_name(OPT_PUBLIC)
+                .long("public")
+                .help("Make the gist public rather than secret"))
+            .arg(Arg::with_name(OPT_DESCRIPTION)
+                .long("description")
+                .takes_value(true)
+                .value_name("TEXT")
+                .help("Description of the gist"))
+            .arg(Arg::with_name(ARG_FILE)
+                .required(true)
+                .help("File with the content of the gist, which also gives the gist its name")
+                .value_name("FILE")))
         .subcommand(subcommand_for(Command::Hosts)
             .about("List supported gist hosts (services)"))
+        .subcommand(subcommand_for(Command::Doctor)
+            .about("Check the environment for problems with fetching & running gists")
+            .arg(Arg::with_name(OPT_LIST_INTERPRETERS)
+                .long("list-interpreters")
+                .help("List the interpreters used to run gists in each language, \
+                       instead of checking the environment")))
 
         .after_help(
             "Hint: `gisht run GIST` can be shortened to just `gisht GIST`.\n\
@@ -398,6 +1139,8 @@ fn create_parser_base<'p>() -> Parser<'p> {
         // Gist locality flags (shared by all subcommands).
         .arg(Arg::with_name(OPT_LOCAL)
             .long("cached").short("c")
+            .visible_alias("offline")
+            .alias("no-update")
             .conflicts_with(OPT_REMOTE)
             .help("Operate only on gists available locally"))
         .arg(Arg::with_name(OPT_REMOTE)
@@ -405,6 +1148,58 @@ fn create_parser_base<'p>() -> Parser<'p> {
             .conflicts_with(OPT_LOCAL)
             .help("Always fetch the gist from a remote host"))
 
+        // Encoding of fetched gists (shared by all subcommands).
+        .arg(Arg::with_name(OPT_ENCODING)
+            .long("encoding")
+            .takes_value(true)
+            .validator(|e| Encoding::from_str(&e).map(|_| ())
+                .map_err(|_| format!("unsupported encoding: {}", e)))
+            .value_name("ENCODING")
+            .help("Convert newly fetched gists from this encoding to UTF-8 (e.g. latin1)"))
+
+        // Flag for expanding shortened gist URLs (shared by all subcommands).
+        .arg(Arg::with_name(OPT_FOLLOW)
+            .long("follow")
+            .help("Follow a single redirect of a shortened gist URL (e.g. git.io)"))
+
+        // Git ref of the gist to use (shared by all subcommands).
+        .arg(Arg::with_name(OPT_REF)
+            .long("ref")
+            .takes_value(true)
+            .conflicts_with(OPT_LOCAL)
+            .value_name("REF")
+            .help("Use given branch, tag, or commit of a Git-based gist (e.g. from GitHub)"))
+
+        // Storing copies of gist files as binaries (shared by all subcommands).
+        .arg(Arg::with_name(OPT_NO_SYMLINK)
+            .long("no-symlink")
+            .help("Store gist binaries as copies rather than symlinks \
+                   (can also be set with GISHT_NO_SYMLINK=1)"))
+
+        // Additional configuration file (shared by all subcommands).
+        .arg(Arg::with_name(OPT_CONFIG)
+            .long("config")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Read configuration from this file, overriding other config files \
+                   (can also be set with GISHT_CONFIG)"))
+
+        // Timeouts of HTTP connections (shared by all subcommands).
+        .arg(Arg::with_name(OPT_CONNECT_TIMEOUT)
+            .long("connect-timeout")
+            .takes_value(true)
+            .validator(validate_timeout)
+            .value_name("SECS")
+            .help("Give up connecting to a gist host after this many seconds \
+                   (defaults to GISHT_TIMEOUT, if set)"))
+        .arg(Arg::with_name(OPT_READ_TIMEOUT)
+            .long("read-timeout")
+            .takes_value(true)
+            .validator(validate_timeout)
+            .value_name("SECS")
+            .help("Give up waiting for data from a gist host after this many seconds \
+                   (defaults to GISHT_TIMEOUT, if set)"))
+
         // Verbosity flags (shared by all subcommands).
         .arg(Arg::with_name(OPT_VERBOSE)
             .long("verbose").short("v")
@@ -417,10 +1212,63 @@ fn create_parser_base<'p>() -> Parser<'p> {
             .conflicts_with(OPT_VERBOSE)
             .help("Decrease logging verbosity"))
 
+        // Output coloring option (shared by all subcommands).
+        .arg(Arg::with_name(OPT_COLOR)
+            .long("color")
+            .takes_value(true)
+            .possible_values(&*COLOR_MODES)
+            .default_value(*DEFAULT_COLOR_MODE)
+            .value_name("WHEN")
+            .help("When to use colors in the output"))
+
         .help_short("H")
         .version_short("V")
 }
 
+/// Check that the value of a timeout flag is a positive number of seconds.
+fn validate_timeout(secs: String) -> Result<(), String> {
+    match secs.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("invalid number of seconds: {}", secs)),
+    }
+}
+
+/// Parse the value of --since as either an absolute UTC date (with optional time),
+/// or a period of time (in minutes, hours, days, or weeks) before `now`.
+fn parse_since(s: &str, now: SystemTime) -> Result<SystemTime, String> {
+    const FORMATS: &'static [&'static str] = &[
+        "%Y-%m-%dT%H:%M:%SZ", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d",
+    ];
+    lazy_static! {
+        static ref PERIOD_RE: Regex = Regex::new(r"^(?P<count>\d+)(?P<unit>[mhdw])$").unwrap();
+    }
+
+    if let Some(caps) = PERIOD_RE.captures(s) {
+        let count: u64 = try!(caps["count"].parse().map_err(|_| format!("invalid period: {}", s)));
+        let unit_secs = match &caps["unit"] {
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => 7 * 24 * 60 * 60,
+        };
+        let period_secs = try!(count.checked_mul(unit_secs)
+            .ok_or_else(|| format!("period too long: {}", s)));
+        let now_secs = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        return Ok(UNIX_EPOCH + Duration::from_secs(now_secs.saturating_sub(period_secs)));
+    }
+
+    // (strptime() ignores any trailing input, so longer formats have to be tried first).
+    for format in FORMATS {
+        if let Ok(tm) = time::strptime(s, format) {
+            let secs = tm.to_timespec().sec;
+            if secs >= 0 {
+                return Ok(UNIX_EPOCH + Duration::from_secs(secs as u64));
+            }
+        }
+    }
+    Err(format!("invalid date or period: {}", s))
+}
+
 /// Create a clap subcommand Parser object for given gist Command.
 fn subcommand_for<'p>(command: Command) -> Parser<'p> {
     SubCommand::with_name(command.name())
@@ -432,7 +1280,53 @@ fn subcommand_for<'p>(command: Command) -> Parser<'p> {
 /// This is also used when there is no command given.
 fn configure_run_gist_parser<'p>(parser: Parser<'p>) -> Parser<'p> {
     parser
-        .arg(gist_arg("Gist to run"))
+        .arg(gist_arg("Gist to run").required_unless_one(&[OPT_FROM_STDIN, OPT_FIND]))
+        .arg(Arg::with_name(OPT_FROM_STDIN)
+            .long("from-stdin")
+            .help("Run a script read from standard input instead of a gist"))
+        .arg(Arg::with_name(OPT_FIND)
+            .long("find")
+            .takes_value(true)
+            .value_name("QUERY")
+            .conflicts_with(OPT_FROM_STDIN)
+            .help("Run your GitHub gist whose description contains QUERY \
+                   (requires GITHUB_TOKEN)"))
+        .arg(Arg::with_name(OPT_LANGUAGE)
+            .long("language")
+            .takes_value(true)
+            .value_name("LANG")
+            .conflicts_with(OPT_FROM_STDIN)
+            .help("Language of the gist, used when guessing its interpreter \
+                   (e.g. for pastes without a hashbang)"))
+        .arg(Arg::with_name(OPT_NO_GUESS)
+            .long("no-guess")
+            .conflicts_with_all(&[OPT_FROM_STDIN, OPT_LANGUAGE])
+            .help("Fail instead of guessing an interpreter for a gist without a valid hashbang"))
+        .arg(Arg::with_name(OPT_FUZZY)
+            .long("fuzzy")
+            .conflicts_with(OPT_FROM_STDIN)
+            .help("If the gist cannot be found, suggest (or offer to run) one with a similar name"))
+        .arg(Arg::with_name(OPT_CONFIRM)
+            .long("confirm")
+            .conflicts_with(OPT_FROM_STDIN)
+            .help("Show the beginning of the gist and ask before running it \
+                   (only in interactive sessions)"))
+        .arg(Arg::with_name(OPT_DEPTH)
+            .long("depth")
+            .takes_value(true)
+            .conflicts_with(OPT_FROM_STDIN)
+            .validator(|n| n.parse::<usize>().map(|_| ())
+                .map_err(|_| format!("invalid depth: {}", n)))
+            .value_name("N")
+            .help(&DEPTH_HELP))
+        .arg(Arg::with_name(OPT_ENV)
+            .long("env")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .validator(|v| parse_env_var(&v).map(|_| ()))
+            .value_name("KEY=VALUE")
+            .help("Set an environment variable for the gist (can be given multiple times)"))
         // This argument spec is capturing everything after the gist URI,
         // allowing for the arguments to be passed to the gist itself.
         .arg(Arg::with_name(ARG_GIST_ARGV)
@@ -444,6 +1338,17 @@ fn configure_run_gist_parser<'p>(parser: Parser<'p>) -> Parser<'p> {
         .setting(AppSettings::TrailingVarArg)
 }
 
+/// Parse a `KEY=VALUE` environment variable assignment.
+fn parse_env_var(s: &str) -> Result<(String, String), String> {
+    let eq_pos = try!(s.find('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got: {}", s)));
+    let (key, value) = (&s[..eq_pos], &s[eq_pos + 1..]);
+    if key.is_empty() {
+        return Err(format!("environment variable name cannot be empty: {}", s));
+    }
+    Ok((key.to_owned(), value.to_owned()))
+}
+
 /// Create the GIST argument to various gist subcommands.
 fn gist_arg(help: &'static str) -> Arg {
     Arg::with_name(ARG_GIST)
@@ -452,12 +1357,33 @@ fn gist_arg(help: &'static str) -> Arg {
         .value_name("GIST")
 }
 
+/// Create the --print0 flag for commands that output gist URIs or paths.
+fn print0_arg(help: &'static str) -> Arg {
+    Arg::with_name(OPT_PRINT0)
+        .long("print0").short("0")
+        .help(help)
+}
+
+/// Create the --no-host-prefix flag for commands that list gist URIs.
+fn no_host_prefix_arg() -> Arg<'static, 'static> {
+    Arg::with_name(OPT_NO_HOST_PREFIX)
+        .long("no-host-prefix")
+        .help("Output gist URIs without their host prefix \
+               (e.g. owner/name rather than gh:owner/name)")
+}
+
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
+    use std::path::PathBuf;
     use std::str::FromStr;
-    use super::{Command, create_full_parser, parse_from_argv};
+    use std::time::{Duration, UNIX_EPOCH};
+    use gist::Datum;
+    use encoding::Encoding;
+    use hosts::DEFAULT_HOST_ID;
+    use super::{ColorMode, Command, DataArg, GistArg, Locality, DEFAULT_REQUIRES_DEPTH,
+                create_full_parser, parse_from_argv, parse_since};
 
     #[test]
     fn command_aliases_distinct_from_name() {
@@ -541,6 +1467,30 @@ mod tests {
         }
     }
 
+    /// Verify that gists can be given inline as `data:` URIs.
+    #[test]
+    fn gist_data_uri() {
+        let opts = parse_from_argv(vec![
+            "gisht", "run", "data:text/x-python;charset=utf-8;base64,cHJpbnQoNDIpCg=="]).unwrap();
+        let data = match opts.gist {
+            Some(GistArg::Data(data)) => data,
+            other => panic!("Unexpected gist argument: {:?}", other),
+        };
+        assert_eq!("text/x-python", data.mime_type);
+        assert_eq!(Some("python"), data.language());
+        assert_eq!(b"print(42)\n".to_vec(), data.content);
+
+        let data = DataArg::from_str("data:,echo%20hi").unwrap();
+        assert_eq!("text/plain", data.mime_type);
+        assert_eq!(None, data.language());
+        assert_eq!(b"echo hi".to_vec(), data.content);
+
+        for invalid in &["data:text/plain;base64", "data:;base64,!!!"] {
+            assert!(parse_from_argv(vec!["gisht", "run", invalid]).is_err(),
+                "Invalid data URI `{}` unexpectedly accepted", invalid);
+        }
+    }
+
     /// Verify that args can only be provided for the `run` subcommand.
     #[test]
     fn gist_args_only_for_run() {
@@ -560,6 +1510,528 @@ mod tests {
             "Gist URI `{}` should cause a parse error but didn't", gist_uri);
     }
 
+    /// Verify that the --color option is parsed correctly.
+    #[test]
+    fn color_mode() {
+        let opts = parse_from_argv(vec!["gisht", "hosts"]).unwrap();
+        assert_eq!(ColorMode::Auto, opts.color);
+        for mode in ColorMode::iter_variants() {
+            let opts = parse_from_argv(vec!["gisht", "--color", mode.name(), "hosts"]).unwrap();
+            assert_eq!(mode, opts.color);
+        }
+        assert!(parse_from_argv(vec!["gisht", "--color", "sometimes", "hosts"]).is_err(),
+            "Invalid color mode unexpectedly accepted");
+    }
+
+    /// Verify that a script from stdin receives all the positional arguments.
+    #[test]
+    fn run_from_stdin() {
+        let opts = parse_from_argv(vec![
+            "gisht", "run", "--from-stdin", "--", "foo", "--bar"]).unwrap();
+        assert!(opts.run_from_stdin);
+        assert_eq!(None, opts.gist);
+        assert_eq!(Some(vec!["foo".to_owned(), "--bar".to_owned()]), opts.gist_args);
+
+        let opts = parse_from_argv(vec!["gisht", "run", "--from-stdin"]).unwrap();
+        assert!(opts.run_from_stdin);
+        assert_eq!(Some(vec![]), opts.gist_args);
+    }
+
+    /// Verify that a gist found by description receives all the positional arguments.
+    #[test]
+    fn run_find() {
+        let opts = parse_from_argv(vec![
+            "gisht", "run", "--find", "deploy script", "--", "foo", "--bar"]).unwrap();
+        assert_eq!(Some("deploy script"), opts.run_find.as_ref().map(|q| q.as_str()));
+        assert_eq!(None, opts.gist);
+        assert_eq!(Some(vec!["foo".to_owned(), "--bar".to_owned()]), opts.gist_args);
+
+        let opts = parse_from_argv(vec!["gisht", "run", "test/test"]).unwrap();
+        assert_eq!(None, opts.run_find);
+
+        assert!(parse_from_argv(vec!["gisht", "run", "--find", "x", "--from-stdin"]).is_err(),
+            "--find unexpectedly accepted together with --from-stdin");
+    }
+
+    /// Verify that the language of a gist to run can be overridden.
+    #[test]
+    fn run_language() {
+        let opts = parse_from_argv(vec![
+            "gisht", "run", "--language", "python", "pb:abc", "--", "foo"]).unwrap();
+        assert_eq!(Some("python"), opts.run_language.as_ref().map(|l| l.as_str()));
+        assert_eq!(Some(vec!["foo".to_owned()]), opts.gist_args);
+
+        let opts = parse_from_argv(vec!["gisht", "run", "pb:abc"]).unwrap();
+        assert_eq!(None, opts.run_language);
+    }
+
+    /// Verify that fuzzy matching of gist names can be enabled.
+    #[test]
+    fn run_fuzzy() {
+        let opts = parse_from_argv(vec!["gisht", "run", "Octocat/hello"]).unwrap();
+        assert!(!opts.run_fuzzy);
+
+        for argv in vec![vec!["gisht", "run", "--fuzzy", "Octocat/helo"],
+                         vec!["gisht", "--fuzzy", "Octocat/helo"]] {
+            let opts = parse_from_argv(argv.clone()).unwrap();
+            assert!(opts.run_fuzzy, "--fuzzy not recognized in {:?}", argv);
+        }
+    }
+
+    /// Verify that running the gist can require a confirmation.
+    #[test]
+    fn run_confirm() {
+        let opts = parse_from_argv(vec!["gisht", "run", "Octocat/hello"]).unwrap();
+        assert!(!opts.run_confirm);
+
+        let opts = parse_from_argv(vec!["gisht", "--confirm", "Octocat/hello"]).unwrap();
+        assert!(opts.run_confirm);
+
+        assert!(parse_from_argv(vec!["gisht", "run", "--confirm", "--from-stdin"]).is_err(),
+            "--confirm unexpectedly accepted along with --from-stdin");
+    }
+
+    /// Verify that interpreter guessing can be disabled.
+    #[test]
+    fn run_no_guess() {
+        let opts = parse_from_argv(vec!["gisht", "run", "pb:abc"]).unwrap();
+        assert!(!opts.run_no_guess);
+
+        let opts = parse_from_argv(vec!["gisht", "run", "--no-guess", "pb:abc"]).unwrap();
+        assert!(opts.run_no_guess);
+
+        assert!(parse_from_argv(vec![
+            "gisht", "run", "--no-guess", "--language", "python", "pb:abc"]).is_err(),
+            "--no-guess unexpectedly accepted along with --language");
+    }
+
+    /// Verify that the depth of fetching required gists can be limited.
+    #[test]
+    fn run_requires_depth() {
+        let opts = parse_from_argv(vec!["gisht", "run", "pb:abc"]).unwrap();
+        assert_eq!(DEFAULT_REQUIRES_DEPTH, opts.run_requires_depth);
+
+        let opts = parse_from_argv(vec!["gisht", "run", "--depth", "0", "pb:abc"]).unwrap();
+        assert_eq!(0, opts.run_requires_depth);
+
+        assert!(parse_from_argv(vec!["gisht", "run", "--depth", "-1", "pb:abc"]).is_err(),
+            "Negative --depth unexpectedly accepted");
+    }
+
+    /// Verify that environment variables for the gist can be provided.
+    #[test]
+    fn run_env() {
+        let opts = parse_from_argv(vec![
+            "gisht", "run", "--env", "FOO=bar", "--env", "EMPTY=", "--env", "EQ=a=b",
+            "test/test", "--", "baz"]).unwrap();
+        assert_eq!(vec![("FOO".to_owned(), "bar".to_owned()),
+                        ("EMPTY".to_owned(), "".to_owned()),
+                        ("EQ".to_owned(), "a=b".to_owned())], opts.run_env);
+        assert_eq!(Some(vec!["baz".to_owned()]), opts.gist_args);
+
+        for invalid in &["FOO", "=bar"] {
+            assert!(parse_from_argv(vec!["gisht", "run", "--env", invalid, "test/test"]).is_err(),
+                "Invalid --env value `{}` unexpectedly accepted", invalid);
+        }
+    }
+
+    /// Verify that the `open` command can be told to open the raw content.
+    #[test]
+    fn open_raw() {
+        let opts = parse_from_argv(vec!["gisht", "open", "test/test"]).unwrap();
+        assert!(!opts.open_raw);
+
+        let opts = parse_from_argv(vec!["gisht", "open", "--raw", "test/test"]).unwrap();
+        assert!(opts.open_raw);
+    }
+
+    /// Verify that the `print` command can be told to skip the local copy.
+    #[test]
+    fn print_remote() {
+        let opts = parse_from_argv(vec!["gisht", "print", "test/test"]).unwrap();
+        assert!(!opts.print_remote);
+
+        let opts = parse_from_argv(vec!["gisht", "cat", "--remote", "test/test"]).unwrap();
+        assert_eq!(Command::Print, opts.command);
+        assert!(opts.print_remote);
+    }
+
+    /// Verify that the `print` command can print all files, but not of a remote gist.
+    #[test]
+    fn print_all_files() {
+        let opts = parse_from_argv(vec!["gisht", "print", "test/test"]).unwrap();
+        assert!(!opts.print_all_files);
+
+        let opts = parse_from_argv(vec!["gisht", "print", "--all-files", "test/test"]).unwrap();
+        assert!(opts.print_all_files);
+        assert!(parse_from_argv(
+            vec!["gisht", "print", "--all-files", "--remote", "test/test"]).is_err(),
+            "--all-files unexpectedly accepted together with --remote");
+    }
+
+    /// Verify that the `download` command requires the output file.
+    #[test]
+    fn download_output_file() {
+        let opts = parse_from_argv(
+            vec!["gisht", "download", "--output", "data.csv", "test/test"]).unwrap();
+        assert_eq!(Command::Download, opts.command);
+        assert_eq!(Some(PathBuf::from("data.csv")), opts.download_path);
+        assert!(!opts.download_force);
+
+        let opts = parse_from_argv(
+            vec!["gisht", "save", "--force", "-o", "data.csv", "test/test"]).unwrap();
+        assert_eq!(Command::Download, opts.command);
+        assert!(opts.download_force);
+
+        assert!(parse_from_argv(vec!["gisht", "download", "test/test"]).is_err(),
+            "`download` unexpectedly accepted without --output");
+    }
+
+    /// Verify that `doctor` can list the interpreters instead of running the probes.
+    #[test]
+    fn doctor_list_interpreters() {
+        let opts = parse_from_argv(vec!["gisht", "doctor"]).unwrap();
+        assert_eq!(Command::Doctor, opts.command);
+        assert!(!opts.doctor_list_interpreters);
+
+        let opts = parse_from_argv(vec!["gisht", "doctor", "--list-interpreters"]).unwrap();
+        assert!(opts.doctor_list_interpreters);
+    }
+
+    /// Verify that the `export` command requires the output directory.
+    #[test]
+    fn export_output_dir() {
+        let opts = parse_from_argv(
+            vec!["gisht", "export", "--output-dir", "out", "test/test"]).unwrap();
+        assert_eq!(Command::Export, opts.command);
+        assert_eq!(Some(PathBuf::from("out")), opts.export_dir);
+        assert!(!opts.export_force);
+
+        let opts = parse_from_argv(
+            vec!["gisht", "export", "--force", "-o", "out", "test/test"]).unwrap();
+        assert!(opts.export_force);
+
+        assert!(parse_from_argv(vec!["gisht", "export", "test/test"]).is_err(),
+            "`export` unexpectedly accepted without --output-dir");
+    }
+
+    /// Verify that the `purge` command only deletes remote gists when asked to.
+    #[test]
+    fn purge_remote() {
+        let opts = parse_from_argv(vec!["gisht", "purge", "test/test"]).unwrap();
+        assert_eq!(Command::Purge, opts.command);
+        assert!(!opts.purge_remote);
+
+        let opts = parse_from_argv(vec!["gisht", "purge", "--remote", "test/test"]).unwrap();
+        assert!(opts.purge_remote);
+    }
+
+    #[test]
+    fn purge_dry_run() {
+        let opts = parse_from_argv(vec!["gisht", "purge", "test/test"]).unwrap();
+        assert!(!opts.purge_dry_run);
+
+        let opts = parse_from_argv(vec!["gisht", "purge", "--dry-run", "test/test"]).unwrap();
+        assert!(opts.purge_dry_run);
+    }
+
+    /// Verify that `which --fetch` forces a download and rejects --cached.
+    #[test]
+    fn which_fetch() {
+        let opts = parse_from_argv(vec!["gisht", "which", "foo"]).unwrap();
+        assert_eq!(None, opts.locality);
+
+        let opts = parse_from_argv(vec!["gisht", "which", "--fetch", "foo"]).unwrap();
+        assert_eq!(Command::Which, opts.command);
+        assert_eq!(Some(Locality::Remote), opts.locality);
+
+        let opts = parse_from_argv(
+            vec!["gisht", "--fetch", "which", "--fetch", "foo"]).unwrap();
+        assert_eq!(Some(Locality::Remote), opts.locality);
+
+        assert!(parse_from_argv(vec!["gisht", "--cached", "which", "--fetch", "foo"]).is_err(),
+            "`which --fetch` unexpectedly accepted together with --cached");
+    }
+
+    /// Verify that --offline and --no-update are the same as --cached.
+    #[test]
+    fn offline_aliases() {
+        for flag in &["--cached", "--offline", "--no-update", "-c"] {
+            let opts = parse_from_argv(vec!["gisht", flag, "info", "foo"]).unwrap();
+            assert_eq!(Some(Locality::Local), opts.locality, "{} isn't --cached", flag);
+        }
+    }
+
+    /// Verify that the `owner-gists` command parses the owner (and host).
+    #[test]
+    fn owner_gists() {
+        let opts = parse_from_argv(vec!["gisht", "owner-gists", "Octocat"]).unwrap();
+        assert_eq!(Command::OwnerGists, opts.command);
+        let owner = opts.owner.unwrap();
+        assert_eq!(DEFAULT_HOST_ID, owner.host_id);
+        assert_eq!("Octocat", owner.owner);
+
+        let opts = parse_from_argv(
+            vec!["gisht", "owner-gists", &format!("{}:Octocat", DEFAULT_HOST_ID)]).unwrap();
+        assert_eq!(DEFAULT_HOST_ID, opts.owner.unwrap().host_id);
+
+        let opts = parse_from_argv(vec!["gisht", "owner-gists", "octo-cat"]).unwrap();
+        assert_eq!("octo-cat", opts.owner.unwrap().owner);
+
+        assert_eq!(None, opts.owner_max_pages);
+
+        let opts = parse_from_argv(
+            vec!["gisht", "owner-gists", "--max-pages", "3", "Octocat"]).unwrap();
+        assert_eq!(Some(3), opts.owner_max_pages);
+        assert!(parse_from_argv(vec!["gisht", "owner-gists", "--max-pages", "x", "Octocat"])
+            .is_err(), "Invalid page limit unexpectedly accepted");
+
+        assert_eq!(None, opts.owner_since);
+        let opts = parse_from_argv(
+            vec!["gisht", "owner-gists", "--since", "7d", "Octocat"]).unwrap();
+        assert!(opts.owner_since.is_some());
+        assert!(parse_from_argv(vec!["gisht", "owner-gists", "--since", "soon", "Octocat"])
+            .is_err(), "Invalid --since unexpectedly accepted");
+
+        assert!(parse_from_argv(vec!["gisht", "owner-gists", "Octocat/foo"]).is_err(),
+            "Gist URI unexpectedly accepted as owner");
+        assert!(parse_from_argv(vec!["gisht", "owner-gists", "unknownhost:Octocat"]).is_err(),
+            "Owner on an unknown host unexpectedly accepted");
+    }
+
+    /// Verify that --since accepts both dates and periods of time.
+    #[test]
+    fn since_dates_and_periods() {
+        let now = UNIX_EPOCH + Duration::from_secs(1500000000);
+        let secs = |s| parse_since(s, now).unwrap().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        assert_eq!(1500000000 - 90 * 60, secs("90m"));
+        assert_eq!(1500000000 - 7 * 24 * 60 * 60, secs("7d"));
+        assert_eq!(1500000000 - 2 * 7 * 24 * 60 * 60, secs("2w"));
+        assert_eq!(1499990400, secs("2017-07-14"));
+        assert_eq!(1500000000, secs("2017-07-14T02:40:00Z"));
+        assert_eq!(1500000000, secs("2017-07-14 02:40:00"));
+
+        assert!(parse_since("", now).is_err());
+        assert!(parse_since("7y", now).is_err());
+        assert!(parse_since("yesterday", now).is_err());
+    }
+
+    /// Verify that the `create` command parses the file and the gist's attributes.
+    #[test]
+    fn create() {
+        let opts = parse_from_argv(vec!["gisht", "create", "hello.sh"]).unwrap();
+        assert_eq!(Command::Create, opts.command);
+        assert_eq!(Some(PathBuf::from("hello.sh")), opts.create_file);
+        assert!(!opts.create_public);
+        assert_eq!(None, opts.create_description);
+        assert_eq!(None, opts.gist);
+
+        let opts = parse_from_argv(vec!["gisht", "paste", "--public",
+                                        "--description", "Says hello", "hello.sh"]).unwrap();
+        assert_eq!(Command::Create, opts.command);
+        assert!(opts.create_public);
+        assert_eq!(Some("Says hello".into()), opts.create_description);
+
+        assert!(parse_from_argv(vec!["gisht", "create"]).is_err(),
+            "`create` unexpectedly accepted without a file");
+    }
+
+    /// Verify that the source encoding of gists can be specified.
+    #[test]
+    fn encoding() {
+        let opts = parse_from_argv(vec!["gisht", "print", "foo"]).unwrap();
+        assert_eq!(None, opts.encoding);
+
+        let opts = parse_from_argv(vec!["gisht", "--encoding", "Latin1", "print", "foo"]).unwrap();
+        assert_eq!(Some(Encoding::Latin1), opts.encoding);
+
+        assert!(parse_from_argv(vec!["gisht", "--encoding", "ebcdic", "print", "foo"]).is_err(),
+            "Unsupported encoding unexpectedly accepted");
+    }
+
+    /// Verify that a Git ref of the gist can be specified.
+    #[test]
+    fn git_ref() {
+        let opts = parse_from_argv(vec!["gisht", "run", "foo"]).unwrap();
+        assert_eq!(None, opts.git_ref);
+
+        let opts = parse_from_argv(vec!["gisht", "--ref", "v1.0", "run", "foo"]).unwrap();
+        assert_eq!(Some("v1.0".to_owned()), opts.git_ref);
+
+        assert!(parse_from_argv(vec!["gisht", "--ref", "v1.0", "--cached", "run", "foo"]).is_err(),
+            "--ref unexpectedly accepted along with --cached");
+    }
+
+    /// Verify that gist binaries can be stored as copies.
+    #[test]
+    fn no_symlink() {
+        let opts = parse_from_argv(vec!["gisht", "run", "foo"]).unwrap();
+        assert!(!opts.no_symlink);
+
+        let opts = parse_from_argv(vec!["gisht", "--no-symlink", "run", "foo"]).unwrap();
+        assert!(opts.no_symlink);
+    }
+
+    /// Verify that an additional config file can be given.
+    #[test]
+    fn config() {
+        let opts = parse_from_argv(vec!["gisht", "run", "foo"]).unwrap();
+        assert_eq!(None, opts.config);
+
+        let opts = parse_from_argv(vec!["gisht", "--config", "gisht.toml", "run", "foo"]).unwrap();
+        assert_eq!(Some(PathBuf::from("gisht.toml")), opts.config);
+    }
+
+    /// Verify that the HTTP timeouts can be given separately.
+    #[test]
+    fn timeouts() {
+        let opts = parse_from_argv(vec!["gisht", "run", "foo"]).unwrap();
+        assert_eq!(None, opts.connect_timeout);
+        assert_eq!(None, opts.read_timeout);
+
+        let opts = parse_from_argv(vec!["gisht", "--connect-timeout", "5",
+                                        "--read-timeout", "60", "run", "foo"]).unwrap();
+        assert_eq!(Some(Duration::from_secs(5)), opts.connect_timeout);
+        assert_eq!(Some(Duration::from_secs(60)), opts.read_timeout);
+
+        assert!(parse_from_argv(vec!["gisht", "--read-timeout", "0", "run", "foo"]).is_err(),
+            "Zero timeout unexpectedly accepted");
+        assert!(parse_from_argv(vec!["gisht", "--connect-timeout", "x", "run", "foo"]).is_err(),
+            "Invalid timeout unexpectedly accepted");
+    }
+
+    /// Verify that the `search` command takes a valid regex pattern.
+    #[test]
+    fn search_pattern() {
+        let opts = parse_from_argv(vec!["gisht", "search", "foo.*bar"]).unwrap();
+        assert_eq!(Command::Search, opts.command);
+        assert_eq!(Some("foo.*bar".into()), opts.search_pattern);
+        assert!(!opts.search_name_only);
+
+        let opts = parse_from_argv(vec!["gisht", "search", "--name-only", "foo"]).unwrap();
+        assert!(opts.search_name_only);
+
+        assert!(parse_from_argv(vec!["gisht", "search", "foo("]).is_err(),
+            "Invalid search regex unexpectedly accepted");
+    }
+
+    /// Verify that the number of concurrent search jobs can be specified.
+    #[test]
+    fn search_jobs() {
+        let opts = parse_from_argv(vec!["gisht", "search", "foo"]).unwrap();
+        assert!(opts.search_jobs >= 1);
+
+        let opts = parse_from_argv(vec!["gisht", "search", "-j", "3", "foo"]).unwrap();
+        assert_eq!(3, opts.search_jobs);
+
+        for invalid in &["0", "-1", "many"] {
+            assert!(parse_from_argv(vec!["gisht", "search", "--jobs", invalid, "foo"]).is_err(),
+                "Invalid --jobs value `{}` unexpectedly accepted", invalid);
+        }
+    }
+
+    /// Verify that NUL-separated output can be requested for commands that list gists.
+    #[test]
+    fn print0() {
+        let opts = parse_from_argv(vec!["gisht", "list"]).unwrap();
+        assert!(!opts.print0);
+
+        for argv in vec![vec!["gisht", "list", "-0"],
+                         vec!["gisht", "which", "--print0", "test/test"],
+                         vec!["gisht", "search", "-l", "-0", "foo"]] {
+            let opts = parse_from_argv(argv.clone()).unwrap();
+            assert!(opts.print0, "--print0 not recognized in {:?}", argv);
+        }
+
+        assert!(parse_from_argv(vec!["gisht", "list", "--json", "-0"]).is_err(),
+            "--print0 unexpectedly accepted along with --json");
+        assert!(parse_from_argv(vec!["gisht", "search", "-0", "foo"]).is_err(),
+            "--print0 unexpectedly accepted without --name-only");
+    }
+
+    #[test]
+    fn which_json() {
+        let opts = parse_from_argv(vec!["gisht", "which", "test/test"]).unwrap();
+        assert!(!opts.which_json);
+
+        let opts = parse_from_argv(vec!["gisht", "which", "--json", "test/test"]).unwrap();
+        assert!(opts.which_json);
+        assert!(!opts.list_json);
+
+        assert!(parse_from_argv(vec!["gisht", "which", "--json", "-0", "test/test"]).is_err(),
+            "--print0 unexpectedly accepted along with --json");
+    }
+
+    /// Verify that the `list` command can output JSON.
+    #[test]
+    fn list_json() {
+        let opts = parse_from_argv(vec!["gisht", "list"]).unwrap();
+        assert_eq!(Command::List, opts.command);
+        assert!(!opts.list_json);
+
+        let opts = parse_from_argv(vec!["gisht", "list", "--json"]).unwrap();
+        assert!(opts.list_json);
+    }
+
+    /// Verify that the listing commands accept --no-host-prefix.
+    #[test]
+    fn no_host_prefix() {
+        let opts = parse_from_argv(vec!["gisht", "list"]).unwrap();
+        assert!(!opts.no_host_prefix);
+
+        for argv in vec![vec!["gisht", "list", "--no-host-prefix"],
+                         vec!["gisht", "search", "--no-host-prefix", "foo"],
+                         vec!["gisht", "owner-gists", "--no-host-prefix", "gh:Octocat"]] {
+            let opts = parse_from_argv(argv.clone()).unwrap();
+            assert!(opts.no_host_prefix, "--no-host-prefix not recognized in {:?}", argv);
+        }
+
+        assert!(parse_from_argv(vec!["gisht", "list", "--json", "--no-host-prefix"]).is_err(),
+            "--no-host-prefix unexpectedly accepted along with --json");
+    }
+
+    /// Verify that pieces of gist info can be selected for the `info` command.
+    #[test]
+    fn info_fields() {
+        let opts = parse_from_argv(vec!["gisht", "info", "test/test"]).unwrap();
+        assert_eq!(Some(vec![]), opts.info_fields);
+
+        let opts = parse_from_argv(vec![
+            "gisht", "info", "--fields", "id,owner", "test/test"]).unwrap();
+        assert_eq!(Some(vec![Datum::Id, Datum::Owner]), opts.info_fields);
+    }
+
+    /// Verify that pieces of gist info can be redacted in the `info` command.
+    #[test]
+    fn info_redact() {
+        let opts = parse_from_argv(vec!["gisht", "info", "test/test"]).unwrap();
+        assert!(opts.info_redact.is_empty());
+
+        let opts = parse_from_argv(vec![
+            "gisht", "info", "--redact", "owner,description", "test/test"]).unwrap();
+        assert_eq!(vec![Datum::Owner, Datum::Description], opts.info_redact);
+
+        assert!(parse_from_argv(vec!["gisht", "info", "--redact", "lolwut", "test/test"])
+            .is_err(), "Unknown redacted gist info field unexpectedly accepted");
+    }
+
+    /// Verify that relative times in gist info can be turned off.
+    #[test]
+    fn info_absolute_time() {
+        let opts = parse_from_argv(
+            vec!["gisht", "info", "--absolute-time", "test/test"]).unwrap();
+        assert!(!opts.info_relative_time);
+    }
+
+    /// Verify that unknown gist info fields cause an error.
+    #[test]
+    fn info_fields_unknown() {
+        let args = vec!["gisht", "info", "--fields", "id,lolwut", "test/test"];
+        assert!(parse_from_argv(args).is_err(),
+            "Unknown gist info field unexpectedly accepted");
+    }
+
     // Verify that "help" command correctly shows help.
     #[test]
     fn help_works() {
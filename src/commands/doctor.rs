@@ -0,0 +1,181 @@
+//! Module implementing the self-diagnostics ("doctor") command.
+//!
+//! The command runs a series of small probes of the environment
+//! that gisht needs to fetch & run gists, and reports their results.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+
+use exitcode::{self, ExitCode};
+use git2::{Direction, Repository};
+
+use ::APP_DIR;
+use failure::{exit_code_for, Failure};
+use util::{find_in_path, symlink_file};
+use super::run::{interpreter_binaries, interpreter_table};
+
+
+/// URL of the Git repository used to check whether GitHub is reachable.
+const GITHUB_PROBE_URL: &'static str = "https://github.com/Xion/gisht.git";
+
+
+/// Check the environment for common problems and write a report to given output.
+///
+/// Problems that prevent gisht from working at all make the command fail,
+/// while those that only affect some gists (like missing interpreters) are just warnings.
+pub fn diagnose(output: &mut Write) -> ExitCode {
+    let mut probes = vec![
+        Probe::required("Application directory is writable", probe_app_dir()),
+        Probe::required("Symlinks can be created", probe_symlinks()),
+        Probe::required("GitHub is reachable with Git over HTTPS", probe_github()),
+    ];
+    for binary in interpreter_binaries() {
+        let result = find_in_path(&binary)
+            .map(|path| path.display().to_string())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found on $PATH"));
+        probes.push(Probe::optional(format!("Interpreter `{}` is available", binary), result));
+    }
+
+    let mut failed = false;
+    for probe in &probes {
+        failed = failed || probe.is_failure();
+        if let Err(e) = writeln!(output, "{}", probe) {
+            error!("Failed to output the diagnostics report: {}", e);
+            return exit_code_for(Failure::Io);
+        }
+    }
+    if failed { exit_code_for(Failure::Environment) } else { exitcode::OK }
+}
+
+/// Write a table of the interpreters that gisht uses to run gists to given output,
+/// along with the languages and file extensions they are used for.
+pub fn list_interpreters(output: &mut Write) -> ExitCode {
+    let table = interpreter_table();
+    if table.is_empty() {
+        warn!("Interpreters are not guessed for gists on this platform");
+        return exitcode::OK;
+    }
+
+    let rows: Vec<_> = table.into_iter().map(|(ext, languages, cmdline)| {
+        let languages = if languages.is_empty() { "-".to_owned() } else { languages.join(", ") };
+        (format!(".{}", ext), languages, cmdline.unwrap_or_else(|| "(none)".to_owned()))
+    }).collect();
+    let ext_width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+    let lang_width = rows.iter().map(|r| r.1.len()).max().unwrap_or(0);
+
+    for (ext, languages, cmdline) in rows {
+        if let Err(e) = writeln!(output, "{:ew$}  {:lw$}  {}",
+                                 ext, languages, cmdline, ew = ext_width, lw = lang_width) {
+            error!("Failed to output the interpreter list: {}", e);
+            return exit_code_for(Failure::Io);
+        }
+    }
+    exitcode::OK
+}
+
+
+/// Result of a single diagnostic probe.
+#[derive(Debug)]
+struct Probe {
+    /// What has been checked.
+    label: String,
+    /// Whether gisht cannot work at all if the probe fails.
+    required: bool,
+    /// Details of the successful check, or the error.
+    result: io::Result<String>,
+}
+
+impl Probe {
+    fn required<L: Into<String>>(label: L, result: io::Result<String>) -> Self {
+        Probe{label: label.into(), required: true, result: result}
+    }
+
+    fn optional<L: Into<String>>(label: L, result: io::Result<String>) -> Self {
+        Probe{label: label.into(), required: false, result: result}
+    }
+
+    #[inline]
+    fn is_failure(&self) -> bool {
+        self.required && self.result.is_err()
+    }
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.result {
+            Ok(ref details) => write!(fmt, "[ OK ] {} ({})", self.label, details),
+            Err(ref e) => write!(fmt, "[{}] {}: {}",
+                if self.required { "FAIL" } else { "WARN" }, self.label, e),
+        }
+    }
+}
+
+
+// Probes
+
+/// Check that files can be written to the application directory.
+fn probe_app_dir() -> io::Result<String> {
+    let path = APP_DIR.join(".doctor");
+    try!(fs::create_dir_all(&*APP_DIR));
+    try!(fs::File::create(&path).and_then(|mut f| f.write_all(b"gisht")));
+    try!(fs::remove_file(&path));
+    Ok(APP_DIR.display().to_string())
+}
+
+/// Check that a symlink to a file can be created and read through.
+/// This is how gist binaries are stored, but it may require special privileges on Windows.
+fn probe_symlinks() -> io::Result<String> {
+    let target = APP_DIR.join(".doctor-target");
+    let link = APP_DIR.join(".doctor-link");
+    let _ = fs::remove_file(&link);
+    try!(fs::File::create(&target).and_then(|mut f| f.write_all(b"gisht")));
+
+    let result = symlink_file(&target, &link).and_then(|_| {
+        let mut content = String::new();
+        try!(fs::File::open(&link).and_then(|mut f| f.read_to_string(&mut content)));
+        if content == "gisht" {
+            Ok(link.display().to_string())
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "symlink points to wrong content"))
+        }
+    });
+    let _ = fs::remove_file(&link);
+    let _ = fs::remove_file(&target);
+    result
+}
+
+/// Check that libgit2 can connect to GitHub over HTTPS, like it does when cloning gists.
+fn probe_github() -> io::Result<String> {
+    let repo_dir = APP_DIR.join(".doctor-repo");
+    let result = Repository::init(&repo_dir).and_then(|repo| {
+        let mut remote = try!(repo.remote_anonymous(GITHUB_PROBE_URL));
+        try!(remote.connect(Direction::Fetch));
+        remote.disconnect();
+        Ok(GITHUB_PROBE_URL.to_owned())
+    });
+    let _ = fs::remove_dir_all(&repo_dir);
+    result.map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use super::Probe;
+
+    #[test]
+    fn probe_display() {
+        let ok = Probe::required("Foo works", Ok("bar".into()));
+        assert_eq!("[ OK ] Foo works (bar)", format!("{}", ok));
+
+        let error = || Err(io::Error::new(io::ErrorKind::Other, "broken"));
+        let failed = Probe::required("Foo works", error());
+        assert_eq!("[FAIL] Foo works: broken", format!("{}", failed));
+        assert!(failed.is_failure());
+
+        let warning = Probe::optional("Foo works", error());
+        assert_eq!("[WARN] Foo works: broken", format!("{}", warning));
+        assert!(!warning.is_failure());
+    }
+}
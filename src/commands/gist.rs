@@ -1,30 +1,63 @@
 //! Module implementing various commands that can be performed on gists.
 
+use std::env;
 use std::fs;
 use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
 
 use exitcode::{self, ExitCode};
+use serde_json;
+use serde_json::builder::ObjectBuilder;
+use shlex;
 use webbrowser;
 
-use gist::Gist;
+use ::{GISTS_DIR, YES};
+use failure::{exit_code_for, Failure};
+use gist::{Datum, Gist};
+use hosts::FetchMode;
+use util::{binary_source, unlink_binary};
+use super::entry_terminator;
 
 
 /// Output the gist's binary path.
-pub fn print_binary_path(gist: &Gist) -> ExitCode {
+///
+/// If `json` is true, the output is a JSON object with the binary path,
+/// the gist's directory, whether the binary exists, and the gist's ID (if known).
+/// The ID is the full one from gist info when available (e.g. `abc.py` for Hastebin),
+/// rather than the one used in paths.
+pub fn print_binary_path(gist: &Gist, print0: bool, json: bool, output: &mut Write) -> ExitCode {
     trace!("Printing binary path of {:?}", gist);
-    println!("{}", gist.binary_path().display());
+    let path = gist.binary_path();
+    let result = if json {
+        let object = ObjectBuilder::new()
+            .insert("binary_path", path.to_str())
+            .insert("dir", gist.path().to_str())
+            .insert("exists", path.exists())
+            .insert("id", gist.info(Datum::Id).or_else(|| gist.id.clone()))
+            .build();
+        serde_json::to_writer(output, &object)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .and_then(|_| writeln!(output))
+    } else {
+        write!(output, "{}{}", path.display(), entry_terminator(print0))
+    };
+    if let Err(e) = result {
+        error!("Failed to output the binary path of gist {}: {}", gist.uri, e);
+        return exit_code_for(Failure::Io);
+    }
     exitcode::OK
 }
 
 
-/// Print the source of the gist's binary.
-pub fn print_gist(gist: &Gist) -> ExitCode {
+/// Print the source of the gist's binary to given output.
+pub fn print_gist(gist: &Gist, output: &mut Write) -> ExitCode {
     trace!("Printing source code of {:?}", gist);
     let mut binary = match fs::File::open(gist.binary_path()) {
         Ok(file) => file,
         Err(e) => {
             error!("Failed to open the binary of gist {}: {}", gist.uri, e);
-            return exitcode::IOERR;
+            return exit_code_for(Failure::Io);
         },
     };
 
@@ -35,13 +68,13 @@ pub fn print_gist(gist: &Gist) -> ExitCode {
             Ok(c) => c,
             Err(e) => {
                 error!("Failed to read the binary of gist {}: {}", gist.uri, e);
-                return exitcode::IOERR;
+                return exit_code_for(Failure::Io);
             },
         };
         if c > 0 {
-            if let Err(e) = io::stdout().write_all(&buf[0..c]) {
-                error!("Failed to write the gist {} to stdout: {}", gist.uri, e);
-                return exitcode::IOERR;
+            if let Err(e) = output.write_all(&buf[0..c]) {
+                error!("Failed to output the gist {}: {}", gist.uri, e);
+                return exit_code_for(Failure::Io);
             }
         }
         if c < BUF_SIZE { break }
@@ -50,42 +83,480 @@ pub fn print_gist(gist: &Gist) -> ExitCode {
 }
 
 
+/// Print the source of a gist that hasn't been downloaded yet to given output.
+///
+/// If the gist's host includes the content of (small) gists in their metadata,
+/// it is printed from there. Otherwise, the gist is fetched and printed from its local copy.
+pub fn print_new_gist(gist: &Gist, output: &mut Write) -> ExitCode {
+    let host = gist.uri.host();
+    match host.read_inline_gist(gist) {
+        Ok(Some(mut content)) => {
+            trace!("Printing source code of {:?} from its metadata", gist);
+            if let Err(e) = io::copy(&mut content, output) {
+                error!("Failed to output the gist {}: {}", gist.uri, e);
+                return exit_code_for(Failure::Io);
+            }
+            return exitcode::OK;
+        },
+        Ok(None) => debug!("Content of gist {} not available from {}", gist.uri, host.name()),
+        Err(e) => warn!("Failed to read the content of gist {} from {}: {}",
+            gist.uri, host.name(), e),
+    }
+
+    debug!("Fetching gist {} to print it...", gist.uri);
+    if let Err(e) = host.fetch_gist(gist, FetchMode::New) {
+        error!("Failed to download gist {}: {}", gist.uri, e);
+        return exit_code_for(Failure::Io);
+    }
+    print_gist(gist, output)
+}
+
+/// Print all the files of a downloaded gist to given output,
+/// each preceded by a header line with its name (like `==> foo.py <==`).
+pub fn print_all_gist_files(gist: &Gist, output: &mut Write) -> ExitCode {
+    trace!("Printing all files of {:?}", gist);
+    let files = match gist_files(gist) {
+        Ok(files) => files,
+        Err(e) => {
+            error!("Failed to list the files of gist {}: {}", gist.uri, e);
+            return exit_code_for(Failure::Io);
+        },
+    };
+    for (i, file) in files.iter().enumerate() {
+        let result = fs::File::open(file).and_then(|mut f| {
+            if i > 0 {
+                try!(writeln!(output));
+            }
+            try!(writeln!(output, "==> {} <==", file.file_name().unwrap().to_string_lossy()));
+            io::copy(&mut f, output)
+        });
+        if let Err(e) = result {
+            error!("Failed to output {} of gist {}: {}", file.display(), gist.uri, e);
+            return exit_code_for(Failure::Io);
+        }
+    }
+    exitcode::OK
+}
+
+/// Print the source of the gist to given output,
+/// reading it directly from the gist's host if possible.
+pub fn print_remote_gist(gist: &Gist, output: &mut Write) -> ExitCode {
+    trace!("Printing source code of {:?} from its remote host", gist);
+    let mut content = match gist.uri.host().read_gist(gist) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to read gist {} from {}: {}", gist.uri, gist.uri.host().name(), e);
+            return exit_code_for(Failure::Host);
+        },
+    };
+    if let Err(e) = io::copy(&mut content, output) {
+        error!("Failed to output the gist {}: {}", gist.uri, e);
+        return exit_code_for(Failure::Io);
+    }
+    exitcode::OK
+}
+
+
 /// Open the gist's HTML page in the default system browser.
-pub fn open_gist(gist: &Gist) -> ExitCode {
-    let url = match gist.uri.host().gist_url(gist) {
+/// If `raw` is true, the URL to gist's raw content is opened instead.
+///
+/// If there is no browser to open it in (e.g. on a headless system),
+/// the URL is written to given output instead, so that it can be copied.
+pub fn open_gist(gist: &Gist, raw: bool, output: &mut Write) -> ExitCode {
+    let host = gist.uri.host();
+    let url = if raw {
+        host.raw_url(gist).and_then(|url| url.ok_or_else(|| io::Error::new(
+            io::ErrorKind::Other, format!("{} has no raw URLs for gists", host.name()))))
+    } else {
+        host.gist_url(gist)
+    };
+    let url = match url {
         Ok(url) => url,
         Err(e) => {
             error!("Failed to determine the URL of gist {}: {}", gist.uri, e);
-            return exitcode::UNAVAILABLE;
+            return exit_code_for(Failure::Host);
         },
     };
-    if let Err(e) = webbrowser::open(&url) {
-        error!("Failed to open the URL of gist {} ({}) in the browser: {}",
-            gist.uri, url, e);
-        return exitcode::UNAVAILABLE;
-    };
+
+    if !has_browser() {
+        warn!("No web browser available to open gist {}, printing its URL instead", gist.uri);
+    } else {
+        match open_with_env_browser(&url) {
+            Some(Ok(ref status)) if status.success() => return exitcode::OK,
+            Some(Ok(status)) => warn!("Failed to open the URL of gist {} with $BROWSER ({}), \
+                trying the default browser", gist.uri, status),
+            Some(Err(e)) => warn!("Failed to open the URL of gist {} with $BROWSER ({}), \
+                trying the default browser", gist.uri, e),
+            None => {},
+        }
+        match webbrowser::open(&url) {
+            Ok(ref out) if out.status.success() => return exitcode::OK,
+            Ok(out) => warn!("Failed to open the URL of gist {} in the browser ({}), \
+                printing it instead", gist.uri, out.status),
+            Err(e) => warn!("Failed to open the URL of gist {} in the browser ({}), \
+                printing it instead", gist.uri, e),
+        }
+    }
+    if let Err(e) = writeln!(output, "{}", url) {
+        error!("Failed to output the URL of gist {}: {}", gist.uri, e);
+        return exit_code_for(Failure::Io);
+    }
     exitcode::OK
 }
 
+/// Open given URL with the browser specified in the $BROWSER environment variable.
+///
+/// Returns None if the variable isn't set (or contains no usable commands).
+/// Otherwise, the commands it lists are tried in turn until one can be run,
+/// and its exit status is returned once it finishes
+/// (which allows for terminal browsers like `w3m` or `lynx`).
+fn open_with_env_browser(url: &str) -> Option<io::Result<ExitStatus>> {
+    let browser = match env::var("BROWSER") {
+        Ok(ref b) if !b.trim().is_empty() => b.clone(),
+        _ => return None,
+    };
+    let mut result = None;
+    for argv in browser_commands(&browser, url) {
+        debug!("Running $BROWSER command: {}", argv.join(" "));
+        match Command::new(&argv[0]).args(&argv[1..]).status() {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                trace!("Browser `{}` not found", argv[0]);
+                result = Some(Err(io::Error::new(io::ErrorKind::NotFound,
+                    format!("browser `{}` not found", argv[0]))));
+            },
+            status => return Some(status),
+        }
+    }
+    result
+}
+
+/// Build the command lines for opening given URL with browsers from $BROWSER.
+///
+/// As per the usual convention, $BROWSER may contain multiple commands
+/// separated with colons, and any `%s` in their arguments is replaced by the URL.
+/// If there is no `%s`, the URL is passed as the last argument.
+fn browser_commands(browser: &str, url: &str) -> Vec<Vec<String>> {
+    browser.split(':')
+        .filter_map(|cmd| shlex::split(cmd))
+        .filter(|argv| !argv.is_empty())
+        .map(|argv| {
+            let has_placeholder = argv.iter().any(|a| a.contains("%s"));
+            let mut argv: Vec<_> = argv.into_iter()
+                .map(|a| a.replace("%s", url)).collect();
+            if !has_placeholder {
+                argv.push(url.to_owned());
+            }
+            argv
+        })
+        .collect()
+}
+
+/// Whether there is (most likely) a web browser that URLs can be opened in.
+///
+/// On Unix systems other than macOS, this requires either a graphical session
+/// or an explicitly configured $BROWSER.
+fn has_browser() -> bool {
+    if cfg!(any(windows, target_os = "macos")) {
+        return true;
+    }
+    ["BROWSER", "DISPLAY", "WAYLAND_DISPLAY"].iter()
+        .any(|v| env::var_os(v).map(|val| !val.is_empty()).unwrap_or(false))
+}
+
 
-/// Show summary information about the gist.
-pub fn show_gist_info(gist: &Gist) -> ExitCode {
+/// Show summary information about the gist by writing it to given output.
+/// If `data` is non-empty, only the selected pieces of information are shown.
+/// Values of the pieces of information in `redact` are masked (e.g. for sharing the output).
+/// If `offline` is true, the gist's host isn't asked for anything,
+/// so only the information available from the gist's local copy is shown.
+/// If `colors` is true, the information labels & URLs are highlighted.
+/// If `relative_time` is true, dates & times are shown relative to now.
+pub fn show_gist_info(gist: &Gist, data: &[Datum], redact: &[Datum], offline: bool,
+                      colors: bool, relative_time: bool, output: &mut Write) -> ExitCode {
     trace!("Obtaining information on {:?}", gist);
-    match gist.uri.host().gist_info(gist) {
+    let info = if offline {
+        // Only what's known from the local copy, without asking the host.
+        let info = gist.local_info().select(data);
+        Ok(if info.len() > 0 { Some(info) } else { None })
+    } else {
+        gist.uri.host().gist_info(gist, data)
+    };
+    match info {
         Ok(Some(info)) => {
             debug!("Successfully obtained {} piece(s) of information on {:?}",
                 info.len(), gist);
-            print!("{}", info);
+            let info = info.redact(redact);
+            let display = info.display(colors).relative_time(relative_time);
+            if let Err(e) = write!(output, "{}", display) {
+                error!("Failed to output information about {:?}: {}", gist, e);
+                return exit_code_for(Failure::Io);
+            }
             exitcode::OK
         },
         Ok(None) => {
             warn!("No information available about {:?}", gist);
-            exitcode::UNAVAILABLE
+            exit_code_for(Failure::Host)
         },
         Err(e) => {
             error!("Failed to obtain information about {:?}: {}", gist, e);
-            exitcode::UNAVAILABLE
+            exit_code_for(Failure::Host)
+        },
+    }
+}
+
+
+/// Save the content of the gist into given file, reading it directly from
+/// the gist's host if possible. Unless `force` is true, the file mustn't exist.
+///
+/// Unlike a regular fetch, this doesn't make the file executable
+/// nor link it as the gist's binary, so it's suitable for gists that aren't scripts.
+pub fn download_gist(gist: &Gist, path: &Path, force: bool) -> ExitCode {
+    if !force && fs::symlink_metadata(path).is_ok() {
+        error!("File {} already exists (use --force to overwrite it)", path.display());
+        return exit_code_for(Failure::Usage);
+    }
+
+    trace!("Downloading {:?} into {}", gist, path.display());
+    match gist.uri.host().download_gist_to(gist, path) {
+        Ok(byte_count) => {
+            debug!("Saved {} byte(s) of gist {} into {}", byte_count, gist.uri, path.display());
+            exitcode::OK
+        },
+        Err(e) => {
+            error!("Failed to save gist {} into {}: {}", gist.uri, path.display(), e);
+            exit_code_for(Failure::Io)
         },
     }
 }
 
+
+/// Copy all the files of a (downloaded) gist into given directory.
+/// Unless `force` is true, the directory has to be empty (if it exists).
+pub fn export_gist(gist: &Gist, dir: &Path, force: bool) -> ExitCode {
+    if !gist.is_local() {
+        error!("Gist {} is not available locally", gist.uri);
+        return exit_code_for(Failure::NotLocal);
+    }
+    if !force {
+        match fs::read_dir(dir) {
+            Ok(mut entries) => if entries.next().is_some() {
+                error!("Directory {} is not empty (use --force to export there anyway)",
+                    dir.display());
+                return exit_code_for(Failure::Usage);
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => {
+                error!("Failed to access directory {}: {}", dir.display(), e);
+                return exit_code_for(Failure::Io);
+            },
+        }
+    }
+
+    let files = match gist_files(gist) {
+        Ok(files) => files,
+        Err(e) => {
+            error!("Failed to list the files of gist {}: {}", gist.uri, e);
+            return exit_code_for(Failure::Io);
+        },
+    };
+    if let Err(e) = fs::create_dir_all(dir) {
+        error!("Failed to create directory {}: {}", dir.display(), e);
+        return exit_code_for(Failure::Io);
+    }
+    for file in &files {
+        let target = dir.join(file.file_name().unwrap());
+        trace!("Copying {} to {}", file.display(), target.display());
+        if let Err(e) = fs::copy(file, &target) {
+            error!("Failed to copy {} of gist {}: {}", file.display(), gist.uri, e);
+            return exit_code_for(Failure::Io);
+        }
+    }
+    debug!("Exported {} file(s) of gist {} to {}", files.len(), gist.uri, dir.display());
+    exitcode::OK
+}
+
+/// List the files of a downloaded gist.
+fn gist_files(gist: &Gist) -> io::Result<Vec<PathBuf>> {
+    let executable = try!(binary_source(&gist.binary_path()));
+
+    // Gists that have a directory of their own (like the Git repositories of GitHub gists,
+    // or multi-file glot.io gists) may have many files in it.
+    // Otherwise it's just the single file.
+    let dir = executable.parent().map(Path::to_owned);
+    let dir = match dir {
+        Some(ref dir) if is_gist_dir(gist, dir) => dir.clone(),
+        _ => return Ok(vec![executable]),
+    };
+    let mut files = vec![];
+    for entry in try!(fs::read_dir(&dir)) {
+        let path = try!(entry).path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether given directory (containing the gist's executable) belongs to that gist alone,
+/// as opposed to being shared with other gists of the same host.
+fn is_gist_dir(gist: &Gist, dir: &Path) -> bool {
+    // GitHub gists are cloned into a directory named after their ID,
+    // which isn't necessarily the gist's path if the ID is unknown.
+    dir.join(".git").is_dir()
+        || gist.path().canonicalize().map(|p| p == dir).unwrap_or(false)
+}
+
+
+/// Remove the local copy of the gist.
+/// If `remote` is true, the gist is deleted from its remote host first
+/// (after the user confirms it).
+///
+/// If `dry_run` is true, nothing is deleted; instead, the paths that would be removed
+/// (and the URL of the remote gist, if applicable) are written to given output.
+pub fn purge_gist(gist: &Gist, remote: bool, dry_run: bool, output: &mut Write) -> ExitCode {
+    let is_local = gist.is_local();
+    if !is_local && !remote {
+        error!("Gist {} is not available locally", gist.uri);
+        return exit_code_for(Failure::NotLocal);
+    }
+
+    if dry_run {
+        if let Err(e) = show_purged_paths(gist, is_local, remote, output) {
+            error!("Failed to determine what would be removed for gist {}: {}", gist.uri, e);
+            return exit_code_for(Failure::Io);
+        }
+        return exitcode::OK;
+    }
+
+    if remote {
+        // Don't ask to confirm a deletion that's bound to fail anyway.
+        if let Err(e) = gist.uri.host().check_remote_deletion(gist) {
+            error!("Cannot delete gist {} from {}: {}", gist.uri, gist.uri.host().name(), e);
+            return exit_code_for(Failure::Host);
+        }
+        match confirm_remote_deletion(gist) {
+            Ok(true) => {},
+            Ok(false) => {
+                debug!("Deletion of gist {} not confirmed -- exiting.", gist.uri);
+                return exit_code_for(Failure::Cancelled);
+            },
+            Err(e) => {
+                error!("Failed to confirm deletion of gist {}: {}", gist.uri, e);
+                return exit_code_for(Failure::Io);
+            },
+        }
+        if let Err(e) = gist.uri.host().delete_remote_gist(gist) {
+            error!("Failed to delete gist {} from {}: {}",
+                gist.uri, gist.uri.host().name(), e);
+            return exit_code_for(Failure::Host);
+        }
+    }
+
+    if is_local {
+        if let Err(e) = local_gist_paths(gist)
+                .and_then(|(b, s)| remove_local_gist(&b, &s, &gist.meta_path())) {
+            error!("Failed to remove local copy of gist {}: {}", gist.uri, e);
+            return exit_code_for(Failure::Io);
+        }
+        debug!("Local copy of gist {} removed", gist.uri);
+    }
+    exitcode::OK
+}
+
+/// Ask the user to confirm the deletion of a gist from its remote host.
+fn confirm_remote_deletion(gist: &Gist) -> io::Result<bool> {
+    try!(write!(&mut io::stderr(), "Delete gist {} from {}? This cannot be undone. [{}/N]: ",
+        gist.uri, gist.uri.host().name(), YES));
+    let mut answer = String::with_capacity(YES.len());
+    try!(io::stdin().read_line(&mut answer));
+    Ok(answer.trim().to_lowercase() == YES)
+}
+
+/// Write what purging the gist would remove: the paths of its local copy
+/// and/or the URL of the remote gist.
+fn show_purged_paths(gist: &Gist, is_local: bool, remote: bool,
+                     output: &mut Write) -> io::Result<()> {
+    if remote {
+        let host = gist.uri.host();
+        let url = host.gist_url(gist).unwrap_or_else(|_| gist.uri.to_string());
+        try!(writeln!(output, "{}", url));
+    }
+    if is_local {
+        let (binary, source) = try!(local_gist_paths(gist));
+        try!(writeln!(output, "{}", binary.display()));
+        try!(writeln!(output, "{}", source.display()));
+    }
+    Ok(())
+}
+
+/// Determine the paths that make up the local copy of a gist:
+/// its binary, and its source (either a single file, or a whole directory).
+fn local_gist_paths(gist: &Gist) -> io::Result<(PathBuf, PathBuf)> {
+    let binary = gist.binary_path();
+    let executable = try!(binary_source(&binary));
+    let gists_dir = try!(GISTS_DIR.canonicalize());
+    if !executable.starts_with(&gists_dir) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "binary of gist {} points outside of the gists directory: {}",
+            gist.uri, executable.display())));
+    }
+
+    // Gists that are Git repositories (like the GitHub ones) occupy
+    // a whole directory that needs to be removed. Otherwise it's just the single file.
+    let source = match executable.parent() {
+        Some(dir) if dir.join(".git").is_dir() => dir.to_owned(),
+        _ => executable.clone(),
+    };
+    Ok((binary, source))
+}
+
+/// Remove the gist's binary, the local copy of its source,
+/// and the gist info stored at `meta_path` (if any).
+fn remove_local_gist(binary: &Path, source: &Path, meta_path: &Path) -> io::Result<()> {
+    trace!("Removing gist binary: {}", binary.display());
+    try!(unlink_binary(binary));
+
+    if source.is_dir() {
+        trace!("Removing gist repository: {}", source.display());
+        try!(fs::remove_dir_all(source));
+    } else {
+        trace!("Removing gist file: {}", source.display());
+        try!(fs::remove_file(source));
+    }
+
+    if meta_path.exists() {
+        trace!("Removing stored gist info: {}", meta_path.display());
+        try!(fs::remove_file(meta_path));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::browser_commands;
+
+    const URL: &'static str = "https://example.com/gist";
+
+    #[test]
+    fn browser_url_appended() {
+        assert_eq!(vec![vec!["w3m".to_owned(), URL.to_owned()]],
+                   browser_commands("w3m", URL));
+    }
+
+    #[test]
+    fn browser_url_substituted() {
+        assert_eq!(vec![vec!["lynx".to_owned(), "-accept_all_cookies".to_owned(),
+                             format!("--url={}", URL)]],
+                   browser_commands("lynx -accept_all_cookies '--url=%s'", URL));
+    }
+
+    #[test]
+    fn browser_multiple_commands() {
+        let cmds = browser_commands("firefox:: elinks", URL);
+        assert_eq!(2, cmds.len());
+        assert_eq!(vec!["firefox".to_owned(), URL.to_owned()], cmds[0]);
+        assert_eq!(vec!["elinks".to_owned(), URL.to_owned()], cmds[1]);
+    }
+}
@@ -1,9 +1,21 @@
 //! Module implementing program commands.
 
+mod doctor;
 mod gist;
 mod non_gist;
+mod pager;
 mod run;
 
+pub use self::doctor::*;
 pub use self::gist::*;
 pub use self::non_gist::*;
+pub use self::pager::with_pager;
 pub use self::run::*;
+
+
+/// Terminator of a single entry (like gist URI or path) in the output of commands.
+/// This is a newline, or NUL character if `nul` is true (for use with `xargs -0` etc.).
+#[inline]
+fn entry_terminator(nul: bool) -> &'static str {
+    if nul { "\0" } else { "\n" }
+}
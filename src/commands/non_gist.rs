@@ -1,18 +1,303 @@
 //! Module implementing commands that do not operate on gists.
 
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::SystemTime;
+
 use exitcode::{self, ExitCode};
+use regex::Regex;
+use serde_json::{self, Value as Json};
+use serde_json::builder::{ArrayBuilder, ObjectBuilder};
 
-use hosts::HOSTS;
+use failure::{exit_code_for, Failure};
+use gist::{self, Datum, Gist, Uri};
+use hosts::{Host, HOSTS};
+use super::entry_terminator;
 
 
-pub fn list_hosts() -> ExitCode {
-    if !HOSTS.is_empty() {
-        let longest_id_len = HOSTS.keys().map(|k| k.len()).max().unwrap();
-        for host in HOSTS.values() {
-            // TODO: display the URL format of the gist host
-            println!("{:id_width$} :: {}",
-                host.id(), host.name(), id_width=longest_id_len);
+/// Write the list of locally available gists to given output.
+///
+/// By default, this is a list of gist URIs, one per line
+/// (or separated by NUL characters if `print0` is true).
+/// If `host_prefix` is false, the URIs are written without their `host:` prefix.
+/// If `json` is true, the output is a JSON array of objects describing the gists.
+pub fn list_gists(json: bool, print0: bool, host_prefix: bool, output: &mut Write) -> ExitCode {
+    let gists = match gist::unignored_local_gists() {
+        Ok(gists) => gists,
+        Err(e) => {
+            error!("Failed to list local gists: {}", e);
+            return exit_code_for(Failure::Io);
+        },
+    };
+    debug!("Found {} local gist(s)", gists.len());
+
+    let result = if json { write_gists_json(&gists, output) }
+                 else { write_gists_plain(&gists, entry_terminator(print0), host_prefix, output) };
+    if let Err(e) = result {
+        error!("Failed to output the list of local gists: {}", e);
+        return exit_code_for(Failure::Io);
+    }
+    exitcode::OK
+}
+
+/// Write the URIs of given gists, each followed by the `terminator`.
+fn write_gists_plain(gists: &[Gist], terminator: &str, host_prefix: bool,
+                     output: &mut Write) -> io::Result<()> {
+    for gist in gists {
+        try!(write!(output, "{}{}", format_uri(&gist.uri, host_prefix), terminator));
+    }
+    Ok(())
+}
+
+/// Write given gists as a JSON array.
+fn write_gists_json(gists: &[Gist], output: &mut Write) -> io::Result<()> {
+    let array = gists.iter()
+        .fold(ArrayBuilder::new(), |arr, gist| arr.push(gist_to_json(gist)))
+        .build();
+    try!(serde_json::to_writer(output, &array)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e)));
+    writeln!(output)
+}
+
+/// Describe a local gist as a JSON object.
+fn gist_to_json(gist: &Gist) -> Json {
+    let owner = if gist.uri.has_owner() { Some(&gist.uri.owner) } else { None };
+    ObjectBuilder::new()
+        .insert("host", &gist.uri.host_id)
+        .insert("owner", owner)
+        .insert("name", &gist.uri.name)
+        .insert("id", gist.id.as_ref())
+        .insert("path", gist.binary_path().to_str())
+        .build()
+}
+
+
+/// Search the source code of local gists for given regular expression.
+///
+/// By default, every matching line is written to the output, prefixed with
+/// the gist URI and line number (like `grep -n`).
+/// If `name_only` is true, only the URI of each matching gist is written (once),
+/// followed by a newline or NUL character (if `print0` is true).
+/// If `host_prefix` is false, the URIs are written without their `host:` prefix.
+///
+/// Up to `jobs` gists are searched at once by separate threads,
+/// but the output is always in the same order as the list of local gists.
+pub fn search_gists(pattern: &str, name_only: bool, print0: bool, host_prefix: bool,
+                    jobs: usize, output: &mut Write) -> ExitCode {
+    let regex = Regex::new(pattern).unwrap();  // Validated by the args parser.
+    let gists = match gist::unignored_local_gists() {
+        Ok(gists) => gists,
+        Err(e) => {
+            error!("Failed to list local gists: {}", e);
+            return exit_code_for(Failure::Io);
+        },
+    };
+    debug!("Searching {} local gist(s) for /{}/ using {} job(s)", gists.len(), pattern, jobs);
+
+    // Worker threads only need the URIs & paths, so that's what they get.
+    let files: Arc<Vec<(String, PathBuf)>> = Arc::new(gists.iter()
+        .map(|g| (format_uri(&g.uri, host_prefix), g.binary_path())).collect());
+    let regex = Arc::new(regex);
+    let terminator = entry_terminator(print0);
+    let next_file = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..cmp::min(jobs, files.len()) {
+        let (files, regex, next_file, tx) =
+            (files.clone(), regex.clone(), next_file.clone(), tx.clone());
+        thread::spawn(move || loop {
+            let i = next_file.fetch_add(1, Ordering::SeqCst);
+            if i >= files.len() {
+                break;
+            }
+            let (ref uri, ref path) = files[i];
+            let mut matches = vec![];
+            let result = search_file(uri, path, &regex, name_only, terminator, &mut matches)
+                .map(|_| matches);
+            if tx.send((i, result)).is_err() {
+                break;  // Searching has been aborted.
+            }
+        });
+    }
+    drop(tx);
+
+    // Results may arrive in any order, so those that come early are held back
+    // until all the previous ones have been written.
+    let mut pending = HashMap::new();
+    let mut written = 0;
+    for (i, result) in rx {
+        pending.insert(i, result);
+        while let Some(result) = pending.remove(&written) {
+            if let Err(e) = result.and_then(|matches| output.write_all(&matches)) {
+                error!("Failed to search gist {}: {}", files[written].0, e);
+                return exit_code_for(Failure::Io);
+            }
+            written += 1;
+        }
+    }
+    if written < files.len() {
+        error!("Failed to search gist {}", files[written].0);
+        return exit_code_for(Failure::Io);
+    }
+    exitcode::OK
+}
+
+/// Search a single gist file for matches of the regex, writing them to output.
+/// The `terminator` follows the gist URI when only that is written.
+fn search_file(uri: &str, path: &Path, regex: &Regex, name_only: bool, terminator: &str,
+               output: &mut Write) -> io::Result<()> {
+    let file = try!(fs::File::open(path));
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => {
+                debug!("Skipping the rest of gist {} which isn't valid text: {}", uri, e);
+                break;
+            },
+        };
+        if !regex.is_match(&line) {
+            continue;
+        }
+        if name_only {
+            return write!(output, "{}{}", uri, terminator);
+        }
+        try!(writeln!(output, "{}:{}:{}", uri, i + 1, line));
+    }
+    Ok(())
+}
+
+
+/// Write the list of gists belonging to given owner on a remote host.
+///
+/// The gists are not downloaded; each line of the output contains
+/// the URI, language, and description of a single gist.
+/// Lines are written as soon as the host returns the gists,
+/// and `max_pages` optionally limits the number of result pages fetched from it.
+/// If `since` is given, only the gists updated after that time are listed.
+/// If `host_prefix` is false, the URIs are written without their `host:` prefix.
+pub fn list_owner_gists(host: &Host, owner: &str, max_pages: Option<usize>,
+                        since: Option<SystemTime>, host_prefix: bool,
+                        output: &mut Write) -> ExitCode {
+    let gists = match host.owner_gists(owner, max_pages, since) {
+        Ok(gists) => gists,
+        Err(e) => {
+            error!("Failed to list gists of {} on {}: {}", owner, host.name(), e);
+            return exit_code_for(Failure::Host);
+        },
+    };
+    debug!("Listing gists of {} on {}...", owner, host.name());
+
+    let mut count = 0;
+    for gist in gists {
+        if let Err(e) = write_owner_gist(&gist, host_prefix, output).and_then(|_| output.flush()) {
+            error!("Failed to output the list of {}'s gists: {}", owner, e);
+            return exit_code_for(Failure::Io);
         }
+        count += 1;
+    }
+    debug!("Found {} gist(s) of {} on {}", count, owner, host.name());
+    exitcode::OK
+}
+
+/// Write a single line with remote gist's URI, language, and description.
+fn write_owner_gist(gist: &Gist, host_prefix: bool, output: &mut Write) -> io::Result<()> {
+    const URI_WIDTH: usize = 40;
+    const LANGUAGE_WIDTH: usize = 12;
+
+    let language = gist.info(Datum::Language).unwrap_or_default();
+    let description = gist.info(Datum::Description).unwrap_or_default();
+    writeln!(output, "{:uw$}  {:lw$}  {}", format_uri(&gist.uri, host_prefix),
+        language, description, uw=URI_WIDTH, lw=LANGUAGE_WIDTH)
+}
+
+/// Format a gist URI for the listings, with or without its `host:` prefix.
+fn format_uri(uri: &Uri, host_prefix: bool) -> String {
+    if host_prefix { uri.to_string() } else { uri.without_host() }
+}
+
+
+/// Create a new gist on given remote host from the contents of a local file,
+/// and write its URI and URL to given output.
+///
+/// The gist consists of that single file and is named after it.
+pub fn create_gist(host: &Host, path: &Path, description: Option<&str>, public: bool,
+                   output: &mut Write) -> ExitCode {
+    let filename = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => {
+            error!("Invalid name of the gist file: {}", path.display());
+            return exit_code_for(Failure::Usage);
+        },
+    };
+    let mut content = String::new();
+    if let Err(e) = fs::File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+        error!("Failed to read the gist file {}: {}", path.display(), e);
+        return exit_code_for(Failure::Io);
+    }
+
+    debug!("Creating a {} gist on {} from {}...",
+        if public { "public" } else { "secret" }, host.name(), path.display());
+    let gist = match host.create_gist(filename, &content, description, public) {
+        Ok(gist) => gist,
+        Err(e) => {
+            error!("Failed to create a gist on {}: {}", host.name(), e);
+            return exit_code_for(Failure::Host);
+        },
+    };
+
+    let url = match host.gist_url(&gist) {
+        Ok(url) => url,
+        Err(e) => {
+            error!("Failed to determine the URL of gist {}: {}", gist.uri, e);
+            return exit_code_for(Failure::Host);
+        },
+    };
+    if let Err(e) = writeln!(output, "{}\n{}", gist.uri, url) {
+        error!("Failed to output the created gist {}: {}", gist.uri, e);
+        return exit_code_for(Failure::Io);
     }
     exitcode::OK
 }
+
+
+/// Write the list of known gist hosts to given output,
+/// grouped by their categories (like "Git-based").
+pub fn list_hosts(output: &mut Write) -> ExitCode {
+    if let Err(e) = write_hosts(output) {
+        error!("Failed to output the list of gist hosts: {}", e);
+        return exit_code_for(Failure::Io);
+    }
+    exitcode::OK
+}
+
+/// Write the known gist hosts grouped by category, with both sorted alphabetically.
+fn write_hosts(output: &mut Write) -> io::Result<()> {
+    if HOSTS.is_empty() {
+        return Ok(());
+    }
+    let longest_id_len = HOSTS.keys().map(|k| k.len()).max().unwrap();
+
+    let mut categories: BTreeMap<&str, Vec<&Arc<Host>>> = BTreeMap::new();
+    for host in HOSTS.values() {
+        categories.entry(host.category()).or_insert_with(Vec::new).push(host);
+    }
+    for (i, (category, hosts)) in categories.iter_mut().enumerate() {
+        hosts.sort_by_key(|h| h.id());
+        if i > 0 {
+            try!(writeln!(output));
+        }
+        try!(writeln!(output, "{}:", category));
+        for host in hosts {
+            // TODO: display the URL format of the gist host
+            try!(writeln!(output, "  {:id_width$} :: {}",
+                          host.id(), host.name(), id_width=longest_id_len));
+        }
+    }
+    Ok(())
+}
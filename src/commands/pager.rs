@@ -0,0 +1,176 @@
+//! Module implementing paging of command output through `$PAGER`.
+
+use std::env;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use exitcode::ExitCode;
+use shlex;
+
+use util;
+
+
+/// Environment variable with the pager command (possibly including arguments).
+const PAGER_ENV_VAR: &'static str = "PAGER";
+
+/// Environment variable with default options for `less`.
+const LESS_ENV_VAR: &'static str = "LESS";
+/// Options for `less` used when $LESS is not set:
+/// quit if the output fits on one screen (-F), pass through ANSI colors (-R),
+/// and don't clear the screen on exit (-X).
+const LESS_DEFAULTS: &'static str = "-FRX";
+
+
+/// Invoke given output function, paging its output through `$PAGER`
+/// if it's set and the standard output is a terminal.
+///
+/// If the pager cannot be spawned, the output goes directly to stdout.
+pub fn with_pager<F>(f: F) -> ExitCode
+    where F: FnOnce(&mut Write) -> ExitCode
+{
+    if !util::stdout_isatty() {
+        return f(&mut io::stdout());
+    }
+    let mut cmd = match env::var(PAGER_ENV_VAR).ok().and_then(|p| pager_command(&p)) {
+        Some(cmd) => cmd,
+        None => return f(&mut io::stdout()),
+    };
+
+    trace!("Spawning pager: {:?}", cmd);
+    let mut child = match cmd.stdin(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to spawn pager {:?}, printing directly: {}", cmd, e);
+            return f(&mut io::stdout());
+        },
+    };
+    let exit_code = f(&mut PagerInput::new(child.stdin.as_mut().unwrap()));
+
+    // Close the pager's input so that it knows there is no more data,
+    // and let the user finish browsing before we exit.
+    drop(child.stdin.take());
+    if let Err(e) = child.wait() {
+        warn!("Failed to wait for the pager to finish: {}", e);
+    }
+    exit_code
+}
+
+
+/// Writer to the pager's input.
+///
+/// If the user quits the pager before it reads all the output (e.g. with `q` in `less`),
+/// the rest of the output is discarded, rather than treated as an error.
+struct PagerInput<W> {
+    inner: W,
+    /// Whether the pager has stopped reading its input.
+    closed: bool,
+}
+
+impl<W: Write> PagerInput<W> {
+    fn new(inner: W) -> Self {
+        PagerInput{inner: inner, closed: false}
+    }
+
+    /// Handle the result of an operation on the pager's input,
+    /// substituting given value if the pager has stopped reading it.
+    fn handle<T>(&mut self, result: io::Result<T>, closed_value: T) -> io::Result<T> {
+        match result {
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                trace!("Pager quit before reading all the output");
+                self.closed = true;
+                Ok(closed_value)
+            },
+            result => result,
+        }
+    }
+}
+
+impl<W: Write> Write for PagerInput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Ok(buf.len());
+        }
+        let result = self.inner.write(buf);
+        self.handle(result, buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        let result = self.inner.flush();
+        self.handle(result, ())
+    }
+}
+
+
+/// Build the pager command from the value of `$PAGER`.
+/// Returns None if the value is empty or cannot be parsed.
+fn pager_command(pager: &str) -> Option<Command> {
+    let argv = try_opt!(shlex::split(pager));
+    let (program, args) = try_opt!(argv.split_first());
+
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    if is_less(program) && env::var_os(LESS_ENV_VAR).is_none() {
+        cmd.env(LESS_ENV_VAR, LESS_DEFAULTS);
+    }
+    Some(cmd)
+}
+
+/// Whether given pager program is `less`.
+fn is_less(program: &str) -> bool {
+    Path::new(program).file_stem() == Some(OsStr::new("less"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use super::{is_less, pager_command, PagerInput};
+
+    /// Writer which behaves like the input of a pager that the user has quit.
+    struct QuitPager;
+
+    impl Write for QuitPager {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"))
+        }
+    }
+
+    #[test]
+    fn pager_with_args() {
+        let cmd = pager_command("less -R").unwrap();
+        assert!(format!("{:?}", cmd).contains(r#""less" "-R""#));
+        let cmd = pager_command(r#"my\ pager "--opt=a b""#).unwrap();
+        assert!(format!("{:?}", cmd).contains(r#""my pager" "--opt=a b""#));
+    }
+
+    #[test]
+    fn empty_pager() {
+        assert!(pager_command("").is_none());
+        assert!(pager_command("   ").is_none());
+        assert!(pager_command("\"unterminated").is_none());
+    }
+
+    #[test]
+    fn less_detection() {
+        assert!(is_less("less"));
+        assert!(is_less("/usr/bin/less"));
+        assert!(!is_less("more"));
+        assert!(!is_less("lesspipe"));
+    }
+
+    #[test]
+    fn quitting_pager_is_not_an_error() {
+        let mut input = PagerInput::new(QuitPager);
+        assert!(writeln!(input, "foo").is_ok());
+        assert!(input.write_all(b"bar").is_ok());
+        assert!(input.flush().is_ok());
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::borrow::Cow;
 use std::fs;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
 use std::path::Path;
 
 use itertools::Itertools;
@@ -20,6 +20,7 @@ pub fn guess_interpreter(gist: &Gist) -> Option<Interpreter> {
     guess_interpreter_for_filename(&binary_path)
         .or_else(|| gist.main_language().and_then(guess_interpreter_for_language))
         .or_else(|| guess_interpreter_for_hashbang(&binary_path))
+        .or_else(|| guess_interpreter_for_content(&binary_path))
 }
 
 
@@ -48,12 +49,9 @@ fn guess_interpreter_for_filename<P: AsRef<Path>>(binary_path: P) -> Option<Inte
 
 /// Guess an interpreter for a file written in given language.
 /// Returns the "format string" for the interpreter's command string.
-fn guess_interpreter_for_language(language: &str) -> Option<Interpreter> {
+pub fn guess_interpreter_for_language(language: &str) -> Option<Interpreter> {
     trace!("Trying to guess an interpreter for {} language", language);
-
-    // Make the language name lowercase & clean it up.
-    let mut lang = language.to_lowercase();
-    lang = LANGNAME_CLEANUP_RE.replace(&*lang, "").into_owned();
+    let lang = clean_language_name(language);
 
     // Determine the file extension for this language.
     // In some cases, the "language" may actually be an extension already,
@@ -65,8 +63,9 @@ fn guess_interpreter_for_language(language: &str) -> Option<Interpreter> {
             match LANGUAGE_MAP.get(&*lang) {
                 Some(ext) => Cow::Borrowed(ext),
                 None => {
-                    debug!("Unsupported gist language: {}", language);
-                    return None;
+                    debug!("Unsupported gist language {}, trying it as a file extension",
+                        language);
+                    lang.into()
                 },
             }
         };
@@ -77,6 +76,19 @@ fn guess_interpreter_for_language(language: &str) -> Option<Interpreter> {
     Some(interpreter.clone())
 }
 
+/// Whether given language is recognized when guessing the interpreter,
+/// either by its name or as a file extension.
+pub fn is_known_language(language: &str) -> bool {
+    let lang = clean_language_name(language);
+    LANGUAGE_MAP.contains_key(&*lang) || LANGUAGE_MAP.values().any(|&ext| ext == &*lang)
+}
+
+/// Make the language name lowercase & clean it up.
+fn clean_language_name(language: &str) -> String {
+    let lang = language.to_lowercase();
+    LANGNAME_CLEANUP_RE.replace(&*lang, "").into_owned()
+}
+
 lazy_static! {
     /// Regex matching characters in gist's language name that are irrelevant.
     static ref LANGNAME_CLEANUP_RE: Regex = Regex::new(r#"[-+#"'()&]|\[|\]"#).unwrap();
@@ -92,7 +104,7 @@ lazy_static! {
 /// we can try to repair it this way.
 ///
 /// Returns the "format string" for the interpreter's command string.
-fn guess_interpreter_for_hashbang<P: AsRef<Path>>(binary_path: P) -> Option<Interpreter> {
+pub fn guess_interpreter_for_hashbang<P: AsRef<Path>>(binary_path: P) -> Option<Interpreter> {
     let binary_path = binary_path.as_ref();
     trace!("Trying to guess an interpreter for a possible hashbang in {}",
         binary_path.display());
@@ -131,7 +143,12 @@ fn guess_interpreter_for_hashbang<P: AsRef<Path>>(binary_path: P) -> Option<Inte
     }
     let mut program = parts.remove(0);
     let mut innate_args = parts;
-    if cfg!(target_os = "linux") && innate_args.len() > 1 {
+    let is_env = program == "/usr/bin/env" || program == "/bin/env";  // TODO: also plain "env"?
+
+    // `env -S` splits the rest of the hashbang into separate args by itself,
+    // so there is nothing to warn about in this case.
+    let env_split = is_env && innate_args.first().map(|a| a.starts_with("-S")).unwrap_or(false);
+    if cfg!(target_os = "linux") && innate_args.len() > 1 && !env_split {
         // TODO: consider also warning when the whole hashbang line is longer
         // than 128 bytes on Linux because this is how much the kernel would
         // actually read if this was executed normally
@@ -141,7 +158,14 @@ fn guess_interpreter_for_hashbang<P: AsRef<Path>>(binary_path: P) -> Option<Inte
 
     // Special case for when the program is `env` in which case the actual name
     // of the interpreter is the second argument (e.g. `#!/usr/bin/env`).
-    if program == "/usr/bin/env" || program == "/bin/env" {  // TODO: also plain "env"?
+    if is_env {
+        // Skip the -S flag, which may also have the interpreter attached (`-Spython`).
+        if env_split {
+            let flag = innate_args.remove(0);
+            if flag.len() > 2 {
+                innate_args.insert(0, flag[2..].to_owned());
+            }
+        }
         if innate_args.is_empty() {
             debug!("Gist binary {} has an incorrect #!{} hashbang w/o an argument",
                 binary_path.display(), program);
@@ -176,6 +200,62 @@ fn guess_interpreter_for_hashbang<P: AsRef<Path>>(binary_path: P) -> Option<Inte
 }
 
 
+/// Guess an interpreter for a file by looking for telltale signs of a language
+/// (like `import` statements) in its content.
+///
+/// This is the least reliable way of guessing,
+/// so it's only used when the file has no extension, known language, or hashbang.
+fn guess_interpreter_for_content<P: AsRef<Path>>(binary_path: P) -> Option<Interpreter> {
+    let binary_path = binary_path.as_ref();
+    trace!("Trying to guess an interpreter for {} from its content", binary_path.display());
+
+    let mut content = Vec::new();
+    let file = try_opt!(fs::File::open(binary_path).map_err(|e| {
+        debug!("Failed to read content of gist binary {}", binary_path.display()); e
+    }).ok());
+    try_opt!(file.take(SNIFF_SIZE).read_to_end(&mut content).ok());
+
+    let extension = try_opt!(sniff_language(&String::from_utf8_lossy(&content)));
+    let interpreter = try_opt!(COMMON_INTERPRETERS.get(extension));
+    debug!("Guessed the interpreter for {} from its content as `{}`",
+        binary_path.display(), interpreter.binary());
+    Some(interpreter.clone())
+}
+
+/// Determine the language (as file extension) of given code
+/// by counting the lines that look like they were written in it.
+/// Returns None if no language has the most lines matching.
+fn sniff_language(code: &str) -> Option<&'static str> {
+    let mut counts: Vec<_> = LANGUAGE_SIGNATURES.iter()
+        .map(|&(ext, ref re)| (re.find_iter(code).count(), ext))
+        .filter(|&(count, _)| count > 0)
+        .collect();
+    counts.sort_by(|a, b| b.cmp(a));
+    match (counts.get(0), counts.get(1)) {
+        (Some(&(first, ext)), Some(&(second, _))) if first > second => Some(ext),
+        (Some(&(_, ext)), None) => Some(ext),
+        _ => None,
+    }
+}
+
+/// How much of the file to read when guessing its language from content.
+const SNIFF_SIZE: u64 = 4096;
+
+lazy_static! {
+    /// File extensions of languages, along with regexes matching lines
+    /// that are characteristic of them.
+    static ref LANGUAGE_SIGNATURES: Vec<(&'static str, Regex)> = vec![
+        ("py", Regex::new(concat!(r#"(?m)^\s*(import \w+|from [\w.]+ import |def \w+\(.*\):"#,
+                                  r#"|class \w+(\(.*\))?:|if __name__ == .__main__.:)"#)).unwrap()),
+        ("rb", Regex::new(r#"(?m)^\s*(require ['"]|puts |def \w+[^:]*$|end$)"#).unwrap()),
+        ("pl", Regex::new(r#"(?m)^\s*(use (strict|warnings);|my [$@%]\w+)"#).unwrap()),
+        ("js", Regex::new(concat!(r#"(?m)^\s*((const|let|var) \w+ = require\("#,
+                                  r#"|console\.log\(|module\.exports)"#)).unwrap()),
+        ("sh", Regex::new(r#"(?m)^\s*(echo |export \w+=|if \[|fi$|done$|esac$|set -e)"#).unwrap()),
+    ];
+}
+
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
@@ -204,6 +284,14 @@ mod tests {
         assert_eq!(Some(PYTHON.into()), guess("py"));
     }
 
+    #[test]
+    fn known_languages() {
+        assert!(is_known_language("Python"));
+        assert!(is_known_language("py"));
+        assert!(is_known_language("Bash"));
+        assert!(!is_known_language("lolwtf"));
+    }
+
     #[test]
     fn interpreter_for_hashbang() {
         let guess_interp = |hashbang: &str| {
@@ -236,5 +324,36 @@ mod tests {
             Some(Interpreter::new(PYTHON,
                 vec!["foo".into(), "bar".into(), "baz".into()])),
             guess_interp("#!/usr/bin/env python foo bar baz"));
+
+        // The -S flag of `env` is skipped.
+        assert_eq!(Some(PYTHON.into()), guess_cmd("#!/usr/bin/env -S python"));
+        assert_eq!(
+            Some(Interpreter::new(PYTHON, vec!["-u".into()])),
+            guess_interp("#!/usr/bin/env -S python -u"));
+        assert_eq!(
+            Some(Interpreter::new(PYTHON, vec!["-u".into()])),
+            guess_interp("#!/usr/bin/env -Spython -u"));
+        assert_eq!(None, guess_cmd("#!/usr/bin/env -S"));
+    }
+
+    #[test]
+    fn language_from_content() {
+        assert_eq!(None, sniff_language(""));
+        assert_eq!(None, sniff_language("Hello world"));
+        assert_eq!(Some("py"), sniff_language("import sys\n\ndef main():\n    print(sys.argv)\n"));
+        assert_eq!(Some("rb"), sniff_language("require 'json'\n\ndef greet\n  puts 'hi'\nend\n"));
+        assert_eq!(Some("sh"), sniff_language("set -e\nif [ -n \"$1\" ]; then\n  echo $1\nfi\n"));
+    }
+
+    #[test]
+    fn interpreter_for_content() {
+        let guess_cmd = |content: &str| {
+            let mut tmpfile = NamedTempFile::new().unwrap();
+            tmpfile.write_all(content.as_bytes()).unwrap();
+            guess_interpreter_for_content(tmpfile.path()).map(|i| i.command_line().to_owned())
+        };
+        assert_eq!(None, guess_cmd("lorem ipsum"));
+        assert_eq!(Some(PYTHON.into()), guess_cmd("from os import path\nprint(path.sep)\n"));
+        assert_eq!(Some("sh -- ${script} ${args}".into()), guess_cmd("echo 'Hello'\n"));
     }
 }
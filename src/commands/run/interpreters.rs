@@ -7,7 +7,7 @@ use std::fmt;
 use std::io;
 use std::os::unix::process::CommandExt;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, ExitStatus};
 
 use shlex;
 
@@ -77,6 +77,11 @@ impl From<&'static str> for Interpreter {
 }
 
 impl Interpreter {
+    #[inline]
+    pub fn command_line(&self) -> &str {
+        self.cmdline
+    }
+
     #[inline]
     pub fn binary(&self) -> &str {
         self.cmdline.split_whitespace().next().unwrap()
@@ -98,22 +103,37 @@ impl fmt::Display for Interpreter {
     }
 }
 
-#[cfg(test)]
-impl Interpreter {
-    #[inline]
-    pub fn command_line(&self) -> &str {
-        self.cmdline
-    }
-}
-
 
 /// Execute a script using given interpreter.
 ///
 /// The interpreter must be a "format string" containing placeholders
 /// for script path and arguments.
-pub fn interpreted_run<P: AsRef<Path>>(interpreter: Interpreter,
-                                       script: P, args: &[String]) -> io::Error {
+pub fn interpreted_run<P: AsRef<Path>>(interpreter: Interpreter, script: P,
+                                       args: &[String], env: &[(String, String)]) -> io::Error {
+    let script = script.as_ref();
+    let mut command = build_interpreted_command(interpreter, script, args, env);
+
+    // If everything goes well, this will not return.
+    let error = command.exec();
+    debug!("Interpreted run of {} failed: {}", script.display(), error);
+    error
+}
+
+/// Execute a script using given interpreter as a child process,
+/// and wait for it to finish.
+///
+/// Unlike interpreted_run, this returns control to the caller
+/// (e.g. so that it can clean up the script file afterwards).
+pub fn interpreted_spawn<P: AsRef<Path>>(interpreter: Interpreter, script: P,
+                                         args: &[String], env: &[(String, String)])
+                                         -> io::Result<ExitStatus> {
     let script = script.as_ref();
+    let mut command = build_interpreted_command(interpreter, script, args, env);
+    command.status()
+}
+
+fn build_interpreted_command(interpreter: Interpreter, script: &Path,
+                             args: &[String], env: &[(String, String)]) -> Command {
     let cmd = interpreter.build_invocation(script, args);
 
     // Split the final interpreter-invoking command into "argv"
@@ -122,11 +142,10 @@ pub fn interpreted_run<P: AsRef<Path>>(interpreter: Interpreter,
     let cmd_argv = shlex::split(&cmd).unwrap();
     let mut command = Command::new(&cmd_argv[0]);
     command.args(&cmd_argv[1..]);
-
-    // If everything goes well, this will not return.
-    let error = command.exec();
-    debug!("Interpreted run of {} failed: {}", script.display(), error);
-    error
+    for &(ref key, ref value) in env {
+        command.env(key, value);
+    }
+    command
 }
 
 
@@ -5,16 +5,24 @@
 
 #[cfg(unix)] mod guess;
 #[cfg(unix)] mod interpreters;
+mod requires;
 
 
+use std::io::{self, Read, Write};
 use std::path::Path;
 use std::process::Command;
 
-use exitcode::{self, ExitCode};
+use exitcode::ExitCode;
+use tempfile::NamedTempFile;
 
+use failure::{exit_code_for, Failure};
 use gist::Gist;
-use self::guess::guess_interpreter;
-use self::interpreters::interpreted_run;
+use util::{binary_source, mark_executable};
+use self::guess::{guess_interpreter, guess_interpreter_for_hashbang,
+                  guess_interpreter_for_language};
+use self::interpreters::{interpreted_run, interpreted_spawn};
+#[cfg(unix)] pub use self::guess::is_known_language;
+pub use self::requires::fetch_requirements;
 
 
 /// Run the specified gist.
@@ -23,24 +31,172 @@ use self::interpreters::interpreted_run;
 /// completely replaced by the gist binary).
 ///
 /// Otherwise, an exit code is returned.
-pub fn run_gist(gist: &Gist, args: &[String]) -> ExitCode {
+///
+/// The `env` variables are set for the gist process, in addition to the inherited ones
+/// and the ones describing the gist itself (see `gist_env`).
+/// If `guess` is false, a gist that cannot be executed directly (e.g. due to a missing
+/// hashbang) isn't fed to a guessed interpreter, and an error is returned instead.
+pub fn run_gist(gist: &Gist, args: &[String], env: &[(String, String)], guess: bool) -> ExitCode {
     let binary = gist.binary_path();
     debug!("Running gist {} ({})...", gist.uri, binary.display());
 
+    // Variables given explicitly come last, so that they can override ours.
+    let mut full_env = gist_env(gist);
+    full_env.extend(env.iter().cloned());
+
     // On Unix, we can replace the app's process completely with gist's executable
     // but on Windows, we have to run it as a child process and wait for it.
-    exec_gist(gist, &binary, args)
+    exec_gist(gist, &binary, args, &full_env, guess)
+}
+
+/// Environment variable with the directory containing the gist's files.
+const GIST_DIR_ENV_VAR: &'static str = "GISHT_GIST_DIR";
+/// Environment variable with the path to the gist's binary.
+const GIST_PATH_ENV_VAR: &'static str = "GISHT_GIST_PATH";
+/// Environment variable with the gist's URI (like `gh:Octocat/greet`).
+const GIST_URI_ENV_VAR: &'static str = "GISHT_GIST_URI";
+
+/// Environment variables that tell a running gist where it's located,
+/// so that e.g. multi-file gists can find their other files regardless of the CWD.
+pub fn gist_env(gist: &Gist) -> Vec<(String, String)> {
+    let binary = gist.binary_path();
+    // The gist's path isn't necessarily where its files are (e.g. when its ID isn't known,
+    // or when the gist is a single file), so use the directory of the file the binary links to.
+    let dir = binary_source(&binary).ok()
+        .and_then(|source| source.parent().map(Path::to_owned))
+        .unwrap_or_else(|| gist.path());
+    vec![
+        (GIST_DIR_ENV_VAR.to_owned(), dir.to_string_lossy().into_owned()),
+        (GIST_PATH_ENV_VAR.to_owned(), binary.to_string_lossy().into_owned()),
+        (GIST_URI_ENV_VAR.to_owned(), gist.uri.to_string()),
+    ]
+}
+
+
+/// Whether given language is recognized when guessing the interpreter.
+/// Interpreters are never guessed on Windows, so any language is fine there.
+#[cfg(not(unix))]
+pub fn is_known_language(_: &str) -> bool { true }
+
+/// Names of the binaries of all interpreters that can be guessed for gists.
+#[cfg(unix)]
+pub fn interpreter_binaries() -> Vec<String> {
+    let mut binaries: Vec<_> = interpreters::COMMON_INTERPRETERS.values()
+        .map(|i| i.binary().to_owned()).collect();
+    binaries.sort();
+    binaries.dedup();
+    binaries
 }
 
+/// Names of the binaries of all interpreters that can be guessed for gists.
+/// Interpreters are never guessed on Windows.
+#[cfg(not(unix))]
+pub fn interpreter_binaries() -> Vec<String> { vec![] }
 
+/// Table of the interpreters used for gists, as tuples of:
+/// file extension, languages mapped to it, and the interpreter's command line (if any).
+///
+/// Rows are sorted by extension, and include extensions that languages map to
+/// even if there is no interpreter for them.
 #[cfg(unix)]
-fn exec_gist(gist: &Gist, binary: &Path, args: &[String]) -> ExitCode {
+pub fn interpreter_table() -> Vec<(String, Vec<String>, Option<String>)> {
+    let mut extensions: Vec<&str> = interpreters::COMMON_INTERPRETERS.keys()
+        .chain(interpreters::LANGUAGE_MAP.values())
+        .cloned().collect();
+    extensions.sort();
+    extensions.dedup();
+
+    extensions.into_iter().map(|ext| {
+        let mut languages: Vec<_> = interpreters::LANGUAGE_MAP.iter()
+            .filter(|&(_, e)| *e == ext)
+            .map(|(lang, _)| lang.to_string()).collect();
+        languages.sort();
+        let cmdline = interpreters::COMMON_INTERPRETERS.get(ext)
+            .map(|i| i.command_line().to_owned());
+        (ext.to_owned(), languages, cmdline)
+    }).collect()
+}
+
+/// Table of the interpreters used for gists.
+/// Interpreters are never guessed on Windows.
+#[cfg(not(unix))]
+pub fn interpreter_table() -> Vec<(String, Vec<String>, Option<String>)> { vec![] }
+
+
+/// Run a script read from standard input, rather than a gist.
+///
+/// The script is stored in a temporary file which is removed afterwards,
+/// so it's ran as a child process rather than replacing the current one.
+/// Since there is no gist metadata, the script must have a hashbang
+/// that indicates its interpreter.
+pub fn run_stdin_script(args: &[String], env: &[(String, String)]) -> ExitCode {
+    run_script(&mut io::stdin(), None, "the script from standard input", args, env)
+}
+
+/// Run a script given inline as data (e.g. in a `data:` URI), rather than a gist.
+///
+/// Like with scripts from standard input, the data is stored in a temporary file
+/// and ran as a child process. Its interpreter is guessed from the `language`
+/// of the script if it's known, or from its hashbang otherwise.
+pub fn run_data_script(data: &[u8], language: Option<&str>,
+                       args: &[String], env: &[(String, String)]) -> ExitCode {
+    run_script(&mut io::Cursor::new(data), language, "the inline script", args, env)
+}
+
+/// Store a script read from given source in a temporary file and run it
+/// with an interpreter guessed from its `language` or hashbang.
+/// The `what` phrase describes the script in log messages.
+fn run_script(source: &mut Read, language: Option<&str>, what: &str,
+              args: &[String], env: &[(String, String)]) -> ExitCode {
+    let script = match store_script(source) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to store {}: {}", what, e);
+            return exit_code_for(Failure::Io);
+        },
+    };
+    debug!("Contents of {} stored in {}", what, script.path().display());
+
+    let interpreter = language.and_then(guess_interpreter_for_language)
+        .or_else(|| guess_interpreter_for_hashbang(script.path()));
+    let interpreter = match interpreter {
+        Some(i) => i,
+        None => {
+            error!("Failed to guess an interpreter for {}; \
+                    make sure it has a correct hashbang", what);
+            return exit_code_for(Failure::BadInput);
+        },
+    };
+    match interpreted_spawn(interpreter, script.path(), args, env) {
+        // Propagate the same exit code that the script returned.
+        Ok(status) => status.code().unwrap_or(exit_code_for(Failure::Exec)),
+        Err(e) => {
+            error!("Failed to execute {}: {}", what, e);
+            exit_code_for(Failure::Exec)
+        },
+    }
+}
+
+/// Write all of the script from given source into a new temporary file.
+fn store_script(source: &mut Read) -> io::Result<NamedTempFile> {
+    let mut file = try!(NamedTempFile::new());
+    let byte_count = try!(io::copy(source, &mut file));
+    try!(file.flush());
+    trace!("Read {} byte(s) of script", byte_count);
+    try!(mark_executable(file.path()));
+    Ok(file)
+}
+
+
+#[cfg(unix)]
+fn exec_gist(gist: &Gist, binary: &Path, args: &[String], env: &[(String, String)],
+             guess: bool) -> ExitCode {
     use std::os::unix::process::CommandExt;
 
     const ERR_NO_SUCH_FILE: i32 = 2;  // For when hashbang is present but wrong.
     const ERR_EXEC_FORMAT: i32 = 8;  // For when hashbang is absent.
 
-    let mut command = build_command(binary, args);
+    let mut command = build_command(binary, args, env);
 
     // This calls execvp() and doesn't return unless an error occurred.
     let mut error = command.exec();
@@ -51,27 +207,34 @@ fn exec_gist(gist: &Gist, binary: &Path, args: &[String]) -> ExitCode {
     // and feed it to its interpreter manually.
     if [ERR_NO_SUCH_FILE, ERR_EXEC_FORMAT].iter().any(|&e| error.raw_os_error() == Some(e)) {
         trace!("Invalid executable format of {}", binary.display());
+        if !guess {
+            error!("Gist {} cannot be ran directly as it doesn't have a proper hashbang \
+                    (and guessing its interpreter has been disabled)", gist.uri);
+            return exit_code_for(Failure::Exec);
+        }
         warn!("Couldn't run gist {} directly; it may not have a proper hashbang.", gist.uri);
         if let Some(interpreter) = guess_interpreter(gist) {
-            error = interpreted_run(interpreter, &binary, args);
+            error = interpreted_run(interpreter, &binary, args, env);
         } else {
             error!("Failed to guess an interpreter for gist {}", gist.uri);
         }
     }
     error!("Failed to execute gist {}: {}", gist.uri, error);
-    exitcode::UNAVAILABLE
+    exit_code_for(Failure::Exec)
 }
 
+/// Interpreters are never guessed on Windows, so `guess` has no effect there.
 #[cfg(not(unix))]
-fn exec_gist(gist: &Gist, binary: &Path, args: &[String]) -> ExitCode {
-    let mut command = build_command(binary, args);
+fn exec_gist(gist: &Gist, binary: &Path, args: &[String], env: &[(String, String)],
+             _guess: bool) -> ExitCode {
+    let mut command = build_command(binary, args, env);
 
     let mut run = match command.spawn() {
         Ok(r) => r,
         Err(e) => {
             error!("Failed to execute gist {} through its binary {}: {}",
                 gist.uri, binary.display(), e);
-            return exitcode::TEMPFAIL;
+            return exit_code_for(Failure::Exec);
         }
     };
 
@@ -80,18 +243,56 @@ fn exec_gist(gist: &Gist, binary: &Path, args: &[String]) -> ExitCode {
         Ok(es) => es,
         Err(e) => {
             error!("Failed to obtain status code for gist {}: {}", gist.uri, e);
-            return exitcode::TEMPFAIL;
+            return exit_code_for(Failure::Exec);
         },
     };
-    exit_status.code().unwrap_or(exitcode::UNAVAILABLE)
+    exit_status.code().unwrap_or(exit_code_for(Failure::Exec))
 }
 
 
 #[inline]
-fn build_command(binary: &Path, args: &[String]) -> Command {
+fn build_command(binary: &Path, args: &[String], env: &[(String, String)]) -> Command {
     let mut command = Command::new(&binary);
     command.args(args);
+    for &(ref key, ref value) in env {
+        command.env(key, value);
+    }
 
     trace!("About to execute {:?}", command);
     command
 }
+
+
+#[cfg(test)]
+mod tests {
+    use gist::{Gist, Uri};
+    use super::{GIST_DIR_ENV_VAR, GIST_PATH_ENV_VAR, GIST_URI_ENV_VAR, gist_env};
+    #[cfg(unix)] use super::interpreter_table;
+
+    #[test]
+    fn env_describes_gist() {
+        let gist = Gist::new(Uri::from_name("gh", "Octocat/greet").unwrap(), "abc123");
+        let env = gist_env(&gist);
+        let value = |key| env.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| v.clone());
+
+        assert!(value(GIST_DIR_ENV_VAR).is_some());
+        assert_eq!(Some(gist.binary_path().to_string_lossy().into_owned()),
+                   value(GIST_PATH_ENV_VAR));
+        assert_eq!(Some(gist.uri.to_string()), value(GIST_URI_ENV_VAR));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn interpreters_tabulated() {
+        let table = interpreter_table();
+        let row = |ext| table.iter().find(|&&(ref e, _, _)| e == ext).cloned();
+
+        let (_, languages, cmdline) = row("py").expect("no row for `py`");
+        assert_eq!(vec!["python".to_owned()], languages);
+        assert!(cmdline.unwrap().starts_with("python "));
+
+        let (_, languages, cmdline) = row("rs").expect("no row for `rs`");
+        assert_eq!(vec!["rust".to_owned()], languages);
+        assert_eq!(None, cmdline);
+    }
+}
@@ -0,0 +1,169 @@
+//! Module handling the gists that a gist requires (depends on),
+//! as declared in its `gisht-requires:` comments.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::str::FromStr;
+
+use exitcode::ExitCode;
+
+use failure::{exit_code_for, Failure};
+use gist::{Gist, Uri};
+use hosts::FetchMode;
+
+
+/// Directive that declares the gists which a gist requires.
+const DIRECTIVE: &'static str = "gisht-requires:";
+
+/// How many lines at the beginning of a gist are scanned for the directives.
+const MAX_DIRECTIVE_LINES: usize = 64;
+
+
+/// Fetch the gists required by given gist, as well as those required by them, etc.
+///
+/// Only `max_depth` levels of requirements are followed;
+/// going any deeper is an error, as is a cycle of gists requiring each other.
+pub fn fetch_requirements(gist: &Gist, mode: FetchMode, max_depth: usize) -> Result<(), ExitCode> {
+    if max_depth == 0 {
+        trace!("Not fetching gists required by {}", gist.uri);
+        return Ok(());
+    }
+    let mut chain = vec![gist.uri.clone()];
+    let mut fetched = vec![];
+    fetch_requirements_of(gist, mode, max_depth, &mut chain, &mut fetched)
+}
+
+/// Recursive part of `fetch_requirements`.
+///
+/// `chain` holds the URIs of gists that led to this one (including itself),
+/// while `fetched` are all the required gists fetched so far.
+fn fetch_requirements_of(gist: &Gist, mode: FetchMode, max_depth: usize,
+                         chain: &mut Vec<Uri>, fetched: &mut Vec<Uri>) -> Result<(), ExitCode> {
+    let requirements = try!(read_requirements(gist).map_err(|err| {
+        error!("Failed to read the requirements of gist {}: {}", gist.uri, err);
+        exit_code_for(Failure::Io)
+    }));
+    if requirements.is_empty() {
+        return Ok(());
+    }
+    if chain.len() > max_depth {
+        error!("Gists required by {} exceed the maximum depth of {}: {}",
+            chain[0], max_depth, format_chain(chain, &gist.uri));
+        return Err(exit_code_for(Failure::BadInput));
+    }
+
+    for req in requirements {
+        let uri = try!(Uri::from_str(&req).map_err(|err| {
+            error!("Invalid gist `{}` required by {}: {}", req, gist.uri, err);
+            exit_code_for(Failure::BadInput)
+        }));
+        let required = try!(resolve_requirement(Gist::from_uri(uri)));
+
+        if chain.contains(&required.uri) {
+            error!("Cycle of required gists: {}", format_chain(chain, &required.uri));
+            return Err(exit_code_for(Failure::BadInput));
+        }
+        if fetched.contains(&required.uri) {
+            continue;
+        }
+
+        debug!("Fetching gist {} required by {}...", required.uri, gist.uri);
+        if let Err(err) = required.uri.host().fetch_gist(&required, mode.clone()) {
+            error!("Failed to download/update gist {} required by {}: {}",
+                required.uri, gist.uri, err);
+            return Err(exit_code_for(Failure::Io));
+        }
+        fetched.push(required.uri.clone());
+
+        chain.push(required.uri.clone());
+        try!(fetch_requirements_of(&required, mode.clone(), max_depth, chain, fetched));
+        chain.pop();
+    }
+    Ok(())
+}
+
+/// Resolve a required gist given by a host-specific shorthand (like just the gist ID).
+fn resolve_requirement(gist: Gist) -> Result<Gist, ExitCode> {
+    if gist.is_local() {
+        return Ok(gist);
+    }
+    match gist.uri.host().resolve_shorthand(&gist) {
+        Some(Ok(resolved)) => Ok(resolved),
+        Some(Err(err)) => {
+            error!("Error asking {} to resolve required gist {}: {}",
+                gist.uri.host().name(), gist.uri, err);
+            Err(exit_code_for(Failure::Io))
+        },
+        None => Ok(gist),
+    }
+}
+
+/// Format a chain of gists that require one another, ending with `last`.
+fn format_chain(chain: &[Uri], last: &Uri) -> String {
+    let mut parts: Vec<_> = chain.iter().map(|u| u.to_string()).collect();
+    parts.push(last.to_string());
+    parts.join(" -> ")
+}
+
+
+/// Read the URIs of gists that a downloaded gist declares as required.
+fn read_requirements(gist: &Gist) -> io::Result<Vec<String>> {
+    let file = try!(fs::File::open(gist.binary_path()));
+    let mut result = vec![];
+    for line in BufReader::new(file).lines().take(MAX_DIRECTIVE_LINES) {
+        let line = match line {
+            Ok(l) => l,
+            // Gists don't have to be text; those that aren't don't require anything.
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidData => break,
+            Err(e) => return Err(e),
+        };
+        result.extend(parse_directive(&line).into_iter().map(String::from));
+    }
+    Ok(result)
+}
+
+/// Parse a single line of gist's code as a possible `gisht-requires:` directive
+/// and return the gists it lists.
+///
+/// The directive has to be in a comment, which is recognized by the line starting
+/// with some punctuation (like `#` or `//`) that is followed by the directive itself.
+/// Multiple gists can be given, separated with whitespace or commas.
+fn parse_directive(line: &str) -> Vec<&str> {
+    let index = match line.find(DIRECTIVE) {
+        Some(i) => i,
+        None => return vec![],
+    };
+    let prefix = line[..index].trim();
+    let is_comment = !prefix.is_empty()
+        && prefix.chars().all(|c| !c.is_alphanumeric());
+    if !is_comment {
+        return vec![];
+    }
+    line[index + DIRECTIVE.len()..]
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::parse_directive;
+
+    #[test]
+    fn directive_in_comments() {
+        assert_eq!(vec!["gh:me/lib"], parse_directive("# gisht-requires: gh:me/lib"));
+        assert_eq!(vec!["gh:me/lib"], parse_directive("  //gisht-requires:gh:me/lib  "));
+        assert_eq!(vec!["gh:me/lib", "pb:abc", "sp:xyz"],
+                   parse_directive("-- gisht-requires: gh:me/lib, pb:abc sp:xyz"));
+    }
+
+    #[test]
+    fn directive_outside_of_comments() {
+        let empty: Vec<&str> = vec![];
+        assert_eq!(empty, parse_directive("gisht-requires: gh:me/lib"));
+        assert_eq!(empty, parse_directive("echo gisht-requires: gh:me/lib"));
+        assert_eq!(empty, parse_directive("# gisht requires gh:me/lib"));
+        assert_eq!(empty, parse_directive("# gisht-requires:"));
+    }
+}
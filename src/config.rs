@@ -0,0 +1,581 @@
+//! Module handling the configuration file(s).
+//!
+//! Configuration is read from several layers of files, in increasing order of precedence:
+//!
+//! * `~/.gisht/config.toml`
+//! * `$XDG_CONFIG_HOME/gisht/config.toml` (or `~/.config/gisht/config.toml`) on Unix,
+//!   `%APPDATA%\gisht\config.toml` on Windows
+//! * the file given by `$GISHT_CONFIG`
+//! * the file given by the `--config` flag
+//!
+//! Values from later layers override those from earlier ones, key by key.
+//! Only the first two layers are optional; explicitly given files have to exist.
+//!
+//! Some settings can also be given through environment variables
+//! (like `$GISHT_HOME` for `home`), which override any of the files.
+//!
+//! The files use a simple subset of TOML: `[section]` headers, `key = value` pairs
+//! (where value is a string, number, or boolean), and `#` comments.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use antidote::RwLock;
+
+use ::NAME;
+
+
+/// Environment variable with the path to a configuration file.
+const CONFIG_ENV_VAR: &'static str = "GISHT_CONFIG";
+
+/// Name of the configuration file inside configuration directories.
+const CONFIG_FILE: &'static str = "config.toml";
+
+/// Environment variable with the path to the application directory.
+const HOME_ENV_VAR: &'static str = "GISHT_HOME";
+
+/// Default file mode of gist executables.
+const DEFAULT_EXEC_MODE: u32 = 0o755;
+
+/// Default time (in seconds) for which the gists resolved from browser URLs are cached.
+const DEFAULT_URL_CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Environment variables that override configuration values, along with their keys.
+const ENV_VARS: &'static [(&'static str, &'static str)] = &[
+    (HOME_ENV_VAR, "home"),
+    ("GISHT_USER_AGENT", "user_agent"),
+    ("GISHT_NO_SYMLINK", "no_symlink"),
+    ("GISHT_TIMEOUT", "timeout"),
+    ("GISHT_EXEC_MODE", "exec_mode"),
+    ("GISHT_LOCAL_DIR", "local.dir"),
+    ("GITHUB_TOKEN", "github.token"),
+];
+
+
+/// Configuration of the application, merged from all the configuration files.
+///
+/// Values are kept as strings under their full, dotted keys
+/// (e.g. `interpreters.py` for the `py` key in the `[interpreters]` section).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    values: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Application directory, i.e. `home` (or $GISHT_HOME), or `~/.gisht` by default.
+    pub fn app_dir(&self) -> PathBuf {
+        self.get("home").map(PathBuf::from).unwrap_or_else(default_app_dir)
+    }
+
+    /// User-Agent for outgoing HTTP requests, i.e. `user_agent` (or $GISHT_USER_AGENT).
+    pub fn user_agent(&self) -> Option<&str> {
+        self.get("user_agent")
+    }
+
+    /// Whether gist binaries should be copies of gist files rather than symlinks,
+    /// i.e. `no_symlink` (or $GISHT_NO_SYMLINK).
+    pub fn no_symlink(&self) -> bool {
+        self.get_bool("no_symlink")
+    }
+
+    /// File mode that gist executables are given, i.e. `exec_mode` (or $GISHT_EXEC_MODE)
+    /// as an octal number, or 755 by default.
+    /// Invalid modes are reported and then ignored.
+    pub fn exec_mode(&self) -> u32 {
+        let value = match self.get("exec_mode") {
+            Some(v) => v,
+            None => return DEFAULT_EXEC_MODE,
+        };
+        parse_exec_mode(value).unwrap_or_else(|| {
+            warn!("Invalid file mode for `exec_mode` in configuration: {}", value);
+            DEFAULT_EXEC_MODE
+        })
+    }
+
+    /// Whether redirects from https:// to plain http:// URLs are followed,
+    /// i.e. `allow_http_redirects`.
+    /// These downgrade HTTPS requests, so they aren't followed by default.
+    pub fn allow_http_redirects(&self) -> bool {
+        self.get_bool("allow_http_redirects")
+    }
+
+    /// How long the gists that browser URLs have resolved into are cached,
+    /// i.e. `url_cache_ttl` in seconds, or a day by default.
+    pub fn url_cache_ttl(&self) -> Duration {
+        self.get_secs("url_cache_ttl")
+            .unwrap_or_else(|| Duration::from_secs(DEFAULT_URL_CACHE_TTL_SECS))
+    }
+
+    /// Timeout for establishing HTTP connections, i.e. `connect_timeout`,
+    /// or the general `timeout` (or $GISHT_TIMEOUT) if that's not set.
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.get_secs("connect_timeout").or_else(|| self.get_secs("timeout"))
+    }
+
+    /// Timeout for reading HTTP responses, i.e. `read_timeout`,
+    /// or the general `timeout` (or $GISHT_TIMEOUT) if that's not set.
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.get_secs("read_timeout").or_else(|| self.get_secs("timeout"))
+    }
+
+    /// Directory with the user's own scripts that are available as `local:` gists,
+    /// i.e. `local.dir` (or $GISHT_LOCAL_DIR).
+    pub fn local_dir(&self) -> Option<PathBuf> {
+        self.get("local.dir").map(PathBuf::from)
+    }
+
+    /// GitHub personal access token, i.e. `github.token` (or $GITHUB_TOKEN).
+    pub fn github_token(&self) -> Option<&str> {
+        self.get("github.token")
+    }
+
+    /// Whether GitHub gists should be checked for changes on the remote
+    /// before deciding to update them, i.e. `github.check_remote`.
+    pub fn github_check_remote(&self) -> bool {
+        self.get_bool("github.check_remote")
+    }
+}
+
+impl Config {
+    /// Retrieve the configuration value under given dotted key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v as &str)
+    }
+
+    /// Retrieve the configuration value under given dotted key as a flag.
+    /// Values like `0`, `false`, or `no` mean it's off, as does the value being absent.
+    pub fn get_bool(&self, key: &str) -> bool {
+        match self.get(key) {
+            Some(v) => !["0", "false", "no", "off"].contains(&&*v.to_lowercase()),
+            None => false,
+        }
+    }
+
+    /// Retrieve the configuration value under given dotted key
+    /// as a positive duration in whole seconds.
+    /// Invalid values are reported and then ignored.
+    pub fn get_secs(&self, key: &str) -> Option<Duration> {
+        let value = try_opt!(self.get(key));
+        match value.parse::<u64>() {
+            Ok(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+            _ => {
+                warn!("Invalid number of seconds for `{}` in configuration: {}", key, value);
+                None
+            },
+        }
+    }
+
+    /// Retrieve all the keys & values from given section,
+    /// with keys stripped of the section name.
+    // (Sections are for the features that are configurable through the file).
+    #[allow(dead_code)]
+    pub fn section(&self, name: &str) -> Vec<(&str, &str)> {
+        let prefix = format!("{}.", name);
+        self.values.iter()
+            .filter(|&(k, _)| k.starts_with(&prefix))
+            .map(|(k, v)| (&k[prefix.len()..], v as &str))
+            .collect()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Merge the values of another config into this one, overriding any existing keys.
+    fn merge(&mut self, other: Config) {
+        self.values.extend(other.values);
+    }
+}
+
+
+lazy_static! {
+    /// Configuration of the application, as loaded at startup.
+    static ref CONFIG: RwLock<Arc<Config>> = RwLock::new(Arc::new(Config::default()));
+}
+
+/// Retrieve the application configuration, as loaded at startup.
+pub fn current() -> Arc<Config> {
+    CONFIG.read().clone()
+}
+
+/// Set the configuration to be used throughout the application.
+pub fn set(config: Config) {
+    *CONFIG.write() = Arc::new(config);
+}
+
+
+/// Load the configuration from all the layers of configuration files, and merge them
+/// along with the environment variables.
+///
+/// The `explicit` file is the one given by the --config flag, if any.
+pub fn load(explicit: Option<&Path>) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    for layer in layers(explicit) {
+        let text = match read_file(&layer.path) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound && !layer.required => {
+                trace!("Config file {} doesn't exist, skipping", layer.path.display());
+                continue;
+            },
+            Err(e) => return Err(ConfigError::new(layer, ConfigErrorKind::Io(e))),
+        };
+        let layer_config = try!(parse(&text)
+            .map_err(|(line, msg)| ConfigError::new(layer.clone(),
+                                                    ConfigErrorKind::Parse(line, msg))));
+        debug!("Read {} value(s) from config file {} ({})",
+            layer_config.len(), layer.path.display(), layer.source);
+        config.merge(layer_config);
+    }
+    config.merge(from_env());
+    Ok(config)
+}
+
+/// Create the configuration from the environment variables that override it.
+/// Variables that are empty are treated as if they weren't set.
+fn from_env() -> Config {
+    let mut config = Config::default();
+    for &(var, key) in ENV_VARS {
+        if let Some(value) = env::var(var).ok().map(|v| v.trim().to_owned()) {
+            if !value.is_empty() {
+                trace!("Config value `{}` set by ${}", key, var);
+                config.values.insert(key.to_owned(), value);
+            }
+        }
+    }
+    config
+}
+
+/// A single layer of configuration, i.e. a (possible) configuration file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    /// Path to the configuration file.
+    pub path: PathBuf,
+    /// Where the path comes from, for the error messages.
+    pub source: &'static str,
+    /// Whether the file has to exist.
+    pub required: bool,
+}
+
+/// Determine the layers of configuration, from the least to the most important.
+fn layers(explicit: Option<&Path>) -> Vec<Layer> {
+    // The configured application directory isn't known yet, so only $GISHT_HOME is considered.
+    let app_dir = env_path(HOME_ENV_VAR).unwrap_or_else(default_app_dir);
+    let mut result = vec![Layer{
+        path: app_dir.join(CONFIG_FILE),
+        source: "application directory",
+        required: false,
+    }];
+    if let Some(dir) = platform_config_dir() {
+        result.push(Layer{
+            path: dir.join(*NAME).join(CONFIG_FILE),
+            source: PLATFORM_CONFIG_SOURCE,
+            required: false,
+        });
+    }
+    if let Some(path) = env_path(CONFIG_ENV_VAR) {
+        result.push(Layer{path: path, source: "$GISHT_CONFIG", required: true});
+    }
+    if let Some(path) = explicit {
+        result.push(Layer{path: path.to_owned(), source: "--config", required: true});
+    }
+    result
+}
+
+/// Description of where the platform-specific configuration directory comes from.
+#[cfg(windows)]
+const PLATFORM_CONFIG_SOURCE: &'static str = "%APPDATA%";
+#[cfg(not(windows))]
+const PLATFORM_CONFIG_SOURCE: &'static str = "$XDG_CONFIG_HOME";
+
+/// Platform-specific directory for configuration files, if any.
+#[cfg(windows)]
+fn platform_config_dir() -> Option<PathBuf> {
+    env_path("APPDATA")
+}
+#[cfg(not(windows))]
+fn platform_config_dir() -> Option<PathBuf> {
+    // As per the XDG Base Directory spec, relative paths are ignored.
+    env_path("XDG_CONFIG_HOME")
+        .and_then(|dir| if dir.is_absolute() { Some(dir) } else { None })
+        .or_else(|| env::home_dir().map(|home| home.join(".config")))
+}
+
+/// Default application directory, i.e. `~/.gisht`.
+fn default_app_dir() -> PathBuf {
+    env::home_dir().unwrap_or_else(env::temp_dir).join(&format!(".{}", *NAME))
+}
+
+/// Retrieve a path from given environment variable, treating an empty one as unset.
+fn env_path(var: &str) -> Option<PathBuf> {
+    env::var_os(var).and_then(|v| if v.is_empty() { None } else { Some(PathBuf::from(v)) })
+}
+
+fn read_file(path: &Path) -> io::Result<String> {
+    let mut text = String::new();
+    try!(fs::File::open(path).and_then(|mut f| f.read_to_string(&mut text)));
+    Ok(text)
+}
+
+
+/// Parse the text of a configuration file.
+/// In case of error, the (1-based) line number and error message is returned.
+fn parse(text: &str) -> Result<Config, (usize, String)> {
+    let mut config = Config::default();
+    let mut section = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            if !line.ends_with(']') || line.starts_with("[[") {
+                return Err((i + 1, format!("invalid section header: {}", line)));
+            }
+            section = line[1..line.len() - 1].trim().to_owned();
+            if !is_valid_key(&section) {
+                return Err((i + 1, format!("invalid section name: {}", section)));
+            }
+            continue;
+        }
+
+        let eq = try!(line.find('=')
+            .ok_or_else(|| (i + 1, format!("expected `key = value`: {}", line))));
+        let key = line[..eq].trim();
+        if !is_valid_key(key) {
+            return Err((i + 1, format!("invalid key: {}", key)));
+        }
+        let value = try!(parse_value(line[eq + 1..].trim()).map_err(|e| (i + 1, e)));
+        let key = if section.is_empty() { key.to_owned() }
+                  else { format!("{}.{}", section, key) };
+        config.values.insert(key, value);
+    }
+    Ok(config)
+}
+
+/// Strip the comment (if any) from a line of the configuration file,
+/// taking care not to treat `#` inside strings as one.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => { escaped = true; continue; },
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {},
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Whether given string is a valid (possibly dotted) key of a configuration value.
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.split('.').all(|part| {
+        !part.is_empty()
+            && part.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+/// Parse a single configuration value into its string representation.
+fn parse_value(value: &str) -> Result<String, String> {
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return Ok(value[1..value.len() - 1].to_owned());
+    }
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        return unescape(&value[1..value.len() - 1]);
+    }
+    let is_bare = !value.is_empty() && value.chars().all(|c| {
+        c.is_alphanumeric() || c == '.' || c == '-' || c == '+' || c == '_'
+    });
+    if is_bare { Ok(value.to_owned()) } else { Err(format!("invalid value: {}", value)) }
+}
+
+/// Resolve the escape sequences in a double-quoted string value.
+fn unescape(s: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            return Err(format!("unescaped quote in string: \"{}\"", s));
+        }
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(c) => return Err(format!("invalid escape sequence: \\{}", c)),
+            None => return Err(format!("unterminated string: \"{}\"", s)),
+        }
+    }
+    Ok(result)
+}
+
+/// Parse an octal file mode for gist executables, like `755` or `0o700`.
+/// The mode must only have permission bits, and let the owner read & execute the file.
+fn parse_exec_mode(value: &str) -> Option<u32> {
+    let digits = value.trim_left_matches("0o");
+    if digits.is_empty() {
+        return None;
+    }
+    let mode = try_opt!(u32::from_str_radix(digits, 8).ok());
+    if mode > 0o777 || mode & 0o500 != 0o500 {
+        return None;
+    }
+    Some(mode)
+}
+
+
+/// Error that occurred while loading one of the configuration files.
+#[derive(Debug)]
+pub struct ConfigError {
+    layer: Layer,
+    kind: ConfigErrorKind,
+}
+
+#[derive(Debug)]
+enum ConfigErrorKind {
+    Io(io::Error),
+    /// Line number & error message.
+    Parse(usize, String),
+}
+
+impl ConfigError {
+    fn new(layer: Layer, kind: ConfigErrorKind) -> Self {
+        ConfigError{layer: layer, kind: kind}
+    }
+}
+
+impl Error for ConfigError {
+    fn description(&self) -> &str { "invalid configuration file" }
+    fn cause(&self) -> Option<&Error> {
+        match self.kind {
+            ConfigErrorKind::Io(ref e) => Some(e),
+            ConfigErrorKind::Parse(..) => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.layer.path.display();
+        try!(match self.kind {
+            ConfigErrorKind::Io(ref e) =>
+                write!(fmt, "cannot read config file {} (from {}): {}",
+                    path, self.layer.source, e),
+            ConfigErrorKind::Parse(line, ref msg) =>
+                write!(fmt, "error in config file {} (from {}), line {}: {}",
+                    path, self.layer.source, line, msg),
+        });
+        write!(fmt, "\n(config files are applied in the order of: application directory, \
+                     {}, $GISHT_CONFIG, --config; later ones override earlier ones)",
+            PLATFORM_CONFIG_SOURCE)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use super::{layers, parse, Config};
+
+    #[test]
+    fn parse_values() {
+        let config = parse("# Comment\n\
+                            top = 42\n\
+                            \n\
+                            [interpreters]\n\
+                            py = \"python3 ${script} ${args}\"  # trailing comment\n\
+                            rb = 'ruby # not a comment'\n\
+                            [aliases.work]\n\
+                            deploy = \"gh:me/deploy\\n\"\n\
+                            enabled = true\n").unwrap();
+        assert_eq!(Some("42"), config.get("top"));
+        assert_eq!(Some("python3 ${script} ${args}"), config.get("interpreters.py"));
+        assert_eq!(Some("ruby # not a comment"), config.get("interpreters.rb"));
+        assert_eq!(Some("gh:me/deploy\n"), config.get("aliases.work.deploy"));
+        assert_eq!(Some("true"), config.get("aliases.work.enabled"));
+        assert_eq!(None, config.get("py"));
+        assert_eq!(vec![("py", "python3 ${script} ${args}"), ("rb", "ruby # not a comment")],
+                   config.section("interpreters"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Some(2), parse("a = 1\nb\n").err().map(|(line, _)| line));
+        assert_eq!(Some(1), parse("[unclosed\n").err().map(|(line, _)| line));
+        assert_eq!(Some(1), parse("a b = 1\n").err().map(|(line, _)| line));
+        assert_eq!(Some(3), parse("\n\nx = \"bad\\q\"\n").err().map(|(line, _)| line));
+        assert_eq!(Some(1), parse("x = not valid\n").err().map(|(line, _)| line));
+    }
+
+    #[test]
+    fn merge_overrides_keys() {
+        let mut config = parse("a = 1\nb = 2\n").unwrap();
+        config.merge(parse("b = 3\nc = 4\n").unwrap());
+        assert_eq!(parse("a = 1\nb = 3\nc = 4\n").unwrap(), config);
+        assert_eq!(Config::default(), parse("# nothing\n").unwrap());
+    }
+
+    #[test]
+    fn bool_values() {
+        let config = parse("a = true\nb = 0\nc = 'No'\nd = 1\n").unwrap();
+        assert!(config.get_bool("a"));
+        assert!(!config.get_bool("b"));
+        assert!(!config.get_bool("c"));
+        assert!(config.get_bool("d"));
+        assert!(!config.get_bool("e"));
+    }
+
+    #[test]
+    fn timeouts() {
+        let config = parse("timeout = 30\nread_timeout = 60\n").unwrap();
+        assert_eq!(Some(Duration::from_secs(30)), config.connect_timeout());
+        assert_eq!(Some(Duration::from_secs(60)), config.read_timeout());
+
+        let config = parse("connect_timeout = 0\nread_timeout = 'soon'\n").unwrap();
+        assert_eq!(None, config.connect_timeout());
+        assert_eq!(None, config.read_timeout());
+    }
+
+    #[test]
+    fn exec_modes() {
+        assert_eq!(0o755, Config::default().exec_mode());
+        assert_eq!(0o700, parse("exec_mode = 700\n").unwrap().exec_mode());
+        assert_eq!(0o750, parse("exec_mode = '0o750'\n").unwrap().exec_mode());
+        assert_eq!(0o755, parse("exec_mode = 644\n").unwrap().exec_mode());
+        assert_eq!(0o755, parse("exec_mode = 4755\n").unwrap().exec_mode());
+        assert_eq!(0o755, parse("exec_mode = 789\n").unwrap().exec_mode());
+    }
+
+    #[test]
+    fn app_dir_from_home() {
+        let config = parse("home = '/opt/gisht'\n").unwrap();
+        assert_eq!(PathBuf::from("/opt/gisht"), config.app_dir());
+        assert!(Config::default().app_dir().ends_with(".gisht"));
+    }
+
+    #[test]
+    fn explicit_layer_is_last() {
+        let path = Path::new("/tmp/explicit.toml");
+        let layers = layers(Some(path));
+        let last = layers.last().unwrap();
+        assert_eq!(path, last.path);
+        assert!(last.required);
+        assert!(!layers[0].required);
+    }
+}
@@ -0,0 +1,174 @@
+//! Module implementing transcoding of gist content into UTF-8.
+//!
+//! By default, gists are stored exactly as they've been downloaded.
+//! This is only used when the user explicitly asks for a specific source encoding.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use conv::errors::Unrepresentable;
+
+
+macro_attr! {
+    /// Text encoding that gist content can be converted from.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash,
+             IterVariants!(Encodings))]
+    pub enum Encoding {
+        /// UTF-8 (which only validates the content).
+        Utf8,
+        /// ISO-8859-1, a.k.a. Latin-1.
+        Latin1,
+        /// Windows-1252, the Western European Windows code page.
+        Windows1252,
+        /// UTF-16, little endian.
+        Utf16Le,
+        /// UTF-16, big endian.
+        Utf16Be,
+    }
+}
+
+impl Encoding {
+    /// Canonical name of the encoding, as used in command line arguments.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "latin1",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+        }
+    }
+
+    /// Alternative names that the encoding is also recognized by.
+    pub fn aliases(&self) -> &'static [&'static str] {
+        match *self {
+            Encoding::Utf8 => &["utf8"],
+            Encoding::Latin1 => &["iso-8859-1"],
+            Encoding::Windows1252 => &["cp1252"],
+            _ => &[],
+        }
+    }
+
+    /// Decode given bytes in this encoding into a string.
+    pub fn decode(&self, bytes: &[u8]) -> io::Result<String> {
+        match *self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            // Latin-1 bytes are exactly the first 256 Unicode code points.
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+            Encoding::Windows1252 => Ok(bytes.iter().map(|&b| decode_windows1252(b)).collect()),
+            Encoding::Utf16Le => decode_utf16(bytes, |pair| pair[0] as u16 | (pair[1] as u16) << 8),
+            Encoding::Utf16Be => decode_utf16(bytes, |pair| (pair[0] as u16) << 8 | pair[1] as u16),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = Unrepresentable<String>;
+
+    /// Create an Encoding from its name (or alias), as returned by Encoding::name().
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        for encoding in Encoding::iter_variants() {
+            if encoding.name() == name || encoding.aliases().contains(&name.as_str()) {
+                return Ok(encoding);
+            }
+        }
+        Err(Unrepresentable(s.to_owned()))
+    }
+}
+
+
+/// Convert the content of given file from specified encoding into UTF-8, in place.
+pub fn transcode_file<P: AsRef<Path>>(path: P, encoding: Encoding) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    {
+        let mut file = try!(fs::File::open(path));
+        try!(file.read_to_end(&mut bytes));
+    }
+    let content = try!(encoding.decode(&bytes));
+
+    let mut file = try!(fs::OpenOptions::new().write(true).truncate(true).open(path));
+    try!(file.write_all(content.as_bytes()));
+    trace!("Transcoded {} from {} to UTF-8 ({} -> {} bytes)",
+        path.display(), encoding.name(), bytes.len(), content.len());
+    Ok(())
+}
+
+
+/// Decode a single Windows-1252 byte.
+fn decode_windows1252(byte: u8) -> char {
+    // Only the 0x80-0x9F range differs from Latin-1.
+    // Bytes that are undefined there are mapped to C1 control characters, like Latin-1 does.
+    const HIGH_CHARS: [char; 32] = [
+        '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+        '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+        '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+        '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+    ];
+    match byte {
+        0x80...0x9F => HIGH_CHARS[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+/// Decode UTF-16 bytes, using given function to combine byte pairs into code units.
+/// A leading byte order mark is skipped.
+fn decode_utf16<F: Fn(&[u8]) -> u16>(bytes: &[u8], code_unit: F) -> io::Result<String> {
+    if bytes.len() % 2 != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            "UTF-16 content has an odd number of bytes"));
+    }
+    let mut units: Vec<u16> = bytes.chunks(2).map(code_unit).collect();
+    if units.first() == Some(&0xFEFF) {
+        units.remove(0);
+    }
+    String::from_utf16(&units).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use super::Encoding;
+
+    #[test]
+    fn from_str_roundtrip() {
+        for encoding in Encoding::iter_variants() {
+            assert_eq!(encoding, Encoding::from_str(encoding.name()).unwrap());
+            for &alias in encoding.aliases() {
+                assert_eq!(encoding, Encoding::from_str(alias).unwrap());
+            }
+        }
+        assert!(Encoding::from_str("ebcdic").is_err());
+    }
+
+    #[test]
+    fn decode_utf8() {
+        assert_eq!("zażółć", Encoding::Utf8.decode("zażółć".as_bytes()).unwrap());
+        assert!(Encoding::Utf8.decode(b"\xFF\xFE").is_err());
+    }
+
+    #[test]
+    fn decode_latin1() {
+        assert_eq!("café", Encoding::Latin1.decode(b"caf\xE9").unwrap());
+        assert_eq!("\u{80}", Encoding::Latin1.decode(b"\x80").unwrap());
+    }
+
+    #[test]
+    fn decode_windows1252() {
+        assert_eq!("café €5", Encoding::Windows1252.decode(b"caf\xE9 \x805").unwrap());
+        assert_eq!("\u{2018}quoted\u{2019}",
+                   Encoding::Windows1252.decode(b"\x91quoted\x92").unwrap());
+    }
+
+    #[test]
+    fn decode_utf16() {
+        assert_eq!("hi", Encoding::Utf16Le.decode(b"\xFF\xFEh\x00i\x00").unwrap());
+        assert_eq!("hi", Encoding::Utf16Be.decode(b"\x00h\x00i").unwrap());
+        assert!(Encoding::Utf16Le.decode(b"h\x00i").is_err());
+    }
+}
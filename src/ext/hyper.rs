@@ -10,7 +10,6 @@ pub mod header {
 
     use hyper;
     use hyper::header::{Header, HeaderFormat};
-    use regex::{Regex, RegexBuilder};
 
 
     /// Type of a single item in Link: header.
@@ -46,25 +45,11 @@ pub mod header {
         fn header_name() -> &'static str { "Link" }
 
         fn parse_header(raw: &[Vec<u8>]) -> hyper::Result<Link> {
-            lazy_static! {
-                static ref RE: Regex = RegexBuilder::new(r#"
-                    <(?P<url>[^>]+)>;
-                    \s*
-                    rel="(?P<rel>\w+)"
-                "#)
-                .ignore_whitespace(true)
-                .build().unwrap();
-            }
-
             let mut links = HashMap::new();
             for value in raw {
                 let value = try!(from_utf8(value).map_err(|_| hyper::Error::Header));
-                if !RE.is_match(value) {
-                    return Err(hyper::Error::Header);
-                }
-                for li_cap in RE.captures_iter(value) {
-                    let li = LinkItem{rel: li_cap["rel"].to_owned(),
-                                      url: li_cap["url"].to_owned()};
+                let items = try!(parse_link_items(value).ok_or(hyper::Error::Header));
+                for li in items {
                     let link_items = links.entry(li.rel.clone()).or_insert_with(|| vec![]);
                     link_items.push(li);
                 }
@@ -73,6 +58,81 @@ pub mod header {
         }
     }
 
+    /// Parse a single value of the Link: header into LinkItems.
+    ///
+    /// The value is a comma-separated list of `<url>; param=value; ...` entries.
+    /// Parameter values may be quoted, and parameters other than rel= are skipped.
+    /// An entry with several relation types (e.g. `rel="next last"`)
+    /// produces a separate LinkItem for each of them.
+    fn parse_link_items(value: &str) -> Option<Vec<LinkItem>> {
+        if value.trim().is_empty() {
+            return None;
+        }
+
+        let mut items = vec![];
+        let mut rest = value.trim_left();
+        while !rest.is_empty() {
+            if !rest.starts_with('<') {
+                return None;
+            }
+            let url_end = try_opt!(rest.find('>'));
+            let url = rest[1..url_end].trim();
+            rest = rest[url_end + 1..].trim_left();
+
+            // Only the first occurrence of rel= counts, as per RFC 5988.
+            let mut rels: Option<Vec<String>> = None;
+            while rest.starts_with(';') {
+                let (name, value, after) = try_opt!(parse_link_param(&rest[1..]));
+                if rels.is_none() && name.to_lowercase() == "rel" {
+                    rels = Some(value.split_whitespace().map(str::to_lowercase).collect());
+                }
+                rest = after.trim_left();
+            }
+            for rel in rels.unwrap_or_else(Vec::new) {
+                items.push(LinkItem{rel: rel, url: url.to_owned()});
+            }
+
+            if rest.starts_with(',') {
+                rest = rest[1..].trim_left();
+            } else if !rest.is_empty() {
+                return None;
+            }
+        }
+        Some(items)
+    }
+
+    /// Parse a single `name=value` parameter of a Link: header entry.
+    /// Returns the parameter name, its (unquoted) value, and the remaining input.
+    fn parse_link_param(s: &str) -> Option<(&str, String, &str)> {
+        let s = s.trim_left();
+        let name_end = s.find(|c| c == '=' || c == ';' || c == ',').unwrap_or(s.len());
+        let name = s[..name_end].trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let rest = &s[name_end..];
+        if !rest.starts_with('=') {
+            return Some((name, String::new(), rest));  // parameter without a value
+        }
+        let rest = rest[1..].trim_left();
+        if rest.starts_with('"') {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                    '"' => return Some((name, value, &rest[i + 2..])),
+                    c => value.push(c),
+                }
+            }
+            None  // unterminated quoted string
+        } else {
+            let value_end = rest.find(|c| c == ';' || c == ',').unwrap_or(rest.len());
+            Some((name, rest[..value_end].trim().to_owned(), &rest[value_end..]))
+        }
+    }
+
     impl HeaderFormat for Link {
         fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
             write!(fmt, "{}", self.0.values()
@@ -136,5 +196,59 @@ pub mod header {
             assert_eq!(2, link.items("stylesheet").len());
             assert!(link.url("stylesheet").is_none());
         }
+
+        #[test]
+        fn link_parse_github() {
+            let link = parse(concat!(
+                r#"<https://api.github.com/user/583231/gists?per_page=50&page=2>; rel="next", "#,
+                r#"<https://api.github.com/user/583231/gists?per_page=50&page=5>; rel="last""#,
+            )).unwrap();
+            assert_eq!("https://api.github.com/user/583231/gists?per_page=50&page=2",
+                link.url("next").unwrap());
+            assert_eq!("https://api.github.com/user/583231/gists?per_page=50&page=5",
+                link.url("last").unwrap());
+        }
+
+        #[test]
+        fn link_parse_extra_params() {
+            let url = "http://example.com/next";
+            let link = parse(&format!(
+                r#"<{}>; title="Next, please; thanks"; rel="next"; type="text/html""#, url
+            )).unwrap();
+            assert_eq!(url, link.url("next").unwrap());
+            assert!(link.items("title").is_empty());
+        }
+
+        #[test]
+        fn link_parse_whitespace_and_unquoted() {
+            let next_url = "http://example.com/next";
+            let prev_url = "http://example.com/prev";
+            let link = parse(&format!(
+                "  < {} > ;rel=next ,\t<{}>  ;  rel = \"prev\"  ", next_url, prev_url
+            )).unwrap();
+            assert_eq!(next_url, link.url("next").unwrap());
+            assert_eq!(prev_url, link.url("prev").unwrap());
+        }
+
+        #[test]
+        fn link_parse_multiple_rels() {
+            let url = "http://example.com/last";
+            let link = parse(&format!(r#"<{}>; rel="next LAST""#, url)).unwrap();
+            assert_eq!(url, link.url("next").unwrap());
+            assert_eq!(url, link.url("last").unwrap());
+        }
+
+        #[test]
+        fn link_parse_url_with_comma() {
+            let url = "http://example.com/search?q=a,b";
+            let link = parse(&format!(r#"<{}>; rel="next""#, url)).unwrap();
+            assert_eq!(url, link.url("next").unwrap());
+        }
+
+        #[test]
+        fn link_parse_unterminated_quote() {
+            let result = parse(r#"<http://example.com>; rel="next"#);
+            assert!(result.is_err(), "Link: with unterminated quote unexpectedly parsed");
+        }
     }
 }
@@ -0,0 +1,62 @@
+//! Module defining the categories of fatal errors & their program exit codes.
+
+use exitcode::{self, ExitCode};
+
+
+macro_attr! {
+    /// Category of an error that causes the program to exit unsuccessfully.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash,
+             IterVariants!(Failures))]
+    pub enum Failure {
+        /// Invalid command line arguments.
+        Usage,
+        /// Inconsistent configuration, e.g. of gist hosts.
+        Config,
+        /// Application directory couldn't be created.
+        AppDir,
+        /// The user declined to continue.
+        Cancelled,
+        /// The gist is required to be available locally, but it isn't.
+        NotLocal,
+        /// Input data (like a script from stdin) is invalid.
+        BadInput,
+        /// I/O error, either locally or when talking to a gist host.
+        Io,
+        /// Gist host (or another remote service) couldn't perform the operation.
+        Host,
+        /// The gist (or script) couldn't be executed.
+        Exec,
+        /// The environment (e.g. file system or network) isn't fit for the program.
+        Environment,
+    }
+}
+
+/// Return the program exit code corresponding to given category of failure.
+pub fn exit_code_for(failure: Failure) -> ExitCode {
+    match failure {
+        Failure::Usage => exitcode::USAGE,
+        Failure::Config => exitcode::CONFIG,
+        Failure::AppDir => exitcode::OSFILE,
+        Failure::Cancelled => exitcode::TEMPFAIL,
+        Failure::NotLocal => exitcode::NOINPUT,
+        Failure::BadInput => exitcode::DATAERR,
+        Failure::Io => exitcode::IOERR,
+        Failure::Host | Failure::Exec => exitcode::UNAVAILABLE,
+        Failure::Environment => exitcode::OSERR,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use exitcode;
+    use super::{exit_code_for, Failure};
+
+    #[test]
+    fn failures_are_not_successful() {
+        for failure in Failure::iter_variants() {
+            assert!(exit_code_for(failure) != exitcode::OK,
+                "Failure {:?} maps to a successful exit code", failure);
+        }
+    }
+}
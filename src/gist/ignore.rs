@@ -0,0 +1,129 @@
+//! Module handling the list of gists ignored by bulk operations.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use regex::{self, Regex};
+
+use super::Gist;
+
+
+/// List of glob patterns matching URIs of gists that should be ignored.
+///
+/// The list is read from a file where each line holds a single pattern,
+/// e.g. `gh:SomeUser/*`. Blank lines and lines starting with `#` are skipped.
+///
+/// In patterns, `*` matches any (possibly empty) sequence of characters
+/// and `?` matches any single character.
+#[derive(Debug)]
+pub struct IgnoreList {
+    patterns: Vec<Regex>,
+}
+
+impl IgnoreList {
+    /// Create an empty IgnoreList that doesn't ignore anything.
+    #[inline]
+    pub fn empty() -> Self {
+        IgnoreList{patterns: vec![]}
+    }
+
+    /// Load the IgnoreList from given file.
+    /// If the file doesn't exist, an empty list is returned.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            trace!("Ignore file {} doesn't exist", path.display());
+            return Ok(IgnoreList::empty());
+        }
+        let mut content = String::new();
+        let mut file = try!(fs::File::open(path));
+        try!(file.read_to_string(&mut content));
+        let list = IgnoreList::parse(&content);
+        debug!("Loaded {} pattern(s) from ignore file {}",
+            list.patterns.len(), path.display());
+        Ok(list)
+    }
+
+    /// Parse the content of an ignore file.
+    pub fn parse(content: &str) -> Self {
+        let patterns = content.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(glob_to_regex)
+            .collect();
+        IgnoreList{patterns: patterns}
+    }
+
+    /// Whether given gist should be ignored.
+    pub fn is_ignored(&self, gist: &Gist) -> bool {
+        let uri = gist.uri.to_string();
+        self.patterns.iter().any(|p| p.is_match(&uri))
+    }
+}
+
+
+/// Convert a glob pattern into an anchored regular expression.
+fn glob_to_regex(glob: &str) -> Regex {
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).unwrap()  // everything besides wildcards is escaped
+}
+
+
+#[cfg(test)]
+mod tests {
+    use gist::{Gist, Uri};
+    use hosts;
+    use super::IgnoreList;
+
+    const HOST_ID: &'static str = hosts::DEFAULT_HOST_ID;
+
+    fn gist(owner: &str, name: &str) -> Gist {
+        Gist::from_uri(Uri::new(HOST_ID, owner, name).unwrap())
+    }
+
+    #[test]
+    fn empty() {
+        let list = IgnoreList::parse("");
+        assert!(!list.is_ignored(&gist("JohnDoe", "foo")));
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let list = IgnoreList::parse("# comment\n\n   \n#gh:JohnDoe/foo\n");
+        assert!(!list.is_ignored(&gist("JohnDoe", "foo")));
+    }
+
+    #[test]
+    fn exact_uri() {
+        let list = IgnoreList::parse(&format!("{}:JohnDoe/foo\n", HOST_ID));
+        assert!(list.is_ignored(&gist("JohnDoe", "foo")));
+        assert!(!list.is_ignored(&gist("JohnDoe", "foobar")));
+        assert!(!list.is_ignored(&gist("JaneDoe", "foo")));
+    }
+
+    #[test]
+    fn wildcards() {
+        let list = IgnoreList::parse(&format!("{0}:JohnDoe/*\n{0}:*/ba?\n", HOST_ID));
+        assert!(list.is_ignored(&gist("JohnDoe", "foo")));
+        assert!(list.is_ignored(&gist("JaneDoe", "bar")));
+        assert!(list.is_ignored(&gist("JaneDoe", "baz")));
+        assert!(!list.is_ignored(&gist("JaneDoe", "bazz")));
+        assert!(!list.is_ignored(&gist("JaneDoe", "foo")));
+    }
+
+    #[test]
+    fn special_chars_are_literal() {
+        let list = IgnoreList::parse(&format!("{}:JohnDoe/foo.sh\n", HOST_ID));
+        assert!(list.is_ignored(&gist("JohnDoe", "foo.sh")));
+        assert!(!list.is_ignored(&gist("JohnDoe", "fooxsh")));
+    }
+}
@@ -3,6 +3,13 @@
 use std::borrow::{Borrow, Cow};
 use std::collections::BTreeMap;
 use std::fmt;
+use std::str::FromStr;
+
+use ansi_term::Style;
+use conv::errors::Unrepresentable;
+use serde_json::Value as Json;
+use serde_json::builder::ObjectBuilder;
+use time;
 
 
 macro_attr! {
@@ -24,6 +31,8 @@ macro_attr! {
         Language,
         /// Description of the gist, typically provided by the owner upon creation.
         Description,
+        /// Whether the local copy of the gist is up-to-date with the remote one.
+        LocalCopy,
         /// Date/time the gist was created.
         CreatedAt,
         /// Date/time the gist was modified.
@@ -31,6 +40,42 @@ macro_attr! {
     }
 }
 impl Datum {
+    /// Canonical name of the datum, as used e.g. in command line arguments.
+    /// This is distinct from the user-facing label provided by Display.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Datum::Id => "id",
+            Datum::Owner => "owner",
+            Datum::BrowserUrl => "browser_url",
+            Datum::RawUrl => "raw_url",
+            Datum::Language => "language",
+            Datum::Description => "description",
+            Datum::LocalCopy => "local_copy",
+            Datum::CreatedAt => "created_at",
+            Datum::UpdatedAt => "updated_at",
+        }
+    }
+
+    /// Alternative names that the datum is also recognized by.
+    pub fn aliases(&self) -> &'static [&'static str] {
+        match *self {
+            Datum::BrowserUrl => &["url"],
+            _ => &[],
+        }
+    }
+
+    /// Whether the value of this datum is a URL.
+    #[inline]
+    pub fn is_url(&self) -> bool {
+        *self == Datum::BrowserUrl || *self == Datum::RawUrl
+    }
+
+    /// Whether the value of this datum is a date/time.
+    #[inline]
+    pub fn is_time(&self) -> bool {
+        *self == Datum::CreatedAt || *self == Datum::UpdatedAt
+    }
+
     pub fn default_value(&self) -> &'static str {
         match *self {
             Datum::Id |
@@ -38,11 +83,25 @@ impl Datum {
             Datum::Language |
             Datum::CreatedAt |
             Datum::UpdatedAt => "(unknown)",
-            Datum::BrowserUrl | Datum::RawUrl => "N/A",
+            Datum::BrowserUrl | Datum::RawUrl | Datum::LocalCopy => "N/A",
             Datum::Description => "",
         }
     }
 }
+impl FromStr for Datum {
+    type Err = Unrepresentable<String>;
+
+    /// Create a Datum from its name (or alias), as returned by Datum::name().
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        for datum in Datum::iter_variants() {
+            if datum.name() == name || datum.aliases().contains(&name.as_str()) {
+                return Ok(datum);
+            }
+        }
+        Err(Unrepresentable(s.to_owned()))
+    }
+}
 impl fmt::Display for Datum {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let msg = match *self {
@@ -52,6 +111,7 @@ impl fmt::Display for Datum {
             Datum::RawUrl => "URL (raw)",
             Datum::Language => "Language",
             Datum::Description => "Description",
+            Datum::LocalCopy => "Local copy",
             Datum::CreatedAt => "Created at",
             Datum::UpdatedAt => "Last update",
         };
@@ -62,6 +122,9 @@ impl fmt::Display for Datum {
 /// Type of gist info data values.
 pub type Value = String;
 
+/// Value shown in place of the pieces of data that have been redacted.
+const REDACTED: &'static str = "(redacted)";
+
 
 /// Information about a particular gist.
 #[derive(Clone, PartialEq)]
@@ -93,6 +156,65 @@ impl Info {
     pub fn to_builder(self) -> InfoBuilder {
         InfoBuilder{data: self.data}
     }
+
+    /// Returns the Info restricted to only given pieces of data.
+    /// If `data` is empty, the Info is returned unchanged.
+    pub fn select(self, data: &[Datum]) -> Info {
+        if data.is_empty() {
+            return self;
+        }
+        let data = self.data.into_iter()
+            .filter(|&(ref datum, _)| data.contains(datum))
+            .collect();
+        Info{data: data}
+    }
+
+    /// Returns the Info with the pieces of data it lacks taken from `other`.
+    pub fn fill_from(self, other: Info) -> Info {
+        let mut data = other.data;
+        data.extend(self.data);
+        Info{data: data}
+    }
+
+    /// Convert the Info to a JSON object, keyed by the names of its pieces of data.
+    pub fn to_json(&self) -> Json {
+        self.data.iter()
+            .fold(ObjectBuilder::new(), |obj, (datum, value)| obj.insert(datum.name(), value))
+            .build()
+    }
+
+    /// Create the Info from a JSON object, as produced by `to_json`.
+    /// Unrecognized keys and non-string values are skipped.
+    pub fn from_json(json: &Json) -> Info {
+        let data = json.as_object().map(|obj| {
+            obj.iter()
+                .filter_map(|(k, v)| Datum::from_str(k).ok()
+                    .and_then(|d| v.as_str().map(|v| (d, v.to_owned()))))
+                .collect()
+        }).unwrap_or_else(BTreeMap::new);
+        Info{data: data}
+    }
+
+    /// Returns the Info with the values of given pieces of data masked,
+    /// so that e.g. the gist's owner isn't revealed when the Info is shown.
+    /// Data that isn't present in the Info is left out, as before.
+    pub fn redact(self, data: &[Datum]) -> Info {
+        let data = self.data.into_iter()
+            .map(|(datum, value)| if data.contains(&datum) {
+                (datum, REDACTED.to_owned())
+            } else {
+                (datum, value)
+            })
+            .collect();
+        Info{data: data}
+    }
+
+    /// Returns an object that displays the Info like its Display impl does,
+    /// but with the labels & URLs highlighted if `colors` is true.
+    #[inline]
+    pub fn display(&self, colors: bool) -> InfoDisplay {
+        InfoDisplay{info: self, colors: colors, relative_time: false}
+    }
 }
 
 impl Default for Info {
@@ -110,15 +232,107 @@ impl fmt::Debug for Info {
 
 impl fmt::Display for Info {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let longest = self.data.keys().map(|k| format!("{}", k).len()).max().unwrap();
-        for (datum, value) in &self.data {
-            try!(writeln!(fmt, "{:w$} : {}", datum, value, w=longest));
+        write!(fmt, "{}", self.display(false))
+    }
+}
+
+
+/// Helper for displaying the gist Info, optionally with colors.
+#[derive(Debug)]
+pub struct InfoDisplay<'i> {
+    info: &'i Info,
+    colors: bool,
+    relative_time: bool,
+}
+
+impl<'i> InfoDisplay<'i> {
+    /// Make the displayed date/time values relative to now (like "3 days ago")
+    /// if `relative` is true. Values that cannot be parsed are displayed as they are.
+    #[inline]
+    pub fn relative_time(self, relative: bool) -> Self {
+        InfoDisplay{relative_time: relative, ..self}
+    }
+}
+
+impl<'i> fmt::Display for InfoDisplay<'i> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let data = &self.info.data;
+        let longest = label_width(data.keys().map(|k| format!("{}", k)));
+        for (datum, value) in data {
+            let value: Cow<str> = if self.relative_time && datum.is_time() {
+                format_relative_time(value, time::get_time().sec)
+                    .map(Cow::Owned).unwrap_or_else(|| Cow::Borrowed(value as &str))
+            } else {
+                Cow::Borrowed(value as &str)
+            };
+            // Continuation lines of multi-line values (like some descriptions)
+            // are aligned with the first one, past the label and separator.
+            let value = indent_continuation_lines(value, longest + VALUE_SEPARATOR.len());
+
+            // Padding has to be applied before painting,
+            // as it would otherwise count the color escape codes, too.
+            let label = format!("{:w$}", datum, w=longest);
+            if self.colors {
+                let value_style = if datum.is_url() { Style::new().underline() }
+                                  else { Style::default() };
+                try!(writeln!(fmt, "{}{}{}", Style::new().bold().paint(label),
+                    VALUE_SEPARATOR, value_style.paint(&*value)));
+            } else {
+                try!(writeln!(fmt, "{}{}{}", label, VALUE_SEPARATOR, value));
+            }
         }
         Ok(())
     }
 }
 
 
+/// Separator between the labels and values of displayed gist info.
+const VALUE_SEPARATOR: &'static str = " : ";
+
+/// Indent all lines of a value but the first one by given number of spaces.
+fn indent_continuation_lines(value: Cow<str>, indent: usize) -> Cow<str> {
+    if !value.contains('\n') {
+        return value;
+    }
+    let separator = format!("\n{:w$}", "", w=indent);
+    Cow::Owned(value.lines().collect::<Vec<_>>().join(&separator))
+}
+
+/// Compute the width of a column holding given labels.
+///
+/// This is measured in characters rather than bytes, just like the padding
+/// applied by `{:w$}`, so that labels with multibyte characters align properly.
+fn label_width<I: IntoIterator<Item=String>>(labels: I) -> usize {
+    labels.into_iter().map(|l| l.chars().count()).max().unwrap_or(0)
+}
+
+/// Format a date/time value (in the ISO format used by gist hosts)
+/// as relative to the `now` timestamp, like "3 days ago".
+fn format_relative_time(value: &str, now: i64) -> Option<String> {
+    const UNITS: &'static [(i64, &'static str)] = &[
+        (365 * 24 * 60 * 60, "year"),
+        (30 * 24 * 60 * 60, "month"),
+        (24 * 60 * 60, "day"),
+        (60 * 60, "hour"),
+        (60, "minute"),
+    ];
+
+    let tm = try_opt!(time::strptime(value, "%Y-%m-%dT%H:%M:%SZ").ok());
+    let secs = now - tm.to_timespec().sec;
+    if secs < 0 {
+        return None;
+    }
+    for &(unit_secs, unit) in UNITS {
+        let count = secs / unit_secs;
+        if count > 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            return Some(format!("{} {}{} ago", count, unit, plural));
+        }
+    }
+    Some("just now".to_owned())
+}
+
+
 /// Builder for the gist Info struct.
 #[derive(Clone)]
 pub struct InfoBuilder {
@@ -199,7 +413,46 @@ impl Default for InfoBuilder {
 
 #[cfg(test)]
 mod tests {
-    use super::{Datum, InfoBuilder};
+    use std::collections::HashSet;
+    use std::str::FromStr;
+    use ansi_term::Style;
+    use serde_json::Value as Json;
+    use super::{Datum, Info, InfoBuilder, REDACTED, format_relative_time,
+                indent_continuation_lines, label_width};
+
+    #[test]
+    fn datum_names_unique() {
+        let mut names = HashSet::new();
+        for datum in Datum::iter_variants() {
+            assert!(names.insert(datum.name()), "Duplicate datum name: {}", datum.name());
+            for &alias in datum.aliases() {
+                assert!(names.insert(alias), "Duplicate datum alias: {}", alias);
+            }
+        }
+    }
+
+    #[test]
+    fn datum_from_str_roundtrip() {
+        for datum in Datum::iter_variants() {
+            assert_eq!(datum, Datum::from_str(datum.name()).unwrap());
+            for &alias in datum.aliases() {
+                assert_eq!(datum, Datum::from_str(alias).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn datum_from_str_display_label() {
+        // Human-readable labels aren't valid names, unless they coincide.
+        for datum in Datum::iter_variants() {
+            let label = format!("{}", datum);
+            if let Ok(parsed) = Datum::from_str(&label) {
+                assert_eq!(datum, parsed);
+            }
+        }
+        assert!(Datum::from_str("Last update").is_err());
+        assert!(Datum::from_str("").is_err());
+    }
 
     #[test]
     fn datum_order_id_always_first() {
@@ -244,4 +497,181 @@ mod tests {
         assert_eq!("JohnDoe", *info.get(Datum::Owner));
         assert_eq!("Amazing gist", *info.get(Datum::Description));
     }
+
+    #[test]
+    fn info_select() {
+        let info = InfoBuilder::new()
+            .with(Datum::Id, "some_id")
+            .with(Datum::Owner, "JohnDoe")
+            .with(Datum::Description, "Amazing gist")
+            .build();
+
+        let all = info.clone().select(&[]);
+        assert_eq!(3, all.len());
+
+        let selected = info.select(&[Datum::Owner, Datum::Language]);
+        assert_eq!(1, selected.len());
+        assert!(selected.has(Datum::Owner));
+        assert!(!selected.has(Datum::Id));
+    }
+
+    #[test]
+    fn info_fill_from() {
+        let info = InfoBuilder::new()
+            .with(Datum::Language, "Python")
+            .build();
+        let other = InfoBuilder::new()
+            .with(Datum::Language, "Ruby")
+            .with(Datum::Description, "Amazing gist")
+            .build();
+
+        let filled = info.fill_from(other);
+        assert_eq!(2, filled.len());
+        assert_eq!("Python", *filled.get(Datum::Language));
+        assert_eq!("Amazing gist", *filled.get(Datum::Description));
+    }
+
+    #[test]
+    fn info_json_roundtrip() {
+        let info = InfoBuilder::new()
+            .with(Datum::Id, "some_id")
+            .with(Datum::Language, "Python")
+            .build();
+
+        let json = info.to_json();
+        assert_eq!(Some("Python"), json.find("language").and_then(Json::as_str));
+        assert!(info == Info::from_json(&json), "Info changed after JSON roundtrip");
+    }
+
+    #[test]
+    fn info_from_json_skips_unknown() {
+        let json = Json::from_str(r#"{"language": "Rust", "lolwut": "x", "id": 42}"#).unwrap();
+        let info = Info::from_json(&json);
+        assert_eq!(1, info.len());
+        assert_eq!("Rust", *info.get(Datum::Language));
+    }
+
+    #[test]
+    fn info_redact() {
+        let info = InfoBuilder::new()
+            .with(Datum::Id, "some_id")
+            .with(Datum::Owner, "JohnDoe")
+            .build();
+
+        let redacted = info.redact(&[Datum::Owner, Datum::Description]);
+        assert_eq!(2, redacted.len());
+        assert_eq!("some_id", *redacted.get(Datum::Id));
+        assert_eq!(REDACTED, *redacted.get(Datum::Owner));
+        assert!(!redacted.has(Datum::Description));
+    }
+
+    #[test]
+    fn info_display_order_stable() {
+        let info = InfoBuilder::new()
+            .with(Datum::UpdatedAt, "2017-01-01T00:00:00Z")
+            .with(Datum::Description, "Amazing gist")
+            .with(Datum::Owner, "JohnDoe")
+            .with(Datum::Id, "some_id")
+            .build();
+        let labels: Vec<_> = format!("{}", info).lines()
+            .map(|l| l.split(" : ").next().unwrap().trim().to_owned())
+            .collect();
+        let expected: Vec<_> = [Datum::Id, Datum::Owner, Datum::Description, Datum::UpdatedAt]
+            .iter().map(|d| format!("{}", d)).collect();
+        assert_eq!(expected, labels);
+    }
+
+    #[test]
+    fn info_display_aligned() {
+        let info = InfoBuilder::new()
+            .with(Datum::Id, "some_id")
+            .with(Datum::Description, "Amazing gist")
+            .build();
+        let output = format!("{}", info);
+        let separators: Vec<_> = output.lines().map(|l| l.find(" : ").unwrap()).collect();
+        assert_eq!(2, separators.len());
+        assert_eq!(separators[0], separators[1]);
+    }
+
+    #[test]
+    fn info_display_multiline_value() {
+        let info = InfoBuilder::new()
+            .with(Datum::Id, "some_id")
+            .with(Datum::Description, "Amazing gist\nthat does\r\nmany things")
+            .build();
+        let output = format!("{}", info);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(4, lines.len(), "Unexpected number of lines in: {}", output);
+        let value_column = lines[0].find(" : ").unwrap() + 3;
+        assert_eq!(Some(value_column), lines[2].find("that does"));
+        assert_eq!(Some(value_column), lines[3].find("many things"));
+        assert!(lines[2..].iter().all(|l| l[..value_column].trim().is_empty()));
+    }
+
+    #[test]
+    fn continuation_lines_indented() {
+        assert_eq!("foo", indent_continuation_lines("foo".into(), 4));
+        assert_eq!("foo\n    bar\n    baz", indent_continuation_lines("foo\nbar\nbaz".into(), 4));
+    }
+
+    #[test]
+    fn label_width_of_multibyte_labels() {
+        let labels = vec!["Größe".to_owned(), "ID".to_owned()];
+        assert_eq!(5, label_width(labels.clone()));
+        let padded: Vec<_> = labels.iter().map(|l| format!("{:w$}", l, w=5)).collect();
+        assert_eq!(padded[0].chars().count(), padded[1].chars().count());
+        assert_eq!(0, label_width(vec![]));
+    }
+
+    #[test]
+    fn info_display_without_colors() {
+        let info = InfoBuilder::new()
+            .with(Datum::Owner, "JohnDoe")
+            .with(Datum::BrowserUrl, "http://example.com")
+            .build();
+        let output = format!("{}", info.display(false));
+        assert_eq!(format!("{}", info), output);
+        assert!(!output.contains('\x1b'), "Uncolored info contains escape codes");
+        assert!(output.contains("Owner : JohnDoe\n"));
+    }
+
+    #[test]
+    fn relative_time() {
+        const NOW: i64 = 1500000000;  // 2017-07-14T02:40:00Z
+        assert_eq!(Some("just now".to_owned()),
+                   format_relative_time("2017-07-14T02:39:30Z", NOW));
+        assert_eq!(Some("1 hour ago".to_owned()),
+                   format_relative_time("2017-07-14T01:30:00Z", NOW));
+        assert_eq!(Some("3 days ago".to_owned()),
+                   format_relative_time("2017-07-11T02:40:00Z", NOW));
+        assert_eq!(Some("2 years ago".to_owned()),
+                   format_relative_time("2015-07-01T00:00:00Z", NOW));
+        assert_eq!(None, format_relative_time("2017-07-15T00:00:00Z", NOW));
+        assert_eq!(None, format_relative_time("yesterday", NOW));
+    }
+
+    #[test]
+    fn info_display_relative_time() {
+        let info = InfoBuilder::new()
+            .with(Datum::CreatedAt, "2010-01-01T00:00:00Z")
+            .with(Datum::Owner, "JohnDoe")
+            .build();
+        let output = format!("{}", info.display(false).relative_time(true));
+        assert!(output.contains(" years ago\n"), "Time not displayed as relative: {}", output);
+        assert!(output.contains("JohnDoe\n"));
+        assert!(format!("{}", info).contains("2010-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn info_display_with_colors() {
+        let info = InfoBuilder::new()
+            .with(Datum::Owner, "JohnDoe")
+            .with(Datum::BrowserUrl, "http://example.com")
+            .build();
+        let output = format!("{}", info.display(true));
+        assert!(output.contains(&*Style::new().bold().paint("Owner").to_string()));
+        assert!(output.contains(
+            &*Style::new().underline().paint("http://example.com").to_string()));
+        assert!(output.contains("JohnDoe\n"), "Non-URL value shouldn't be styled");
+    }
 }
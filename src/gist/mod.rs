@@ -3,16 +3,30 @@
 //! Gists are represented as the Gist structure, with the auxiliary URI
 //! that helps refering to them as command line arguments to the program.
 
+mod ignore;
 mod info;
 mod uri;
+mod url_cache;
 
 
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::{BIN_DIR, GISTS_DIR};
-pub use self::info::{Datum, Info, InfoBuilder};
+use serde_json::{self, Value as Json};
+use time;
+
+use config;
+use super::{BIN_DIR, GISTS_DIR, IGNORE_FILE, URL_CACHE_FILE};
+use util::{binary_source, walk_files};
+pub use self::ignore::IgnoreList;
+pub use self::info::{Datum, Info, InfoBuilder, InfoDisplay};
 pub use self::uri::{Uri, UriError};
+use self::uri::from_path_component;
+pub use self::url_cache::UrlCache;
 
 
 /// Structure representing a single gist.
@@ -115,6 +129,244 @@ impl Gist {
     }
 }
 
+impl Gist {
+    /// Returns the path to the file with gist Info stored alongside the gist's local copy
+    /// (regardless whether it exists or not).
+    #[inline]
+    pub fn meta_path(&self) -> PathBuf {
+        meta_path_for(self.path())
+    }
+
+    /// Store the gist's Info (if any) alongside its local copy,
+    /// so that it's available even when the gist is later used without its host.
+    pub fn store_info(&self) -> io::Result<()> {
+        let info = match self.info {
+            Some(ref info) if info.len() > 0 => info,
+            _ => return Ok(()),
+        };
+        let path = self.meta_path();
+        trace!("Storing info of gist {} in {}", self.uri, path.display());
+        let mut file = try!(fs::File::create(&path));
+        serde_json::to_writer(&mut file, &info.to_json())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Create a copy of Gist that has the pieces of Info it lacks
+    /// filled in from the Info stored alongside its local copy, if any.
+    ///
+    /// This is useful for local gists, which otherwise start with no Info at all.
+    pub fn with_stored_info(self) -> Self {
+        let id = self.id.clone().or_else(|| id_from_local_copy(&self));
+        let path = match id {
+            Some(id) => self.clone().with_id(id).meta_path(),
+            None => self.meta_path(),
+        };
+        let stored = match fs::File::open(&path) {
+            Ok(file) => match serde_json::from_reader::<_, Json>(file) {
+                Ok(json) => Info::from_json(&json),
+                Err(e) => {
+                    warn!("Invalid stored info of gist {} in {}: {}",
+                        self.uri, path.display(), e);
+                    return self;
+                },
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return self,
+            Err(e) => {
+                warn!("Failed to read stored info of gist {} from {}: {}",
+                    self.uri, path.display(), e);
+                return self;
+            },
+        };
+        debug!("Read {} piece(s) of stored info of gist {}", stored.len(), self.uri);
+        let info = match self.info {
+            Some(ref info) => info.clone().fill_from(stored),
+            None => stored,
+        };
+        self.with_info(info)
+    }
+
+    /// Gather the Info about the gist that's available without talking to its host,
+    /// i.e. from its local copy and the Info stored alongside it.
+    pub fn local_info(&self) -> Info {
+        let stored = self.clone().with_stored_info().info.unwrap_or_default();
+        let mut builder = InfoBuilder::new();
+
+        let id = self.id.clone().or_else(|| id_from_local_copy(self));
+        builder.set_opt(Datum::Id, id.as_ref());
+
+        let executable = binary_source(&self.binary_path()).ok();
+        let language = executable.as_ref()
+            .and_then(|e| e.extension()).and_then(|e| e.to_str())
+            .and_then(language_for_extension);
+        builder.set_opt(Datum::Language, language);
+
+        // Git-based gists have their last update (pull) recorded in FETCH_HEAD,
+        // while for others, it's when their file was written.
+        let updated_at = fs::metadata(self.path().join(".git").join("FETCH_HEAD")).ok()
+            .or_else(|| executable.as_ref().and_then(|e| fs::metadata(e).ok()))
+            .and_then(|m| m.modified().ok())
+            .and_then(format_system_time);
+        builder.set_opt(Datum::UpdatedAt, updated_at.as_ref());
+
+        // Stored info is more accurate (e.g. about the language), so it takes precedence.
+        stored.fill_from(builder.build())
+    }
+}
+
+/// Suffix of the file which stores gist Info alongside the gist's local copy.
+const META_SUFFIX: &'static str = ".gisht-meta";
+
+/// Returns the path to the file with gist Info stored alongside given local copy of a gist.
+pub fn meta_path_for<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut path: OsString = path.as_ref().to_owned().into();
+    path.push(META_SUFFIX);
+    path.into()
+}
+
+/// Maximum depth of the binary directory, where gists are at bin/$HOST/$OWNER/$NAME.
+const BIN_DIR_DEPTH: usize = 3;
+
+/// List all the gists that have been downloaded locally.
+///
+/// The gists are reconstructed from the contents of the binary directory,
+/// and sorted by their URIs. Their IDs are included when they can be determined.
+pub fn local_gists() -> io::Result<Vec<Gist>> {
+    if !BIN_DIR.exists() {
+        trace!("Binary directory {} doesn't exist, no gists downloaded yet",
+            BIN_DIR.display());
+        return Ok(vec![]);
+    }
+
+    // Binaries are either at bin/$HOST/$OWNER/$NAME or bin/$HOST/$NAME.
+    let mut uris = Vec::new();
+    for path in try!(walk_files(&*BIN_DIR, BIN_DIR_DEPTH)) {
+        let parts: Option<Vec<&str>> = path.strip_prefix(&*BIN_DIR).unwrap()
+            .components().map(|c| c.as_os_str().to_str()).collect();
+        match parts {
+            Some(ref p) if p.len() == 2 =>
+                uris.push(Uri::from_name(p[0], from_path_component(p[1]))),
+            Some(ref p) if p.len() == 3 => uris.push(Uri::new(
+                p[0], from_path_component(p[1]), from_path_component(p[2]))),
+            _ => trace!("Skipping unexpected path in binary directory: {}", path.display()),
+        }
+    }
+
+    let mut gists: Vec<_> = uris.into_iter().filter_map(|uri| match uri {
+        Ok(uri) => Some(uri),
+        Err(e) => { warn!("Skipping unrecognized local gist: {}", e); None },
+    }).map(|uri| {
+        let gist = Gist::from_uri(uri);
+        match id_from_local_copy(&gist) {
+            Some(id) => gist.with_id(id),
+            None => gist,
+        }
+    }).collect();
+    gists.sort_by_key(|g| g.uri.to_string());
+    Ok(gists)
+}
+
+/// List the gists available locally, except those matched by the ignore file.
+///
+/// This is what bulk operations (like listing or searching gists) should use.
+pub fn unignored_local_gists() -> io::Result<Vec<Gist>> {
+    let ignore_list = try!(IgnoreList::from_file(&*IGNORE_FILE));
+    let mut gists = try!(local_gists());
+    gists.retain(|g| {
+        let ignored = ignore_list.is_ignored(g);
+        if ignored {
+            trace!("Ignoring local gist {}", g.uri);
+        }
+        !ignored
+    });
+    Ok(gists)
+}
+
+/// Return the gist that given (canonical) browser URL has recently resolved into, if any.
+pub fn cached_gist_for_url(url: &str) -> Option<Gist> {
+    let cache = match UrlCache::from_file(&*URL_CACHE_FILE) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Failed to read the URL cache from {}: {}", URL_CACHE_FILE.display(), e);
+            return None;
+        },
+    };
+    cache.get(url, unix_now(), config::current().url_cache_ttl())
+}
+
+/// Remember that given (canonical) browser URL has resolved into given gist.
+pub fn cache_gist_for_url(url: &str, gist: &Gist) {
+    let result = UrlCache::from_file(&*URL_CACHE_FILE).and_then(|mut cache| {
+        cache.insert(url, gist, unix_now());
+        cache.save(&*URL_CACHE_FILE)
+    });
+    if let Err(e) = result {
+        warn!("Failed to cache the gist for URL {}: {}", url, e);
+    }
+}
+
+/// Forget the browser URLs that have resolved into given gist,
+/// e.g. because it turned out it cannot be fetched.
+pub fn forget_gist_urls(gist: &Gist) {
+    let result = UrlCache::from_file(&*URL_CACHE_FILE).and_then(|mut cache| {
+        if cache.remove(gist) {
+            debug!("Removed URLs of gist {} from the URL cache", gist.uri);
+            try!(cache.save(&*URL_CACHE_FILE));
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        warn!("Failed to remove gist {} from the URL cache: {}", gist.uri, e);
+    }
+}
+
+/// Current time as a UNIX timestamp.
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Determine the ID of a local gist based on where its binary points to.
+///
+/// This reverses the logic of Gist::path, where gists with host-specific IDs
+/// are stored under said IDs (rather than under their owner & name).
+fn id_from_local_copy(gist: &Gist) -> Option<String> {
+    let executable = try_opt!(binary_source(&gist.binary_path()).ok());
+    let host_dir = try_opt!(GISTS_DIR.join(&gist.uri.host_id).canonicalize().ok());
+    let relative = try_opt!(executable.strip_prefix(&host_dir).ok());
+
+    let uri_path: PathBuf = gist.uri.clone().into();
+    if PathBuf::from(&gist.uri.host_id).join(relative) == uri_path {
+        return None;
+    }
+    relative.components().next()
+        .and_then(|c| c.as_os_str().to_str()).map(String::from)
+}
+
+/// Name of the language (as gist hosts report it) of a file with given extension,
+/// if it's one of the common ones.
+fn language_for_extension(extension: &str) -> Option<&'static str> {
+    match &*extension.to_lowercase() {
+        "c" => Some("C"),
+        "clj" => Some("Clojure"),
+        "go" => Some("Go"),
+        "hs" => Some("Haskell"),
+        "js" => Some("JavaScript"),
+        "pl" => Some("Perl"),
+        "py" => Some("Python"),
+        "rb" => Some("Ruby"),
+        "rs" => Some("Rust"),
+        "sh" => Some("Shell"),
+        _ => None,
+    }
+}
+
+/// Format a point in time the same way gist hosts do in gist info.
+fn format_system_time(t: SystemTime) -> Option<String> {
+    let secs = try_opt!(t.duration_since(UNIX_EPOCH).ok()).as_secs();
+    let tm = time::at_utc(time::Timespec::new(secs as i64, 0));
+    tm.strftime("%Y-%m-%dT%H:%M:%SZ").ok().map(|t| t.to_string())
+}
+
+
 impl PartialEq<Gist> for Gist {
     fn eq(&self, other: &Gist) -> bool {
         if self.uri != other.uri {
@@ -132,7 +384,7 @@ impl PartialEq<Gist> for Gist {
 mod tests {
     use gist::Uri;
     use hosts;
-    use super::Gist;
+    use super::{Gist, language_for_extension};
 
     const HOST_ID: &'static str = hosts::DEFAULT_HOST_ID;
     const OWNER: &'static str = "JohnDoe";
@@ -158,6 +410,16 @@ mod tests {
         assert!(!path.contains(NAME), "Gist path shouldn't contain gist name");
     }
 
+    #[test]
+    fn meta_path_next_to_path() {
+        let gist = Gist::from_uri(Uri::from_name(HOST_ID, NAME).unwrap())
+            .with_id(ID);
+        let meta_path = gist.meta_path();
+        assert_eq!(gist.path().parent(), meta_path.parent());
+        assert_eq!(format!("{}.gisht-meta", ID),
+                   meta_path.file_name().unwrap().to_str().unwrap());
+    }
+
     #[test]
     fn binary_path() {
         let gist = Gist::from_uri(Uri::new(HOST_ID, OWNER, NAME).unwrap());
@@ -166,4 +428,11 @@ mod tests {
         assert!(path.contains(OWNER), "Gist binary path should contain owner");
         assert!(path.contains(NAME), "Gist binary path should contain gist name");
     }
+
+    #[test]
+    fn language_of_extension() {
+        assert_eq!(Some("Python"), language_for_extension("py"));
+        assert_eq!(Some("Shell"), language_for_extension("SH"));
+        assert_eq!(None, language_for_extension("txt"));
+    }
 }
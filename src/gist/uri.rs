@@ -1,5 +1,6 @@
 //! Gist URI module.
 
+use std::borrow::Cow;
 use std::error::Error;
 use std::fmt;
 use std::path::PathBuf;
@@ -57,6 +58,15 @@ impl Uri {
     pub fn host(&self) -> &Host {
         let host = HOSTS.get(&self.host_id as &str).unwrap(); &**host
     }
+
+    /// Format the URI without its `host:` prefix, i.e. as just `owner/name` or `name`.
+    pub fn without_host(&self) -> String {
+        if self.has_owner() {
+            format!("{}/{}", self.owner, self.name)
+        } else {
+            self.name.clone()
+        }
+    }
 }
 
 impl FromStr for Uri {
@@ -88,13 +98,95 @@ impl Into<PathBuf> for Uri {
         let mut path = PathBuf::new();
         path.push(self.host_id);
         if has_owner {
-            path.push(self.owner);
+            path.push(&*to_path_component(&self.owner));
         }
-        path.push(self.name);
+        path.push(&*to_path_component(&self.name));
         path
     }
 }
 
+
+/// Convert a part of gist URI (its owner or name) into a path component.
+///
+/// On Windows, names reserved for devices (like `con` or `nul.txt`) and names ending with
+/// a dot or space cannot be used as filenames, so they get an underscore appended
+/// to their stem or end, respectively (e.g. `con_.py`).
+/// Names that look like they have been escaped already get another underscore,
+/// so that from_path_component() can always recover the original name.
+#[cfg(windows)]
+pub(super) fn to_path_component(part: &str) -> Cow<str> {
+    let (stem, rest) = split_stem(part);
+    let mut component = if is_reserved_stem(stem.trim_right_matches(ESCAPE)) {
+        Cow::Owned(format!("{}{}{}", stem, ESCAPE, rest))
+    } else {
+        Cow::Borrowed(part)
+    };
+    if has_invalid_ending(component.trim_right_matches(ESCAPE)) {
+        component.to_mut().push(ESCAPE);
+    }
+    component
+}
+#[cfg(not(windows))]
+#[inline]
+pub(super) fn to_path_component(part: &str) -> Cow<str> { Cow::Borrowed(part) }
+
+/// Convert a path component back into a part of gist URI (its owner or name).
+/// This reverses to_path_component().
+#[cfg(windows)]
+pub(super) fn from_path_component(component: &str) -> Cow<str> {
+    let mut part = component.to_owned();
+    if part.ends_with(ESCAPE) && has_invalid_ending(part.trim_right_matches(ESCAPE)) {
+        part.pop();
+    }
+    let unescaped = {
+        let (stem, rest) = split_stem(&part);
+        if stem.ends_with(ESCAPE) && is_reserved_stem(stem.trim_right_matches(ESCAPE)) {
+            Some(format!("{}{}", &stem[..stem.len() - 1], rest))
+        } else {
+            None
+        }
+    };
+    match unescaped {
+        Some(p) => Cow::Owned(p),
+        None if part == component => Cow::Borrowed(component),
+        None => Cow::Owned(part),
+    }
+}
+#[cfg(not(windows))]
+#[inline]
+pub(super) fn from_path_component(component: &str) -> Cow<str> { Cow::Borrowed(component) }
+
+/// Character appended to names that cannot be used as filenames on Windows.
+#[cfg(windows)]
+const ESCAPE: char = '_';
+
+/// Names of devices that Windows reserves, regardless of the file extension.
+#[cfg(windows)]
+const RESERVED_STEMS: &'static [&'static str] = &[
+    "con", "prn", "aux", "nul",
+    "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Split a filename into its stem (up to the first dot) and the rest.
+#[cfg(windows)]
+fn split_stem(name: &str) -> (&str, &str) {
+    match name.find('.') {
+        Some(idx) => name.split_at(idx),
+        None => (name, ""),
+    }
+}
+
+#[cfg(windows)]
+fn is_reserved_stem(stem: &str) -> bool {
+    RESERVED_STEMS.contains(&&*stem.to_lowercase())
+}
+
+#[cfg(windows)]
+fn has_invalid_ending(name: &str) -> bool {
+    name.ends_with('.') || name.ends_with(' ')
+}
+
 impl fmt::Display for Uri {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         if self.has_owner() {
@@ -201,4 +293,30 @@ mod tests {
             e => panic!("Unexpected error when parsing gist URI with invalid host: {:?}", e),
         }
     }
+
+    #[cfg(windows)]
+    #[test]
+    fn reserved_windows_names() {
+        use std::path::PathBuf;
+        use super::{from_path_component, to_path_component};
+
+        let uri = Uri::from_str(&format!("{}:con/con.py", DEFAULT_HOST_ID)).unwrap();
+        let path: PathBuf = uri.into();
+        assert_eq!(PathBuf::from(DEFAULT_HOST_ID).join("con_").join("con_.py"), path);
+
+        for &(name, component) in &[("con.py", "con_.py"), ("NUL", "NUL_"), ("foo.", "foo._"),
+                                    ("foo ", "foo _"), ("con_.py", "con__.py"),
+                                    ("foo._", "foo.__"), ("console.py", "console.py")] {
+            assert_eq!(component, to_path_component(name));
+            assert_eq!(name, from_path_component(component));
+        }
+    }
+
+    #[test]
+    fn without_host() {
+        let uri = Uri::from_str(&format!("{}:foo/bar", DEFAULT_HOST_ID)).unwrap();
+        assert_eq!("foo/bar", uri.without_host());
+        let uri = Uri::from_str(&format!("{}:foo", DEFAULT_HOST_ID)).unwrap();
+        assert_eq!("foo", uri.without_host());
+    }
 }
@@ -0,0 +1,169 @@
+//! Module handling the cache of gists that browser URLs have been resolved into.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::{Gist, Uri};
+
+
+/// Cache of gists that (canonical) browser URLs have resolved into,
+/// so that resolving the same URL again doesn't need to talk to the host.
+///
+/// The cache is stored in a file where each line holds a single entry
+/// as tab-separated fields: the URL, the UNIX timestamp of when it was resolved,
+/// the gist URI, and the gist ID (which may be empty).
+/// Lines that cannot be parsed are skipped.
+#[derive(Debug, Default)]
+pub struct UrlCache {
+    entries: BTreeMap<String, Entry>,
+}
+
+/// Single entry in the UrlCache.
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    /// When the URL was resolved, as a UNIX timestamp.
+    timestamp: u64,
+    uri: Uri,
+    id: Option<String>,
+}
+
+impl UrlCache {
+    /// Load the UrlCache from given file.
+    /// If the file doesn't exist, an empty cache is returned.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            trace!("URL cache file {} doesn't exist", path.display());
+            return Ok(UrlCache::default());
+        }
+        let mut content = String::new();
+        let mut file = try!(fs::File::open(path));
+        try!(file.read_to_string(&mut content));
+        let cache = UrlCache::parse(&content);
+        trace!("Loaded {} entries from URL cache file {}",
+            cache.entries.len(), path.display());
+        Ok(cache)
+    }
+
+    /// Parse the content of a URL cache file.
+    pub fn parse(content: &str) -> Self {
+        let entries = content.lines().filter_map(|line| {
+            let fields: Vec<_> = line.split('\t').collect();
+            if fields.len() != 4 {
+                return None;
+            }
+            let timestamp = try_opt!(fields[1].parse().ok());
+            let uri = try_opt!(fields[2].parse().ok());
+            let id = if fields[3].is_empty() { None } else { Some(fields[3].to_owned()) };
+            Some((fields[0].to_owned(), Entry{timestamp: timestamp, uri: uri, id: id}))
+        }).collect();
+        UrlCache{entries: entries}
+    }
+
+    /// Write the UrlCache to given file, replacing its previous content.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            try!(fs::create_dir_all(dir));
+        }
+        let mut file = try!(fs::File::create(path));
+        file.write_all(self.to_string().as_bytes())
+    }
+
+    /// Return the gist that given URL has resolved into, as of `now` (a UNIX timestamp),
+    /// provided it was no longer than `ttl` ago.
+    pub fn get(&self, url: &str, now: u64, ttl: Duration) -> Option<Gist> {
+        let entry = try_opt!(self.entries.get(url));
+        if now.saturating_sub(entry.timestamp) > ttl.as_secs() {
+            trace!("Cached resolution of URL {} has expired", url);
+            return None;
+        }
+        let gist = Gist::from_uri(entry.uri.clone());
+        Some(match entry.id {
+            Some(ref id) => gist.with_id(id.as_str()),
+            None => gist,
+        })
+    }
+
+    /// Record that given URL has resolved into given gist at `now` (a UNIX timestamp).
+    pub fn insert(&mut self, url: &str, gist: &Gist, now: u64) {
+        self.entries.insert(url.to_owned(), Entry{
+            timestamp: now, uri: gist.uri.clone(), id: gist.id.clone(),
+        });
+    }
+
+    /// Remove all the URLs that have resolved into given gist.
+    /// Returns whether anything was removed.
+    pub fn remove(&mut self, gist: &Gist) -> bool {
+        let urls: Vec<_> = self.entries.iter()
+            .filter(|&(_, e)| e.uri == gist.uri)
+            .map(|(url, _)| url.clone()).collect();
+        for url in &urls {
+            self.entries.remove(url);
+        }
+        !urls.is_empty()
+    }
+}
+
+impl ToString for UrlCache {
+    fn to_string(&self) -> String {
+        self.entries.iter().map(|(url, e)| format!("{}\t{}\t{}\t{}\n",
+            url, e.timestamp, e.uri, e.id.as_ref().map(|id| id as &str).unwrap_or("")))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use gist::{Gist, Uri};
+    use hosts;
+    use super::UrlCache;
+
+    const URL: &'static str = "https://gist.github.com/JohnDoe/abc123";
+    const TTL_SECS: u64 = 60;
+
+    fn gist() -> Gist {
+        Gist::from_uri(Uri::new(hosts::DEFAULT_HOST_ID, "JohnDoe", "foo").unwrap())
+            .with_id("abc123")
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut cache = UrlCache::default();
+        cache.insert(URL, &gist(), 1000);
+        let cache = UrlCache::parse(&cache.to_string());
+
+        let cached = cache.get(URL, 1000, Duration::from_secs(TTL_SECS)).unwrap();
+        assert_eq!(gist().uri, cached.uri);
+        assert_eq!(gist().id, cached.id);
+    }
+
+    #[test]
+    fn expiry() {
+        let mut cache = UrlCache::default();
+        cache.insert(URL, &gist(), 1000);
+        let ttl = Duration::from_secs(TTL_SECS);
+        assert!(cache.get(URL, 1000 + TTL_SECS, ttl).is_some());
+        assert!(cache.get(URL, 1000 + TTL_SECS + 1, ttl).is_none());
+    }
+
+    #[test]
+    fn removal() {
+        let mut cache = UrlCache::default();
+        cache.insert(URL, &gist(), 1000);
+        assert!(cache.remove(&gist()));
+        assert!(!cache.remove(&gist()));
+        assert!(cache.get(URL, 1000, Duration::from_secs(TTL_SECS)).is_none());
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let cache = UrlCache::parse("garbage\nhttps://x\tnotanumber\tgh:a/b\t\n");
+        assert!(cache.entries.is_empty());
+    }
+}
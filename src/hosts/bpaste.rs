@@ -1,5 +1,7 @@
 //! Module implementing bpaste.net as Basic gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -9,11 +11,11 @@ use hosts::common::Basic;
 pub const ID: &'static str = "bp";
 
 /// Create the bpaste.net Host implementation.
-pub fn create() -> Basic {
+pub fn create() -> Result<Basic, Box<Error>> {
     Basic::new(ID, "bpaste.net",
                "http://bpaste.net/raw/${id}",
                "http://bpaste.net/show/${id}",
-               Regex::new("[0-9a-z]+").unwrap()).unwrap()
+               try!(Regex::new("[0-9a-z]+")))
 }
 
 
@@ -23,7 +25,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = create();
+        let host = create().unwrap();
         let html_url: String = host.html_url_origin() + "/show";
 
         let valid_html_urls: Vec<(/* URL */ String,
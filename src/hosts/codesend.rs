@@ -1,5 +1,7 @@
 //! Module implementing the codesend.com gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 use select::predicate::{Attr, Name, Predicate};
 
@@ -12,11 +14,10 @@ pub const ID: &'static str = "cs";
 
 
 /// Create the CodeSend host implementation.
-pub fn create() -> Box<Host> {
-    Box::new(
-        HtmlOnly::new(ID, "CodeSend",
-                      "http://www.codesend.com/view/${id}/",
-                      Regex::new("[0-9a-z]+").unwrap(),
-                      Name("pre").and(Attr("id", "viewer"))).unwrap()
-    )
+pub fn create() -> Result<Box<Host>, Box<Error>> {
+    let host = try!(HtmlOnly::new(ID, "CodeSend",
+                                  "http://www.codesend.com/view/${id}/",
+                                  try!(Regex::new("[0-9a-z]+")),
+                                  Name("pre").and(Attr("id", "viewer"))));
+    Ok(Box::new(host))
 }
@@ -3,13 +3,12 @@
 use std::error::Error;
 use std::io;
 
-use hyper::header::UserAgent;
+use hyper::header::ContentType;
 use regex::Regex;
 
-use ::USER_AGENT;
 use gist::Gist;
 use hosts::{FetchMode, Host};
-use util::http_client;
+use util::{http_client, http_error, http_get, send_following_redirects, user_agent};
 use super::util::{ID_PLACEHOLDER, validate_url_pattern};
 use super::util::snippet_handler::SnippetHandler;
 
@@ -30,6 +29,9 @@ pub struct Basic {
     handler: SnippetHandler,
     /// Pattern for "raw" URLs used to download gists.
     raw_url_pattern: &'static str,
+    /// Pattern for the form body of POST requests used to download gists,
+    /// for hosts that don't serve the raw gist content to GET requests.
+    raw_post_body_pattern: Option<&'static str>,
 }
 
 // Creation functions.
@@ -44,8 +46,15 @@ impl Basic {
         Ok(Basic {
             handler: SnippetHandler::new(id, name, html_url_pattern, gist_id_re)?,
             raw_url_pattern: raw_url_pattern,
+            raw_post_body_pattern: None,
         })
     }
+
+    /// Make the host download gists by POSTing given form body to the raw URL
+    /// (with the ${id} placeholder in the body replaced by gist ID), rather than GETting it.
+    pub fn with_raw_post_body(self, body_pattern: &'static str) -> Self {
+        Basic{raw_post_body_pattern: Some(body_pattern), ..self}
+    }
 }
 
 // Accessors / getters, used for testing of individual host setups.
@@ -62,6 +71,7 @@ impl Basic {
 impl Host for Basic {
     fn id(&self) -> &'static str { self.handler.host_id() }
     fn name(&self) -> &'static str { self.handler.host_name() }
+    fn category(&self) -> &'static str { "Immutable pastebins" }
 
     /// Fetch the gist from remote host.
     fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()> {
@@ -77,6 +87,22 @@ impl Host for Basic {
         self.handler.gist_url(gist)
     }
 
+    /// Return the URL to gist's raw content.
+    /// There is none if the raw content has to be requested with POST.
+    fn raw_url(&self, gist: &Gist) -> io::Result<Option<String>> {
+        try!(self.handler.ensure_host_id(gist));
+        if self.raw_post_body_pattern.is_some() {
+            return Ok(None);
+        }
+        let gist = self.handler.resolve_gist(gist);
+        Ok(Some(self.raw_url_for(&*gist)))
+    }
+
+    /// Check if given URL points to a paste's browser website.
+    fn recognizes_url(&self, url: &str) -> bool {
+        self.handler.recognizes_url(url)
+    }
+
     /// Return a Gist based on URL to a paste's browser website.
     fn resolve_url(&self, url: &str) -> Option<io::Result<Gist>> {
         self.handler.resolve_url(url)
@@ -89,24 +115,53 @@ impl Basic {
     fn download_gist(&self, gist: &Gist) -> io::Result<()> {
         let http = http_client();
 
-        // Download the gist using the raw URL pattern.
-        let url = self.raw_url_pattern.replace(ID_PLACEHOLDER, gist.id.as_ref().unwrap());
+        // Download the gist using the raw URL pattern (and the POST body pattern, if any).
+        let url = self.raw_url_for(gist);
         debug!("Downloading {} gist from {}", self.name(), url);
-        let resp = try!(http.get(&url)
-            .header(UserAgent(USER_AGENT.clone()))
-            .send()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e)));
+        let post_body = self.raw_post_body_for(gist.id.as_ref().unwrap());
+        let resp = try!(send_following_redirects(&url, |url| {
+            let request = match post_body {
+                Some(ref body) => {
+                    trace!("POSTing `{}` to {}", body, url);
+                    http.post(url).header(ContentType::form_url_encoded()).body(&**body)
+                },
+                None => http_get(&http, url),
+            };
+            request.header(user_agent(None))
+        }).map_err(http_error));
+        // Otherwise, we'd store an error page (or a refused redirect) as the gist.
+        if !resp.status.is_success() {
+            return Err(io::Error::new(io::ErrorKind::Other, format!(
+                "HTTP {} when downloading gist {} from {}", resp.status, gist.uri, url)));
+        }
 
         self.handler.store_gist(gist, resp)?;
         Ok(())
     }
+
+    /// Build the raw URL of an already resolved gist.
+    fn raw_url_for(&self, gist: &Gist) -> String {
+        self.raw_url_pattern.replace(ID_PLACEHOLDER, gist.id.as_ref().unwrap())
+    }
+
+    /// Build the body of the POST request downloading the gist with given ID,
+    /// if the host needs one.
+    pub(crate) fn raw_post_body_for(&self, id: &str) -> Option<String> {
+        self.raw_post_body_pattern.map(|p| p.replace(ID_PLACEHOLDER, id))
+    }
 }
 
 
 // TODO: these are really tests for ImmutableGistHandler, move them accordingly
 #[cfg(test)]
 mod tests {
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
     use regex::Regex;
+    use gist::{Gist, Uri};
+    use hosts::{bpaste, FetchMode, Host};
     use super::Basic;
 
     const ID: &'static str = "foo";
@@ -127,6 +182,82 @@ mod tests {
         assert!(format!("{}", error).contains("placeholder"));
     }
 
+    #[test]
+    fn raw_post_body() {
+        let host = Basic::new(
+            ID, NAME, "http://example.com/raw/${id}", "http://example.com/${id}", ID_RE.clone())
+            .unwrap();
+        assert_eq!(None, host.raw_post_body_for("abc"));
+
+        let host = host.with_raw_post_body("paste=${id}&format=raw");
+        assert_eq!(Some("paste=abc&format=raw".into()), host.raw_post_body_for("abc"));
+    }
+
+    /// Serve given raw HTTP responses (one per connection) on a local port.
+    /// Returns the port number.
+    fn serve(responses: Vec<&'static str>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                let count = stream.read(&mut buf).unwrap();
+                if count == 0 { break }
+                request.extend_from_slice(&buf[..count]);
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        port
+    }
+
+    /// Build the raw URL pattern for gists served from given local port over plain HTTP.
+    fn local_raw_url(port: u16) -> String {
+        format!("http://127.0.0.1:{}/raw/${{id}}", port)
+    }
+
+    /// Create a Basic host which downloads gists using given raw URL pattern.
+    /// It pretends to be bpaste.net, as gist URIs need to refer to a known host.
+    fn local_http_host(raw_url: &'static str) -> Basic {
+        Basic::new(bpaste::ID, NAME, raw_url, "http://example.com/${id}", ID_RE.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn http_redirect_followed_on_http_host() {
+        // URL patterns are &'static str, as they're normally literals.
+        lazy_static! {
+            static ref RAW_URL: String = local_raw_url(serve(vec![
+                "HTTP/1.1 302 Found\r\nLocation: /moved\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n",
+                "HTTP/1.1 200 OK\r\nContent-Length: 14\r\nConnection: close\r\n\r\n\
+                 echo redirect\n",
+            ]));
+        }
+        let host = local_http_host(&RAW_URL);
+        let gist = Gist::from_uri(Uri::from_name(bpaste::ID, "redirected").unwrap());
+        host.fetch_gist(&gist, FetchMode::Always).unwrap();
+
+        let mut content = String::new();
+        fs::File::open(gist.binary_path()).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!("echo redirect\n", content);
+    }
+
+    #[test]
+    fn error_response_not_stored() {
+        lazy_static! {
+            static ref RAW_URL: String = local_raw_url(serve(vec![
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 9\r\nConnection: close\r\n\r\n\
+                 Not Found",
+            ]));
+        }
+        let host = local_http_host(&RAW_URL);
+        let gist = Gist::from_uri(Uri::from_name(bpaste::ID, "missing").unwrap());
+        assert!(host.fetch_gist(&gist, FetchMode::Always).is_err());
+        assert!(!gist.is_local());
+    }
+
     #[test]
     fn invalid_html_url() {
         let error = Basic::new(
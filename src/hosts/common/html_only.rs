@@ -2,17 +2,16 @@
 
 use std::error::Error;
 use std::io::{self, Read};
+use std::path::Path;
 
 use antidote::Mutex;
-use hyper::header::UserAgent;
 use regex::Regex;
 use select::document::Document;
 use select::predicate::Predicate;
 
-use ::USER_AGENT;
 use gist::Gist;
 use hosts::{FetchMode, Host};
-use util::{http_client, LINESEP};
+use util::{http_client, http_error, http_get, send_following_redirects, user_agent};
 use super::util::ID_PLACEHOLDER;
 use super::util::snippet_handler::SnippetHandler;
 
@@ -61,6 +60,7 @@ impl<P: Predicate + Clone + Send> HtmlOnly<P> {
 impl<P: Predicate + Clone + Send> Host for HtmlOnly<P> {
     fn id(&self) -> &'static str { self.handler.host_id() }
     fn name(&self) -> &'static str { self.handler.host_name() }
+    fn category(&self) -> &'static str { "Immutable pastebins" }
 
     /// Fetch the gist from remote host.
     fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()> {
@@ -72,11 +72,24 @@ impl<P: Predicate + Clone + Send> Host for HtmlOnly<P> {
         Ok(())
     }
 
+    /// Save the gist into given file, extracting its code from the HTML page.
+    fn download_gist_to(&self, gist: &Gist, path: &Path) -> io::Result<u64> {
+        self.handler.ensure_host_id(gist)?;
+        let gist = self.handler.resolve_gist(gist);
+        let code = self.download_code(&*gist)?;
+        self.handler.save_gist(&*gist, code.as_bytes(), path)
+    }
+
     /// Return the URL to gist's HTML website.
     fn gist_url(&self, gist: &Gist) -> io::Result<String> {
         self.handler.gist_url(gist)
     }
 
+    /// Check if given URL points to a paste's browser website.
+    fn recognizes_url(&self, url: &str) -> bool {
+        self.handler.recognizes_url(url)
+    }
+
     /// Return a Gist based on URL to a paste's browser website.
     fn resolve_url(&self, url: &str) -> Option<io::Result<Gist>> {
         self.handler.resolve_url(url)
@@ -86,20 +99,26 @@ impl<P: Predicate + Clone + Send> Host for HtmlOnly<P> {
 // Fetching gists.
 impl<P: Predicate + Clone + Send> HtmlOnly<P> {
     /// Download given gist.
+    fn download_gist(&self, gist: &Gist) -> io::Result<()> {
+        let code = self.download_code(gist)?;
+        self.handler.store_gist(gist, code.as_bytes())?;
+        Ok(())
+    }
+
+    /// Download the code of given gist.
     ///
     /// The gist is downloaded from the HTML URL and its code is extracted
     /// using the stored HTML predicate.
-    fn download_gist(&self, gist: &Gist) -> io::Result<()> {
+    fn download_code(&self, gist: &Gist) -> io::Result<String> {
         let http = http_client();
 
         // Download the gist using the HTML URL pattern.
         let url = self.handler.html_url_pattern()
             .replace(ID_PLACEHOLDER, gist.id.as_ref().unwrap());
         debug!("Downloading {} gist from {}", self.name(), url);
-        let mut resp = try!(http.get(&url)
-            .header(UserAgent(USER_AGENT.clone()))
-            .send()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e)));
+        let mut resp = try!(send_following_redirects(&url, |url| {
+            http_get(&http, url).header(user_agent(None))
+        }).map_err(http_error));
 
         let mut html = String::new();
         resp.read_to_string(&mut html)?;
@@ -111,21 +130,14 @@ impl<P: Predicate + Clone + Send> HtmlOnly<P> {
                 s.push_str(node.text().as_str()); s
             });
 
-        // Ensure it ends with a newline, avoiding reallocation if possible.
-        if !code.ends_with(LINESEP) {
-            let code_len = code.len();
-            if code_len - code.trim_right().len() >= LINESEP.len() {
-                // TODO: replace with String::splice when it's stable
-                unsafe {
-                    code[code_len - LINESEP.len()..].as_bytes_mut()
-                       .copy_from_slice(LINESEP.as_bytes());
-                }
-            } else {
-                code = code.trim_right().to_owned() + LINESEP;
-            }
+        // Ensure it ends with a newline.
+        // Note that this is always \n, regardless of the platform,
+        // as we don't want to alter the line endings of gist content.
+        if !code.ends_with('\n') {
+            let trimmed_len = code.trim_right().len();
+            code.truncate(trimmed_len);
+            code.push('\n');
         }
-
-        self.handler.store_gist(gist, code.as_bytes())?;
-        Ok(())
+        Ok(code)
     }
 }
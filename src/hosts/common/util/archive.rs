@@ -0,0 +1,131 @@
+//! Module for extracting gists that hosts deliver as archives, rather than file by file.
+//!
+//! Support for the actual archive formats is optional:
+//! zip archives are only handled when gisht is built with the `zip` feature.
+
+#[cfg(feature = "zip")] use std::fs;
+#[cfg(feature = "zip")] use std::io::{self, Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(feature = "zip")] use hyper::client::Response;
+#[cfg(feature = "zip")] use hyper::header::ContentType;
+#[cfg(feature = "zip")] use hyper::mime::{Mime, SubLevel, TopLevel};
+#[cfg(feature = "zip")] use zip::ZipArchive;
+
+
+/// Return the path that an archive entry of given name should be extracted to,
+/// provided it lies inside `dir`.
+///
+/// Entries with absolute paths, or those which refer to parent directories
+/// (and could thus escape `dir`, a.k.a. "zip slip"), result in None.
+#[cfg_attr(not(feature = "zip"), allow(dead_code))]
+pub fn entry_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut path = dir.to_owned();
+    let mut is_empty = true;
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => { path.push(part); is_empty = false; }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    if is_empty { None } else { Some(path) }
+}
+
+
+/// Whether given HTTP response carries a zip archive.
+#[cfg(feature = "zip")]
+pub fn is_zip(response: &Response) -> bool {
+    match response.headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Application, SubLevel::Ext(ref sub), _))) =>
+            sub == "zip" || sub == "x-zip-compressed",
+        _ => false,
+    }
+}
+
+/// Extract the zip archive from given HTTP response into `dir`.
+/// Returns the paths of extracted files, in the order they appear in the archive.
+#[cfg(feature = "zip")]
+pub fn extract_zip_response(response: &mut Response, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    // Reading the archive's central directory requires seeking, which responses don't support.
+    let mut body = Vec::new();
+    try!(response.read_to_end(&mut body));
+    extract_zip(Cursor::new(body), dir)
+}
+
+/// Extract given zip archive into `dir`.
+/// Returns the paths of extracted files, in the order they appear in the archive.
+///
+/// If any of the entries would end up outside of `dir`, an error is returned.
+/// Entries are always written as regular files (even if the archive says they're symlinks).
+#[cfg(feature = "zip")]
+pub fn extract_zip<R: Read + Seek>(reader: R, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut archive = try!(ZipArchive::new(reader));
+    let mut files = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut entry = try!(archive.by_index(i));
+        let path = try!(entry_path(dir, entry.name()).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData, format!(
+                "archive entry `{}` would be extracted outside of {}",
+                entry.name(), dir.display()))));
+
+        if entry.name().ends_with('/') {
+            try!(fs::create_dir_all(&path));
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            try!(fs::create_dir_all(parent));
+        }
+        trace!("Extracting archive entry {} to {}", entry.name(), path.display());
+        let mut file = try!(fs::File::create(&path));
+        let byte_count = try!(io::copy(&mut entry, &mut file));
+        trace!("Wrote {} byte(s) to {}", byte_count, path.display());
+        files.push(path);
+    }
+    Ok(files)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::entry_path;
+
+    #[test]
+    fn entry_paths() {
+        let dir = Path::new("/home/user/.gisht/gists/gl/anonymous/foo");
+        assert_eq!(Some(dir.join("main.py")), entry_path(dir, "main.py"));
+        assert_eq!(Some(dir.join("lib").join("util.py")), entry_path(dir, "lib/util.py"));
+        assert_eq!(Some(dir.join("main.py")), entry_path(dir, "./main.py"));
+        assert_eq!(None, entry_path(dir, ""));
+        assert_eq!(None, entry_path(dir, "."));
+        assert_eq!(None, entry_path(dir, "../evil.sh"));
+        assert_eq!(None, entry_path(dir, "lib/../../evil.sh"));
+        assert_eq!(None, entry_path(dir, "/etc/passwd"));
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_slip_rejected() {
+        use std::env;
+        use std::fs;
+        use std::io::{Cursor, Write};
+        use zip::ZipWriter;
+        use zip::write::FileOptions;
+        use super::extract_zip;
+
+        let dir = env::temp_dir().join("gisht-archive-test-zip_slip_rejected");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("main.sh", FileOptions::default()).unwrap();
+        writer.write_all(b"echo hi\n").unwrap();
+        writer.start_file("../evil.sh", FileOptions::default()).unwrap();
+        writer.write_all(b"rm -rf ~\n").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+
+        assert!(extract_zip(Cursor::new(archive), &dir).is_err());
+        assert!(!dir.parent().unwrap().join("evil.sh").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,5 +1,6 @@
 //! Utility code shared by common gist host implementations.
 
+pub mod archive;
 pub mod snippet_handler;
 
 
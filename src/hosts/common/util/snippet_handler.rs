@@ -4,12 +4,14 @@ use std::borrow::Cow;
 use std::error::Error;
 use std::fs;
 use std::io::{self, Read};
+use std::path::Path;
 
 use regex::{self, Regex};
 
 use gist::{self, Gist};
 use hosts::FetchMode;
-use util::{mark_executable, symlink_file};
+use interrupt;
+use util::{link_binary, mark_executable};
 use super::{HTTP, HTTPS, ID_PLACEHOLDER, validate_url_pattern};
 
 
@@ -134,6 +136,7 @@ impl SnippetHandler {
         // so we need to ensure its *parent* exists.
         let path = gist.path();
         debug!("Saving gist {} as {}", gist.uri, path.display());
+        let _guard = interrupt::guard_fetch(gist);
         try!(fs::create_dir_all(path.parent().unwrap()));
         let mut file = try!(fs::OpenOptions::new()
             .create(true).write(true).truncate(true)
@@ -150,16 +153,29 @@ impl SnippetHandler {
         try!(mark_executable(&executable));
         trace!("Marked gist file as executable: {}", executable.display());
 
-        // Create a symlink in the binary directory.
-        let binary = gist.binary_path();
-        if !binary.exists() {
-            try!(fs::create_dir_all(binary.parent().unwrap()));
-            try!(symlink_file(&executable, &binary));
-            trace!("Created symlink to gist executable: {}", binary.display());
+        // Create a symlink (or copy) in the binary directory.
+        try!(link_binary(&executable, &gist.binary_path()));
+
+        // Keep whatever info was obtained when resolving the gist (like its language),
+        // as it won't be available once the gist is ran from its local copy.
+        if let Err(e) = gist.store_info() {
+            warn!("Failed to store info of gist {}: {}", gist.uri, e);
         }
 
         Ok(byte_count as usize)
     }
+
+    /// Save the downloaded content of a gist into given file, outside of the gists directory.
+    /// Returns the number of bytes written.
+    ///
+    /// Unlike `store_gist`, this doesn't make the file executable nor link it as gist's binary.
+    pub fn save_gist<R: Read>(&self, gist: &Gist, mut content: R, path: &Path) -> io::Result<u64> {
+        debug!("Saving gist {} into {}", gist.uri, path.display());
+        let mut file = try!(fs::File::create(path));
+        let byte_count = try!(io::copy(&mut content, &mut file));
+        trace!("Wrote {} byte(s) to {}", byte_count, path.display());
+        Ok(byte_count)
+    }
 }
 
 // Working with gist URLs.
@@ -176,6 +192,12 @@ impl SnippetHandler {
         Ok(url)
     }
 
+    /// Check if given URL matches the pattern of gist's browser website URLs.
+    /// This method can be pass-through called by Host::recognizes_url.
+    pub fn recognizes_url(&self, url: &str) -> bool {
+        self.html_url_re.is_match(&*self.canonicalize_url(url))
+    }
+
     /// Return a Gist based on URL to a gist's browser website.
     /// This method can be pass-through called by Host::resolve_url.
     pub fn resolve_url(&self, url: &str) -> Option<io::Result<Gist>> {
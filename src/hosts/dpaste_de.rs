@@ -1,5 +1,7 @@
 //! Module implementing dpaste.de as gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -9,11 +11,11 @@ use hosts::common::Basic;
 pub const ID: &'static str = "dp";
 
 /// Create the dpaste.de host implementation.
-pub fn create() -> Basic {
+pub fn create() -> Result<Basic, Box<Error>> {
     Basic::new(ID, "dpaste.de",
                "https://dpaste.de/${id}/raw",
                "https://dpaste.de/${id}",
-               Regex::new("[A-Za-z]+").unwrap()).unwrap()
+               try!(Regex::new("[A-Za-z]+")))
 }
 
 
@@ -23,7 +25,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = create();
+        let host = create().unwrap();
         let html_url: String = host.html_url_origin();
 
         let valid_html_urls: Vec<(/* URL */ String,
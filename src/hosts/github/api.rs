@@ -1,19 +1,28 @@
 //! Module for interacting with GitHub API.
 
 use std::borrow::Cow;
+use std::cmp;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::io;
+use std::str;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use hyper;
 use hyper::client::{Client, Response};
-use hyper::header::UserAgent;
-use serde_json::Value as Json;
+use hyper::header::{Authorization, ContentType, Headers};
+use hyper::status::StatusCode;
+use serde_json::{self, Value as Json};
+use serde_json::builder::ObjectBuilder;
+use time;
 use url::Url;
+use url::percent_encoding::percent_decode;
 
-use ::USER_AGENT;
+use config;
 use ext::hyper::header::Link;
 use gist::{self, Datum, Gist};
-use util::{http_client, read_json};
+use util::{http_client, http_error, read_json, send_following_redirects, user_agent};
 use super::ID;
 
 
@@ -25,9 +34,39 @@ pub const BASE_URL: &'static str = "https://api.github.com";
 /// https://gist.github.com/anonymous/42 and https://gist.github.com/42
 const ANONYMOUS: &'static str = "anonymous";
 
+/// Where the GitHub personal access token comes from, for the error messages.
+/// The token is only needed for operations that modify gists.
+const TOKEN_SOURCE: &'static str = "GITHUB_TOKEN (or `github.token` in the config file)";
+
+/// Suffix of the User-Agent header for GitHub API requests.
+/// GitHub asks for the agent to identify the application, so this points to its homepage.
+const USER_AGENT_SUFFIX: &'static str = "(+https://github.com/Xion/gisht)";
+
 /// Size of the GitHub response page in items (e.g. gists).
 const RESPONSE_PAGE_SIZE: usize = 50;
 
+/// Maximum size (in bytes) of a gist file whose content included in the gist info
+/// will be used directly, rather than cloning the gist.
+const MAX_INLINE_CONTENT_SIZE: u64 = 64 * 1024;
+
+
+// HTTP client
+
+/// HTTP client capable of making GET requests to GitHub API.
+///
+/// This is normally just hyper's Client,
+/// but tests can substitute it with a fake that returns canned responses.
+trait HttpGet: fmt::Debug {
+    /// Send a GET request to given URL, with given headers.
+    fn get(&self, url: &str, headers: Headers) -> hyper::Result<Response>;
+}
+
+impl HttpGet for Client {
+    fn get(&self, url: &str, headers: Headers) -> hyper::Result<Response> {
+        send_following_redirects(url, |url| Client::get(self, url).headers(headers.clone()))
+    }
+}
+
 
 // Iterating over gists
 
@@ -35,7 +74,23 @@ const RESPONSE_PAGE_SIZE: usize = 50;
 #[inline]
 pub fn iter_gists(owner: &str) -> GistsIterator {
     // TODO: when `impl Trait` is available, GistsIterator no longer has to be public
-    GistsIterator::new(owner)
+    GistsIterator::new(owner, &format!("users/{}/gists", owner), Box::new(http_client()))
+}
+
+/// Iterate over GitHub gists belonging to given owner that were updated after given time.
+#[inline]
+pub fn iter_gists_since(owner: &str, since: SystemTime) -> GistsIterator {
+    iter_gists(owner).since(since)
+}
+
+/// Iterate over all gists (including secret ones) of the authenticated user,
+/// whose login is given as `owner`.
+///
+/// Note that without the access token, GitHub would return *all* public gists here,
+/// so the token should be checked beforehand (e.g. with `authenticated_user`).
+#[inline]
+pub fn iter_own_gists(owner: &str) -> GistsIterator {
+    GistsIterator::new(owner, "gists", Box::new(http_client()))
 }
 
 
@@ -47,15 +102,16 @@ pub struct GistsIterator<'o> {
     gists_url: Option<String>,
     gists_json_array: Option<Vec<Json>>,
     index: usize,  // within the above array
+    pages_left: Option<usize>,
     // Other.
-    http: Client,
+    http: Box<HttpGet>,
 }
 
 impl<'o> GistsIterator<'o> {
-    fn new(owner: &'o str) -> Self {
+    fn new(owner: &'o str, path: &str, http: Box<HttpGet>) -> Self {
         let gists_url = {
             let mut url = Url::parse(BASE_URL).unwrap();
-            url.set_path(&format!("users/{}/gists", owner));
+            url.set_path(path);
             url.query_pairs_mut()
                 .append_pair("per_page", &RESPONSE_PAGE_SIZE.to_string());
             url.into_string()
@@ -67,8 +123,28 @@ impl<'o> GistsIterator<'o> {
             gists_url: Some(gists_url),
             gists_json_array: None,
             index: 0,
-            http: http_client(),
+            pages_left: None,
+            http: http,
+        }
+    }
+
+    /// Limit the number of result pages that will be fetched from GitHub API.
+    /// The iteration stops once the gists from the last allowed page are exhausted.
+    #[inline]
+    pub fn max_pages(mut self, count: usize) -> Self {
+        self.pages_left = Some(count);
+        self
+    }
+
+    /// Only iterate over the gists that were updated after given time.
+    /// This must be called before the iteration starts.
+    pub fn since(mut self, time: SystemTime) -> Self {
+        if let Some(ref mut gists_url) = self.gists_url {
+            let mut url = Url::parse(gists_url).unwrap();
+            url.query_pairs_mut().append_pair("since", &format_timestamp(time));
+            *gists_url = url.into_string();
         }
+        self
     }
 }
 
@@ -84,6 +160,11 @@ impl<'o> Iterator for GistsIterator<'o> {
         // If we don't have any cached gists in JSON form,
         // talk to the GitHub API to obtain the next (or first) page.
         if self.gists_json_array.is_none() && self.gists_url.is_some() {
+            if self.pages_left == Some(0) {
+                debug!("Stopping at the page limit when listing {}'s GitHub gists", self.owner);
+                self.gists_url = None;
+                return None;
+            }
             if let Err(error) = self.try_fetch_gists() {
                 warn!("Error listing {}'s GitHub gists: {}", self.owner, error);
                 return None;
@@ -121,14 +202,22 @@ impl<'o> GistsIterator<'o> {
 
         let gists_url = self.gists_url.clone().unwrap();
         trace!("Listing GitHub gists from {}", gists_url);
+        if let Some(ref mut count) = self.pages_left {
+            *count -= 1;
+        }
 
-        let mut resp = try!(self.http.get(&*gists_url)
-            .header(UserAgent(USER_AGENT.clone()))
-            .send());
+        // Authenticated requests have a much higher rate limit,
+        // so use the access token if it's available.
+        let mut headers = Headers::new();
+        headers.set(user_agent(Some(USER_AGENT_SUFFIX)));
+        if let Some(token) = access_token() {
+            headers.set(Authorization(format!("token {}", token)));
+        }
+        let http = &self.http;
+        let mut resp = try!(send_with_retry(|| http.get(&gists_url, headers.clone())));
 
         // Parse the response as JSON array and extract gist names from it.
-        // TODO: handle the (unlikely) JSON parse error here
-        let gists_json = read_json(&mut resp).unwrap();
+        let gists_json = try!(read_json(&mut resp));
         if let Json::Array(gists) = gists_json {
             let page_size = gists.len();
             self.gists_json_array = Some(gists);
@@ -161,7 +250,7 @@ impl<'o> GistsIterator<'o> {
             },
         };
 
-        let uri = gist::Uri::new(ID, self.owner, name).unwrap();
+        let uri = gist::Uri::new(ID, self.owner, &*name).unwrap();
         if uri.owner != self.owner {
             warn!("Foreign gist ({}, ID={}) found when iterating {}'s gists",
                 uri, id, self.owner);
@@ -169,9 +258,11 @@ impl<'o> GistsIterator<'o> {
         }
         trace!("GitHub gist found ({}) with ID={}", uri, id);
 
-        // Include the gist Info with fields that are commonly used by gist commands.
+        // Include the gist Info with fields that are commonly used by gist commands,
+        // or shown when listing the gists.
         // TODO: determine the complete set of fields that can be fetched here
-        let info = build_gist_info(&gist, &[Datum::RawUrl, Datum::BrowserUrl]);
+        let info = build_gist_info(&gist, Some(id), &[Datum::RawUrl, Datum::BrowserUrl,
+                                                      Datum::Description, Datum::Language]);
         let result = Gist::new(uri, id).with_info(info);
         Some(result)
     }
@@ -182,18 +273,141 @@ impl<'o> GistsIterator<'o> {
 
 /// Retrieve information/metadata about a gist.
 /// Returns a Json object with the parsed GitHub response.
+#[inline]
 pub fn get_gist_info(gist_id: &str) -> io::Result<Json> {
+    fetch_gist_info(&http_client(), gist_id)
+}
+
+/// Retrieve information/metadata about a gist using given HTTP client.
+fn fetch_gist_info(http: &HttpGet, gist_id: &str) -> io::Result<Json> {
     let mut gist_url = Url::parse(BASE_URL).unwrap();
     gist_url.set_path(&format!("gists/{}", gist_id));
 
     debug!("Getting GitHub gist info from {}", gist_url);
-    let mut resp = try!(simple_get(gist_url));
+    let mut resp = try!(simple_get(http, gist_url));
     read_json(&mut resp)
 }
 
+/// Retrieve the login of the GitHub user that the access token belongs to.
+/// The token must be present in the environment.
+#[inline]
+pub fn authenticated_user() -> io::Result<String> {
+    let token = try!(require_access_token("list the authenticated user's GitHub gists"));
+    fetch_authenticated_user(&http_client(), &token)
+}
+
+/// Retrieve the login of the GitHub user with given access token,
+/// using given HTTP client.
+fn fetch_authenticated_user(http: &HttpGet, token: &str) -> io::Result<String> {
+    let mut user_url = Url::parse(BASE_URL).unwrap();
+    user_url.set_path("user");
+
+    debug!("Getting the authenticated GitHub user from {}", user_url);
+    let mut headers = Headers::new();
+    headers.set(user_agent(Some(USER_AGENT_SUFFIX)));
+    headers.set(Authorization(format!("token {}", token)));
+    let mut resp = try!(send_with_retry(|| http.get(user_url.as_str(), headers.clone()))
+        .map_err(http_error));
+    match resp.status {
+        StatusCode::Ok => {},
+        StatusCode::Unauthorized => return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+            format!("GitHub rejected the access token from {}", TOKEN_SOURCE))),
+        status => return Err(io::Error::new(io::ErrorKind::Other, format!(
+            "unexpected response when getting the authenticated GitHub user: HTTP {}", status))),
+    }
+
+    let user = try!(read_json(&mut resp));
+    user.pointer("/login").and_then(Json::as_str).map(String::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData,
+            "no login in the authenticated GitHub user's data"))
+}
+
+/// Check that gists can be deleted, i.e. that the GitHub access token is present
+/// in the environment (though not whether it belongs to the gist owner).
+pub fn check_delete_access() -> io::Result<()> {
+    require_access_token("delete GitHub gists").map(|_| ())
+}
+
+/// Delete the gist with given ID.
+/// The GitHub access token of the gist owner must be present in the environment.
+pub fn delete_gist(gist_id: &str) -> io::Result<()> {
+    let token = try!(require_access_token("delete GitHub gists"));
+
+    let mut gist_url = Url::parse(BASE_URL).unwrap();
+    gist_url.set_path(&format!("gists/{}", gist_id));
+
+    debug!("Deleting GitHub gist with DELETE {}", gist_url);
+    let http = http_client();
+    let resp = try!(send_with_retry(|| {
+        http.delete(gist_url.as_str())
+            .header(user_agent(Some(USER_AGENT_SUFFIX)))
+            .header(Authorization(format!("token {}", token)))
+            .send()
+    }).map_err(http_error));
+    match resp.status {
+        StatusCode::NoContent => Ok(()),
+        StatusCode::NotFound => Err(io::Error::new(io::ErrorKind::NotFound,
+            format!("GitHub gist with ID={} not found", gist_id))),
+        StatusCode::Unauthorized | StatusCode::Forbidden =>
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, format!(
+                "not allowed to delete GitHub gist with ID={} (HTTP {})", gist_id, resp.status))),
+        status => Err(io::Error::new(io::ErrorKind::Other, format!(
+            "unexpected response when deleting GitHub gist with ID={}: HTTP {}", gist_id, status))),
+    }
+}
+
+/// Create a new gist consisting of a single file with given name and content.
+/// The GitHub access token of the user who will own the gist must be present in the environment.
+///
+/// Returns the JSON representation of the newly created gist.
+pub fn create_gist(filename: &str, content: &str,
+                   description: Option<&str>, public: bool) -> io::Result<Json> {
+    let token = try!(require_access_token("create GitHub gists"));
+
+    let mut gists_url = Url::parse(BASE_URL).unwrap();
+    gists_url.set_path("gists");
+    let payload = serde_json::to_string(
+        &new_gist_payload(filename, content, description, public)).unwrap();
+
+    debug!("Creating GitHub gist `{}` with POST {}", filename, gists_url);
+    let http = http_client();
+    let mut resp = try!(send_with_retry(|| {
+        http.post(gists_url.as_str())
+            .header(user_agent(Some(USER_AGENT_SUFFIX)))
+            .header(Authorization(format!("token {}", token)))
+            .header(ContentType::json())
+            .body(&payload as &str)
+            .send()
+    }).map_err(http_error));
+    match resp.status {
+        StatusCode::Created => read_json(&mut resp),
+        StatusCode::Unauthorized | StatusCode::Forbidden =>
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, format!(
+                "not allowed to create GitHub gists (HTTP {})", resp.status))),
+        StatusCode::UnprocessableEntity => Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("GitHub rejected the gist `{}` as invalid", filename))),
+        status => Err(io::Error::new(io::ErrorKind::Other, format!(
+            "unexpected response when creating GitHub gist `{}`: HTTP {}", filename, status))),
+    }
+}
+
+/// Build the JSON payload of a request that creates a single-file gist.
+fn new_gist_payload(filename: &str, content: &str,
+                    description: Option<&str>, public: bool) -> Json {
+    ObjectBuilder::new()
+        .insert("description", description.unwrap_or(""))
+        .insert("public", public)
+        .insert_object("files", |files| {
+            files.insert_object(filename, |file| file.insert("content", content))
+        })
+        .build()
+}
+
 /// Build the complete gist Info from its GitHub JSON representation.
 /// If `data` is non-empty, only selected fields are included in the info.
-pub fn build_gist_info(info: &Json, data: &[Datum]) -> gist::Info {
+///
+/// The `gist_id` (if known) is used for Datum::Id when the JSON doesn't include it.
+pub fn build_gist_info(info: &Json, gist_id: Option<&str>, data: &[Datum]) -> gist::Info {
     let mut data: Vec<_> = data.to_vec();
     if data.is_empty() {
         data = Datum::iter_variants().collect();
@@ -205,7 +419,6 @@ pub fn build_gist_info(info: &Json, data: &[Datum]) -> gist::Info {
             Datum::Id => "id",
             Datum::Description => "description",
             Datum::BrowserUrl => "html_url",
-            Datum::RawUrl => "git_pull_url",
             Datum::CreatedAt => "created_at",
             Datum::UpdatedAt => "updated_at",
         };
@@ -213,7 +426,9 @@ pub fn build_gist_info(info: &Json, data: &[Datum]) -> gist::Info {
     let mut result = gist::InfoBuilder::new();
     for datum in data {
         if let Some(field) = INFO_FIELDS.get(&datum) {
-            match info.find(field).and_then(Json::as_str) {
+            let value = info.find(field).and_then(Json::as_str);
+            let value = if datum == Datum::Id { value.or(gist_id) } else { value };
+            match value {
                 Some(value) => { result.set(datum, value); },
                 None => { warn!("Missing info key '{}' in gist JSON", field); },
             }
@@ -221,12 +436,22 @@ pub fn build_gist_info(info: &Json, data: &[Datum]) -> gist::Info {
             // Special-cased data that are more complicated to get.
             match datum {
                 Datum::Owner => { result.set(datum, gist_owner_from_info(&info)); },
+                Datum::RawUrl => {
+                    let raw_url = gist_name_from_info(&info)
+                        .and_then(|name| gist_raw_url_from_info(&info, &name));
+                    match raw_url {
+                        Some(url) => { result.set(datum, url); },
+                        None => { warn!("Couldn't retrieve the raw URL of GitHub gist's file"); },
+                    }
+                },
                 Datum::Language => {
                     match gist_language_from_info(&info) {
                         Some(lang) => { result.set(datum, &*lang); },
                         None => { trace!("Couldn't retrieve the language of GitHub gist"); },
                     }
                 },
+                // This one requires the local copy of the gist, see GitHub::gist_info.
+                Datum::LocalCopy => {},
                 _ => { panic!("Unexpected gist info data piece: {:?}", datum); },
             }
         }
@@ -241,9 +466,77 @@ pub fn build_gist_info(info: &Json, data: &[Datum]) -> gist::Info {
 ///
 /// The gist name is defined to be the name of its first file,
 /// as this is how GitHub page itself picks it.
-pub fn gist_name_from_info(info: &Json) -> Option<&str> {
+/// It is sanitized, however, so that it can be safely used as a filename.
+pub fn gist_name_from_info(info: &Json) -> Option<Cow<str>> {
+    let filenames = try_opt!(gist_filenames_from_info(info));
+    Some(sanitize_filename(filenames[0]))
+}
+
+/// Retrieve the URL to raw content of the gist's file from the parsed JSON of gist info.
+/// This is the file that the gist is named after, or the first one if there is no such file.
+pub fn gist_raw_url_from_info<'i>(info: &'i Json, name: &str) -> Option<&'i str> {
     let filenames = try_opt!(gist_filenames_from_info(info));
-    Some(filenames[0])
+    let filename = filenames.iter().find(|f| sanitize_filename(f) == name)
+        .unwrap_or(&filenames[0]);
+    info.find("files").and_then(|fs| fs.find(filename))
+        .and_then(|f| f.find("raw_url")).and_then(Json::as_str)
+}
+
+/// Retrieve the content of the gist's file from the parsed JSON of gist info,
+/// provided the gist is small, has only one file, and its content isn't truncated.
+pub fn gist_inline_content_from_info(info: &Json) -> Option<&str> {
+    let files = try_opt!(info.find("files").and_then(|fs| fs.as_object()));
+    if files.len() != 1 {
+        return None;
+    }
+    let file = files.values().next().unwrap();
+    let truncated = file.find("truncated").and_then(Json::as_bool).unwrap_or(true);
+    let size = try_opt!(file.find("size").and_then(Json::as_u64));
+    if truncated || size > MAX_INLINE_CONTENT_SIZE {
+        return None;
+    }
+    file.find("content").and_then(Json::as_str)
+}
+
+/// Convert the name of a gist file into one that's safe to use in the filesystem
+/// (and the command line), i.e. as the name of the gist's binary.
+///
+/// This decodes any URL-encoded characters, and replaces whitespace
+/// and other problematic characters with underscores.
+pub fn sanitize_filename(filename: &str) -> Cow<str> {
+    const UNSAFE_CHARS: &'static [char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+    let is_unsafe = |c: char| c.is_whitespace() || c.is_control() || UNSAFE_CHARS.contains(&c);
+
+    let decoded = percent_decode(filename.as_bytes()).decode_utf8_lossy();
+    if decoded.chars().any(&is_unsafe) {
+        Cow::Owned(decoded.chars().map(|c| if is_unsafe(c) { '_' } else { c }).collect())
+    } else {
+        decoded
+    }
+}
+
+/// Retrieve the time of last gist update from the parsed JSON of gist info.
+pub fn gist_updated_at_from_info(info: &Json) -> Option<SystemTime> {
+    let updated_at = try_opt!(info.find("updated_at").and_then(Json::as_str));
+    let tm = match time::strptime(updated_at, "%Y-%m-%dT%H:%M:%SZ") {
+        Ok(tm) => tm,
+        Err(err) => {
+            warn!("Invalid 'updated_at' in gist JSON (`{}`): {}", updated_at, err);
+            return None;
+        },
+    };
+    let secs = tm.to_timespec().sec;
+    if secs < 0 {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(secs as u64))
+}
+
+/// Format a point in time as an ISO 8601 timestamp, the way GitHub API expects it.
+fn format_timestamp(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let tm = time::at_utc(time::Timespec::new(secs as i64, 0));
+    tm.strftime("%Y-%m-%dT%H:%M:%SZ").unwrap().to_string()
 }
 
 /// Retrieve gist owner from the parsed JSON of gist info.
@@ -266,8 +559,9 @@ pub fn gist_language_from_info(info: &Json) -> Option<Cow<str>> {
         return Some(Cow::Borrowed(language));
     }
 
-    // Otherwise, include the languages of all files, in order, w/o repetitions.
-    // (This makes the language of the gist's namesake to appear first).
+    // Otherwise, include the languages of all files, w/o repetitions,
+    // following the sorted order of filenames regardless of how they appear in JSON.
+    // (This makes the language of the gist's namesake -- its first file -- appear first).
     let mut languages = Vec::with_capacity(filenames.len());
     let mut langset: HashSet<&str> = HashSet::new();
     for filename in filenames {
@@ -298,24 +592,108 @@ fn gist_filenames_from_info(info: &Json) -> Option<Vec<&str>> {
 
 // Utility functions
 
+/// Retrieve the GitHub personal access token from the configuration
+/// (e.g. the $GITHUB_TOKEN environment variable), if it's set.
+fn access_token() -> Option<String> {
+    config::current().github_token().map(String::from)
+}
+
+/// Retrieve the GitHub access token, failing if it's not present in the environment.
+/// The `purpose` is used in the error message, as in "... must be set to <purpose>".
+fn require_access_token(purpose: &str) -> io::Result<String> {
+    access_token().ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied,
+        format!("{} must be set to {}", TOKEN_SOURCE, purpose)))
+}
+
 /// Make a simple GET request to GitHub API.
-fn simple_get(url: Url) -> io::Result<Response> {
+fn simple_get(http: &HttpGet, url: Url) -> io::Result<Response> {
     let url = url.into_string();
-    let http = http_client();
-    http.get(&url)
-        .header(UserAgent(USER_AGENT.clone()))
-        .send()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    let mut headers = Headers::new();
+    headers.set(user_agent(Some(USER_AGENT_SUFFIX)));
+    send_with_retry(|| http.get(&url, headers.clone()))
+        .map_err(http_error)
+}
+
+/// Send a request to GitHub API using given closure,
+/// retrying it once if GitHub asks us to wait a while.
+///
+/// This happens when we hit one of the secondary ("abuse") rate limits,
+/// in which case the response will have a Retry-After header.
+fn send_with_retry<F>(mut send: F) -> hyper::Result<Response>
+    where F: FnMut() -> hyper::Result<Response>
+{
+    let resp = try!(send());
+    let wait = match resp.status {
+        StatusCode::Forbidden | StatusCode::TooManyRequests => retry_after(&resp),
+        _ => None,
+    };
+    match wait {
+        Some(wait) => {
+            let wait = wait + retry_jitter();
+            info!("GitHub asked to retry the request later, waiting {}.{:03}s...",
+                wait.as_secs(), wait.subsec_nanos() / 1000000);
+            thread::sleep(wait);
+            send()
+        },
+        None => Ok(resp),
+    }
+}
+
+/// Maximum time we're willing to wait before retrying a GitHub request.
+const MAX_RETRY_WAIT_SECS: u64 = 60;
+
+/// Determine how long to wait before retrying the request,
+/// based on the Retry-After header of the response (if any).
+/// The resulting duration is capped at MAX_RETRY_WAIT_SECS.
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let raw = try_opt!(resp.headers.get_raw("Retry-After"));
+    let value = try_opt!(raw.first().and_then(|v| str::from_utf8(v).ok()));
+    parse_retry_after(value)
+}
+
+/// Parse the value of Retry-After header as a number of seconds to wait.
+/// (GitHub doesn't use the alternative HTTP date format).
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let secs: u64 = try_opt!(value.trim().parse().ok());
+    if secs > MAX_RETRY_WAIT_SECS {
+        warn!("GitHub asked to wait {}s before retrying, capping it at {}s",
+            secs, MAX_RETRY_WAIT_SECS);
+    }
+    Some(Duration::from_secs(cmp::min(secs, MAX_RETRY_WAIT_SECS)))
+}
+
+/// Random-ish, sub-second extra delay before retrying a request,
+/// so that concurrent instances of the program don't retry all at once.
+fn retry_jitter() -> Duration {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos()).unwrap_or(0);
+    Duration::from_millis((nanos % 1000) as u64)
 }
 
 
 #[cfg(test)]
 mod test {
     use std::borrow::Cow;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::io::{self, Cursor, Read, Write};
+    use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+    use std::rc::Rc;
     use std::str::FromStr;
+    use std::time::{Duration, UNIX_EPOCH};
+    use hyper;
+    use hyper::client::Response;
+    use hyper::header::Headers;
+    use hyper::net::NetworkStream;
     use serde_json::Value as Json;
+    use url::Url;
     use util::http_client;
-    use super::{GistsIterator, gist_language_from_info};
+    use gist::Datum;
+    use super::{BASE_URL, GistsIterator, HttpGet, MAX_RETRY_WAIT_SECS, RESPONSE_PAGE_SIZE,
+                build_gist_info, fetch_authenticated_user, fetch_gist_info, gist_language_from_info,
+                gist_raw_url_from_info, gist_inline_content_from_info, gist_name_from_info,
+                gist_updated_at_from_info, new_gist_payload, parse_retry_after,
+                sanitize_filename};
 
     const OWNER: &'static str = "Octocat";
     const GIST_ID: &'static str = "12345";
@@ -328,7 +706,8 @@ mod test {
             gists_url: None,
             gists_json_array: Some(vec![]),
             index: 0,
-            http: http_client(),
+            pages_left: None,
+            http: Box::new(http_client()),
         };
         assert_eq!(None, iter.next());
     }
@@ -347,7 +726,8 @@ mod test {
             gists_url: None,
             gists_json_array: Some(vec![Json::from_str(&gist_json).unwrap()]),
             index: 0,
-            http: http_client(),
+            pages_left: None,
+            http: Box::new(http_client()),
         };
         let gist = iter.next().unwrap();
 
@@ -356,7 +736,105 @@ mod test {
         assert_eq!(GIST_NAME, gist.uri.name);
     }
 
-    // TODO: test GistsIterator with a mock/fake http_client
+    #[test]
+    fn gists_iterator_stops_at_page_limit() {
+        let mut iter = GistsIterator {
+            owner: OWNER,
+            gists_url: Some(format!("{}/users/{}/gists", BASE_URL, OWNER)),
+            gists_json_array: None,
+            index: 0,
+            pages_left: None,
+            http: Box::new(http_client()),
+        }.max_pages(0);
+        // No request should be made, as the page limit has already been reached.
+        assert_eq!(None, iter.next());
+        assert!(iter.gists_url.is_none());
+    }
+
+    #[test]
+    fn gists_iterator_follows_next_page() {
+        let first_url = format!("{}/users/{}/gists?per_page={}",
+            BASE_URL, OWNER, RESPONSE_PAGE_SIZE);
+        let second_url = format!("{}&page=2", first_url);
+        let http = FakeHttp::new()
+            .respond(&first_url, &[("Link", &format!(r#"<{}>; rel="next""#, second_url))],
+                     &gists_page(&[("1", "first")]))
+            .respond(&second_url, &[], &gists_page(&[("2", "second"), ("3", "third")]));
+
+        let iter = GistsIterator::new(OWNER, &format!("users/{}/gists", OWNER), Box::new(http));
+        let names: Vec<_> = iter.map(|g| g.uri.name).collect();
+        assert_eq!(vec!["first", "second", "third"], names);
+    }
+
+    #[test]
+    fn gists_iterator_stops_at_last_page() {
+        let url = format!("{}/users/{}/gists?per_page={}", BASE_URL, OWNER, RESPONSE_PAGE_SIZE);
+        let http = FakeHttp::new()
+            .respond(&url, &[], &gists_page(&[("1", GIST_NAME)]));
+
+        let requested = http.requested.clone();
+        let mut iter = GistsIterator::new(OWNER, &format!("users/{}/gists", OWNER), Box::new(http));
+        assert_eq!(GIST_NAME, iter.next().unwrap().uri.name);
+        assert_eq!(None, iter.next());
+        assert!(iter.gists_url.is_none());
+
+        // Once exhausted, the iterator shouldn't make any more requests.
+        assert_eq!(None, iter.next());
+        assert_eq!(vec![url], *requested.borrow());
+    }
+
+    #[test]
+    fn gists_iterator_since() {
+        let url = format!("{}/users/{}/gists?per_page={}&since=2017-07-14T02%3A40%3A00Z",
+            BASE_URL, OWNER, RESPONSE_PAGE_SIZE);
+        let http = FakeHttp::new()
+            .respond(&url, &[], &gists_page(&[("1", GIST_NAME)]));
+
+        let requested = http.requested.clone();
+        let since = UNIX_EPOCH + Duration::from_secs(1500000000);
+        let iter = GistsIterator::new(OWNER, &format!("users/{}/gists", OWNER), Box::new(http))
+            .since(since);
+        assert_eq!(1, iter.count());
+        assert_eq!(vec![url], *requested.borrow());
+    }
+
+    #[test]
+    fn gist_info_fetched() {
+        let url = format!("{}/gists/{}", BASE_URL, GIST_ID);
+        let http = FakeHttp::new()
+            .respond(&url, &[], &gist_json(GIST_ID, GIST_NAME));
+
+        let info = fetch_gist_info(&http, GIST_ID).unwrap();
+        assert_eq!(Some(GIST_ID), info.pointer("/id").and_then(Json::as_str));
+        assert_eq!(vec![url], *http.requested.borrow());
+    }
+
+    #[test]
+    fn authenticated_user_fetched() {
+        let url = format!("{}/user", BASE_URL);
+        let http = FakeHttp::new()
+            .respond(&url, &[], &format!(r#"{{"login": "{}", "id": 42}}"#, OWNER));
+        assert_eq!(OWNER, fetch_authenticated_user(&http, "token").unwrap());
+    }
+
+    #[test]
+    fn gist_raw_url() {
+        let gist_json = r#"{
+            "id": "12345",
+            "files": {
+                "a.txt": {"raw_url": "https://gist.githubusercontent.com/x/12345/raw/a.txt"},
+                "test gist.sh": {
+                    "raw_url": "https://gist.githubusercontent.com/x/12345/raw/test%20gist.sh"
+                }
+            }
+        }"#;
+        let gist = Json::from_str(gist_json).unwrap();
+        assert_eq!(Some("https://gist.githubusercontent.com/x/12345/raw/test%20gist.sh"),
+                   gist_raw_url_from_info(&gist, "test_gist.sh"));
+        // Falls back to the first file.
+        assert_eq!(Some("https://gist.githubusercontent.com/x/12345/raw/a.txt"),
+                   gist_raw_url_from_info(&gist, "other.py"));
+    }
 
     #[test]
     fn gist_language_single() {
@@ -379,45 +857,232 @@ mod test {
 
     #[test]
     fn gist_language_multiple() {
-        let languages = ["TrumpScript", "Haskal", "Rust++"];
+        // Files are deliberately listed out of order, with a repeated language.
         let gist_json = format!(r#"{{
             "id": "{}",
             "description": "Test gist",
             "owner": {{"login": "{owner}"}},
             "files": {{
+                "third.rs": {{
+                    "language": "Rust++"
+                }},
                 "{name}": {{
-                    "language": "{lang0}"
+                    "language": "TrumpScript"
                 }},
-                "second": {{
-                    "language": "{lang1}"
+                "second.hs": {{
+                    "language": "Haskal"
                 }},
-                "third": {{
-                    "language": "{lang2}"
+                "fourth.trump": {{
+                    "language": "TrumpScript"
                 }}
             }}
-        }}"#,
-        id=GIST_ID, owner=OWNER, name=GIST_NAME,
-        lang0=languages[0], lang1=languages[1], lang2=languages[2]);
+        }}"#, GIST_ID, owner=OWNER, name=GIST_NAME);
 
         let gist_info = Json::from_str(&gist_json).unwrap();
         let gist_lang = gist_language_from_info(&gist_info).map(Cow::into_owned);
 
-        // Note that the exact ordering of languages depends on how the JSON
-        // gets parsed into a HashMap, and the resulting order of "files".
-        // This is wonkiness inside the whole GitHub gist support code
-        // and we cannot really do anything about it :(
-        assert!(gist_lang.is_some());
-        let expected = {
-            let mut langs: Vec<_> = languages.iter().map(ToOwned::to_owned).collect();
-            langs.sort();
-            langs
-        };
-        let actual = {
-            let mut langs: Vec<_> = gist_lang.unwrap()
-                .split(", ").map(ToOwned::to_owned).collect();
-            langs.sort();
-            langs
-        };
-        assert_eq!(expected, actual);
+        // Languages follow the sorted filenames: fourth.trump, second.hs, test-gist, third.rs.
+        assert_eq!(Some("TrumpScript, Haskal, Rust++".to_owned()), gist_lang);
+    }
+
+    #[test]
+    fn gist_info_includes_resolved_id() {
+        let gist_json = format!(r#"{{
+            "owner": {{"login": "{owner}"}},
+            "files": {{"{name}": {{"language": "Python"}}}}
+        }}"#, owner=OWNER, name=GIST_NAME);
+
+        let gist_info = Json::from_str(&gist_json).unwrap();
+        let info = build_gist_info(&gist_info, Some(GIST_ID), &[Datum::Id, Datum::Owner]);
+        assert_eq!(GIST_ID, &*info.get(Datum::Id));
+        assert!(format!("{}", info).contains(GIST_ID),
+            "Resolved gist ID missing from displayed info: {}", info);
+
+        // Without a known ID, there is nothing to fall back on.
+        let info = build_gist_info(&gist_info, None, &[Datum::Id]);
+        assert!(!info.has(Datum::Id));
+    }
+
+    #[test]
+    fn gist_info_raw_url_of_file() {
+        let gist_json = format!(r#"{{
+            "git_pull_url": "https://gist.github.com/{id}.git",
+            "owner": {{"login": "{owner}"}},
+            "files": {{"{name}": {{"raw_url": "https://gist.githubusercontent.com/raw/{name}"}}}}
+        }}"#, id=GIST_ID, owner=OWNER, name=GIST_NAME);
+
+        let gist_info = Json::from_str(&gist_json).unwrap();
+        let info = build_gist_info(&gist_info, Some(GIST_ID), &[Datum::RawUrl]);
+        assert_eq!(format!("https://gist.githubusercontent.com/raw/{}", GIST_NAME),
+                   *info.get(Datum::RawUrl));
+    }
+
+    #[test]
+    fn gist_inline_content() {
+        let gist = Json::from_str(r#"{
+            "files": {"a.sh": {"size": 10, "truncated": false, "content": "echo hello"}}
+        }"#).unwrap();
+        assert_eq!(Some("echo hello"), gist_inline_content_from_info(&gist));
+
+        let gist = Json::from_str(r#"{
+            "files": {"a.sh": {"size": 2000000, "truncated": true, "content": "echo hel"}}
+        }"#).unwrap();
+        assert_eq!(None, gist_inline_content_from_info(&gist));
+
+        let gist = Json::from_str(r#"{
+            "files": {
+                "a.sh": {"size": 10, "truncated": false, "content": "echo hello"},
+                "b.sh": {"size": 10, "truncated": false, "content": "echo world"}
+            }
+        }"#).unwrap();
+        assert_eq!(None, gist_inline_content_from_info(&gist));
+    }
+
+    #[test]
+    fn gist_name_with_space() {
+        let gist_json = format!(r#"{{
+            "id": "{}",
+            "owner": {{"login": "{owner}"}},
+            "files": {{"my script.py": {{"language": "Python"}}}}
+        }}"#, GIST_ID, owner=OWNER);
+
+        let gist_info = Json::from_str(&gist_json).unwrap();
+        let gist_name = gist_name_from_info(&gist_info).map(Cow::into_owned);
+        assert_eq!(Some("my_script.py".into()), gist_name);
+    }
+
+    #[test]
+    fn filename_sanitization() {
+        assert_eq!("script.py", sanitize_filename("script.py"));
+        assert_eq!("my_script.py", sanitize_filename("my script.py"));
+        assert_eq!("my_script.py", sanitize_filename("my%20script.py"));
+        assert_eq!("a_b_c", sanitize_filename("a/b\\c"));
+        assert_eq!("what_.sh", sanitize_filename("what?.sh"));
+        assert_eq!("zażółć.rs", sanitize_filename("zażółć.rs"));
+    }
+
+    #[test]
+    fn new_gist_payload_single_file() {
+        let payload = new_gist_payload("hello.sh", "echo hello", Some("Greeting"), true);
+        assert_eq!(Some("Greeting"), payload.find("description").and_then(Json::as_str));
+        assert_eq!(Some(true), payload.find("public").and_then(Json::as_bool));
+        assert_eq!(Some("echo hello"), payload.find_path(&["files", "hello.sh", "content"])
+            .and_then(Json::as_str));
+    }
+
+    #[test]
+    fn new_gist_payload_secret_without_description() {
+        let payload = new_gist_payload("hello.sh", "echo hello", None, false);
+        assert_eq!(Some(""), payload.find("description").and_then(Json::as_str));
+        assert_eq!(Some(false), payload.find("public").and_then(Json::as_bool));
+    }
+
+    #[test]
+    fn gist_updated_at() {
+        let gist_info = Json::from_str(r#"{"updated_at": "2017-05-07T16:20:00Z"}"#).unwrap();
+        assert_eq!(Some(UNIX_EPOCH + Duration::from_secs(1494174000)),
+            gist_updated_at_from_info(&gist_info));
+    }
+
+    #[test]
+    fn gist_updated_at_invalid() {
+        let gist_info = Json::from_str(r#"{"updated_at": "yesterday"}"#).unwrap();
+        assert_eq!(None, gist_updated_at_from_info(&gist_info));
+        let gist_info = Json::from_str("{}").unwrap();
+        assert_eq!(None, gist_updated_at_from_info(&gist_info));
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        assert_eq!(Some(Duration::from_secs(0)), parse_retry_after("0"));
+        assert_eq!(Some(Duration::from_secs(30)), parse_retry_after(" 30 "));
+    }
+
+    #[test]
+    fn retry_after_capped() {
+        let max_wait = Duration::from_secs(MAX_RETRY_WAIT_SECS);
+        assert_eq!(Some(max_wait), parse_retry_after("3600"));
+    }
+
+    #[test]
+    fn retry_after_invalid() {
+        assert_eq!(None, parse_retry_after(""));
+        assert_eq!(None, parse_retry_after("-5"));
+        assert_eq!(None, parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"));
+    }
+
+    // Fake HTTP client
+
+    /// HTTP client that serves canned responses for known URLs,
+    /// and records the URLs that have been requested.
+    #[derive(Debug)]
+    struct FakeHttp {
+        responses: HashMap<String, String>,
+        requested: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl FakeHttp {
+        fn new() -> Self {
+            FakeHttp{responses: HashMap::new(), requested: Rc::new(RefCell::new(vec![]))}
+        }
+
+        /// Serve a 200 OK response with given headers & body for requests to given URL.
+        fn respond(mut self, url: &str, headers: &[(&str, &str)], body: &str) -> Self {
+            let mut raw = String::from("HTTP/1.1 200 OK\r\n");
+            for &(name, value) in headers {
+                raw.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            raw.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+            self.responses.insert(url.to_owned(), raw);
+            self
+        }
+    }
+
+    impl HttpGet for FakeHttp {
+        fn get(&self, url: &str, _: Headers) -> hyper::Result<Response> {
+            self.requested.borrow_mut().push(url.to_owned());
+            let raw = self.responses.get(url)
+                .unwrap_or_else(|| panic!("Unexpected request to {}", url));
+            let stream = FakeStream(Cursor::new(raw.clone().into_bytes()));
+            Response::new(Url::parse(url).unwrap(), Box::new(stream))
+        }
+    }
+
+    /// Network stream that reads a raw HTTP response from memory.
+    struct FakeStream(Cursor<Vec<u8>>);
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> { Ok(buf.len()) }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    impl NetworkStream for FakeStream {
+        fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 80)))
+        }
+        fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> { Ok(()) }
+        fn set_write_timeout(&self, _: Option<Duration>) -> io::Result<()> { Ok(()) }
+    }
+
+    /// JSON representation of a single gist of the test OWNER.
+    fn gist_json(id: &str, name: &str) -> String {
+        format!(r#"{{
+            "id": "{}",
+            "description": "Test gist",
+            "owner": {{"login": "{}"}},
+            "files": {{"{}": {{"language": null}}}}
+        }}"#, id, OWNER, name)
+    }
+
+    /// JSON page of gist results, given as (id, name) pairs.
+    fn gists_page(gists: &[(&str, &str)]) -> String {
+        let items: Vec<_> = gists.iter().map(|&(id, name)| gist_json(id, name)).collect();
+        format!("[{}]", items.join(","))
     }
 }
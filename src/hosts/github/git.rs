@@ -1,19 +1,69 @@
 //! Module containing Git operations.
 //! They are used for cloning & updating GitHub gists.
 
+use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-use git2::{self, Repository, RepositoryState};
+use git2::{self, BranchType, ObjectType, Repository, RepositoryState};
 use git2::build::CheckoutBuilder;
 
 
 /// Clone a Git repository from an URL to given path.
+///
+/// Note that this is always a full clone.
+// TODO: make it a shallow (depth=1) clone once we upgrade to a version of git2/libgit2
+// that supports fetch depth (the current one doesn't), and make sure pull() can then
+// update (or unshallow) such clones
 pub fn clone<P: AsRef<Path>>(url: &str, path: P) -> io::Result<()> {
     try!(Repository::clone(url, path.as_ref()).map_err(to_io_error));
     Ok(())
 }
 
+/// Complete a clone of a Git repository that has been cut short (e.g. by a network error),
+/// by fetching from its `remote` and checking out the remote's branch.
+pub fn resume_clone<P: AsRef<Path>>(repo_path: P, remote: &str) -> Result<(), git2::Error> {
+    let repo_path = repo_path.as_ref();
+    trace!("Resuming the clone from remote `{}` inside {}", remote, repo_path.display());
+
+    let repo = try!(Repository::open(repo_path));
+    {
+        let mut origin = try!(repo.find_remote(remote));
+        try!(origin.fetch(/* refspecs */ &[], /* options */ None, /* reflog_msg */ None));
+    }
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+
+    // If the clone got as far as creating the local branch, it only needs a checkout.
+    if repo.head().ok().and_then(|h| h.target()).is_some() {
+        return repo.checkout_head(Some(&mut checkout));
+    }
+
+    // Otherwise create the local branch from the remote one, like `git clone` would.
+    // (Gist repos only ever have a single branch).
+    let prefix = format!("{}/", remote);
+    let (name, commit) = {
+        let mut found = None;
+        for branch in try!(repo.branches(Some(BranchType::Remote))) {
+            let (branch, _) = try!(branch);
+            let name = match try!(branch.name()) {
+                Some(n) if n.starts_with(&prefix) && n != format!("{}HEAD", prefix) => n.to_owned(),
+                _ => continue,
+            };
+            let commit = try!(branch.get().peel(ObjectType::Commit));
+            found = Some((name[prefix.len()..].to_owned(), commit));
+            break;
+        }
+        try!(found.ok_or_else(|| git2::Error::from_str("no remote branch to check out")))
+    };
+    let commit = try!(commit.into_commit()
+        .map_err(|_| git2::Error::from_str("remote branch doesn't point to a commit")));
+    try!(repo.branch(&name, &commit, /* force */ true));
+    try!(repo.set_head(&format!("refs/heads/{}", name)));
+    repo.checkout_head(Some(&mut checkout))
+}
+
 
 /// Perform a standard Git "pull" operation.
 pub fn pull<P: AsRef<Path>>(repo_path: P,
@@ -34,6 +84,55 @@ pub fn pull<P: AsRef<Path>>(repo_path: P,
     Ok(())
 }
 
+/// Check out given ref (a branch, tag, or commit) of a Git repository, detaching its HEAD.
+///
+/// The ref is also looked up among the branches of given `remote`,
+/// as most branches only exist as remote-tracking ones after a clone.
+pub fn checkout_ref<P: AsRef<Path>>(repo_path: P,
+                                    remote: &str,
+                                    refname: &str) -> Result<(), git2::Error> {
+    let repo_path = repo_path.as_ref();
+    trace!("Checking out `{}` inside {}", refname, repo_path.display());
+
+    let repo = try!(Repository::open(repo_path));
+    let object = try!(repo.revparse_single(refname)
+        .or_else(|_| repo.revparse_single(&format!("{}/{}", remote, refname))));
+    let commit = try!(object.peel(ObjectType::Commit));
+
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+    try!(repo.checkout_tree(&commit, Some(&mut checkout)));
+    repo.set_head_detached(commit.id())
+}
+
+/// Check out the default (local) branch of a Git repository if its HEAD is detached,
+/// e.g. because of an earlier checkout_ref().
+/// Returns whether the branch had to be checked out.
+pub fn checkout_default_branch<P: AsRef<Path>>(repo_path: P) -> Result<bool, git2::Error> {
+    let repo_path = repo_path.as_ref();
+    let repo = try!(Repository::open(repo_path));
+    if try!(repo.head()).is_branch() {
+        return Ok(false);
+    }
+
+    // Gist repos only ever have a single local branch, created when cloning them.
+    let branch_ref = {
+        let mut branches = try!(repo.branches(Some(BranchType::Local)));
+        let (branch, _) = try!(try!(branches.next()
+            .ok_or_else(|| git2::Error::from_str("no local branch to check out"))));
+        let name = try!(branch.get().name()
+            .ok_or_else(|| git2::Error::from_str("invalid name of local branch")));
+        name.to_owned()
+    };
+    trace!("Checking out {} inside {}", branch_ref, repo_path.display());
+
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+    try!(repo.set_head(&branch_ref));
+    try!(repo.checkout_head(Some(&mut checkout)));
+    Ok(true)
+}
+
 /// Reset an ongoing Git merge operation.
 ///
 /// This isn't exactly the same as `git reset --merge`, because local changes to working tree
@@ -63,9 +162,190 @@ pub fn reset_merge<P: AsRef<Path>>(repo_path: P) -> io::Result<()> {
 }
 
 
+/// Lock files (relative to .git directory) that may be left behind
+/// by an interrupted Git operation, and prevent any subsequent ones.
+const LOCK_FILES: &'static [&'static str] = &[
+    "index.lock", "HEAD.lock", "config.lock", "FETCH_HEAD.lock", "ORIG_HEAD.lock",
+];
+
+/// Remove lock files left behind by an interrupted Git operation in given repository.
+///
+/// Only locks older than `max_age` are considered stale and removed,
+/// so that a Git operation that's still in progress isn't disrupted.
+/// Returns the paths of lock files that have been removed.
+pub fn remove_stale_locks<P: AsRef<Path>>(repo_path: P,
+                                          max_age: Duration) -> io::Result<Vec<PathBuf>> {
+    let git_dir = repo_path.as_ref().join(".git");
+    let now = SystemTime::now();
+
+    let mut removed = vec![];
+    for lock in LOCK_FILES.iter().map(|f| git_dir.join(f)) {
+        let modified = match fs::metadata(&lock).and_then(|m| m.modified()) {
+            Ok(time) => time,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        // Locks with modification time in the future are surely stale, too.
+        let is_stale = now.duration_since(modified).map(|age| age >= max_age).unwrap_or(true);
+        if is_stale {
+            try!(fs::remove_file(&lock));
+            removed.push(lock);
+        } else {
+            trace!("Git lock file {} is too recent to be removed", lock.display());
+        }
+    }
+    Ok(removed)
+}
+
+/// Check whether the Git repository at given path is in a good enough shape
+/// to be updated, i.e. whether it can be opened and its HEAD points to a commit.
+pub fn is_healthy<P: AsRef<Path>>(repo_path: P) -> bool {
+    let repo_path = repo_path.as_ref();
+    let result = Repository::open(repo_path).and_then(|repo| {
+        let head = try!(repo.head());
+        let oid = try!(head.target()
+            .ok_or_else(|| git2::Error::from_str("HEAD doesn't point to a commit")));
+        repo.find_commit(oid).map(|_| ())
+    });
+    if let Err(ref e) = result {
+        debug!("Git repository in {} is broken: {}", repo_path.display(), e);
+    }
+    result.is_ok()
+}
+
+/// Check whether the local HEAD of the Git repository at given path
+/// points to the same commit as the HEAD of given `remote`.
+///
+/// This connects to the remote (like `git ls-remote` does) but doesn't fetch anything.
+pub fn is_head_same_as_remote<P: AsRef<Path>>(repo_path: P,
+                                              remote: &str) -> Result<bool, git2::Error> {
+    let repo_path = repo_path.as_ref();
+    let repo = try!(Repository::open(repo_path));
+    let local_head = try!(try!(repo.head()).target()
+        .ok_or_else(|| git2::Error::from_str("HEAD doesn't point to a commit")));
+
+    let mut remote = try!(repo.find_remote(remote));
+    try!(remote.connect(git2::Direction::Fetch));
+    let remote_head = try!(try!(remote.list()).iter()
+        .find(|h| h.name() == "HEAD").map(|h| h.oid())
+        .ok_or_else(|| git2::Error::from_str("remote has no HEAD")));
+    remote.disconnect();
+
+    trace!("Local HEAD of {} is {}, remote HEAD is {}",
+        repo_path.display(), local_head, remote_head);
+    Ok(local_head == remote_head)
+}
+
+
 // Utility functions
 
 /// Convert a git2 library error to a generic Rust I/O error.
 pub fn to_io_error(git_err: git2::Error) -> io::Error {
     io::Error::new(io::ErrorKind::Other, git_err)
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use git2::{Repository, Signature};
+    use super::{checkout_default_branch, checkout_ref, is_head_same_as_remote, is_healthy,
+                remove_stale_locks, resume_clone};
+
+    fn temp_repo(name: &str) -> (PathBuf, Repository) {
+        let path = env::temp_dir().join(format!("gisht-git-test-{}", name));
+        let _ = fs::remove_dir_all(&path);
+        let repo = Repository::init(&path).unwrap();
+        (path, repo)
+    }
+
+    fn commit(repo: &Repository) {
+        let sig = Signature::now("gisht", "gisht@example.com").unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.target())
+            .map(|oid| repo.find_commit(oid).unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, "Commit", &tree, &parents).unwrap();
+    }
+
+    #[test]
+    fn stale_locks_removed() {
+        let (path, _) = temp_repo("stale_locks_removed");
+        let lock = path.join(".git").join("index.lock");
+        fs::File::create(&lock).unwrap();
+
+        // A fresh lock may belong to an operation in progress.
+        assert!(remove_stale_locks(&path, Duration::from_secs(3600)).unwrap().is_empty());
+        assert!(lock.exists());
+
+        assert_eq!(vec![lock.clone()], remove_stale_locks(&path, Duration::from_secs(0)).unwrap());
+        assert!(!lock.exists());
+    }
+
+    #[test]
+    fn repo_health() {
+        let (path, repo) = temp_repo("repo_health");
+        assert!(!is_healthy(&path), "Repo without commits unexpectedly healthy");
+        commit(&repo);
+        assert!(is_healthy(&path), "Repo with a commit unexpectedly broken");
+
+        fs::remove_dir_all(path.join(".git")).unwrap();
+        assert!(!is_healthy(&path), "Non-repo directory unexpectedly healthy");
+    }
+
+    #[test]
+    fn partial_clone_resumed() {
+        let (origin_path, origin) = temp_repo("partial_clone_resumed_origin");
+        commit(&origin);
+
+        // This is what's left behind by a clone that failed while fetching.
+        let (path, repo) = temp_repo("partial_clone_resumed");
+        repo.remote("origin", origin_path.to_str().unwrap()).unwrap();
+        assert!(!is_healthy(&path));
+
+        resume_clone(&path, "origin").unwrap();
+        assert!(is_healthy(&path), "Partial clone not completed");
+        assert_eq!(origin.head().unwrap().target(), repo.head().unwrap().target());
+    }
+
+    #[test]
+    fn head_compared_with_remote() {
+        let (origin_path, origin) = temp_repo("head_compared_with_remote_origin");
+        commit(&origin);
+        let (path, repo) = temp_repo("head_compared_with_remote");
+        repo.remote("origin", origin_path.to_str().unwrap()).unwrap();
+        resume_clone(&path, "origin").unwrap();
+        assert!(is_head_same_as_remote(&path, "origin").unwrap(),
+            "Fresh clone differs from remote");
+
+        commit(&origin);
+        assert!(!is_head_same_as_remote(&path, "origin").unwrap(), "Remote change not detected");
+        assert!(is_head_same_as_remote(&path, "nonexistent").is_err());
+    }
+
+    #[test]
+    fn checkout_ref_and_back() {
+        let (path, repo) = temp_repo("checkout_ref_and_back");
+        commit(&repo);
+        let first = repo.head().unwrap().target().unwrap();
+        repo.tag_lightweight("first", &repo.find_object(first, None).unwrap(), false).unwrap();
+        commit(&repo);
+        let second = repo.head().unwrap().target().unwrap();
+
+        assert!(!checkout_default_branch(&path).unwrap(), "Default branch checked out again");
+
+        checkout_ref(&path, "origin", "first").unwrap();
+        assert!(!repo.head().unwrap().is_branch(), "HEAD not detached after checking out a ref");
+        assert_eq!(Some(first), repo.head().unwrap().target());
+
+        assert!(checkout_default_branch(&path).unwrap(), "Default branch not checked out");
+        assert!(repo.head().unwrap().is_branch());
+        assert_eq!(Some(second), repo.head().unwrap().target());
+
+        assert!(checkout_ref(&path, "origin", "nonexistent").is_err());
+    }
+}
@@ -9,16 +9,20 @@ mod storage;
 
 
 use std::borrow::Cow;
-use std::io;
+use std::io::{self, Cursor, Read};
 use std::marker::PhantomData;
 use std::path::Path;
+use std::time::SystemTime;
 
 use regex::{self, Regex};
+use serde_json::Value as Json;
 use url::Url;
 
 use gist::{self, Datum, Gist};
+use util::{binary_source, edit_distance};
 use super::{FetchMode, Host};
-use self::storage::{needs_update, update_gist, clone_gist};
+use self::storage::{checkout_gist_ref, clone_gist, is_outdated, is_stale, needs_update,
+                    update_gist};
 
 
 /// GitHub host ID.
@@ -41,32 +45,24 @@ impl GitHub {
 impl Host for GitHub {
     fn id(&self) -> &'static str { ID }
     fn name(&self) -> &str { "GitHub" }
+    fn category(&self) -> &'static str { "Git-based" }
 
     /// Fetch the gist's repo from GitHub & create the appropriate binary symlink.
     ///
     /// If the gist hasn't been downloaded already, a clone of the gist's Git repo is performed.
     /// Otherwise, updating the gist (if needed) is just a simple Git pull.
     fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()> {
-        try!(ensure_github_gist(gist));
-        let gist = try!(resolve_gist(gist));
+        fetch_github_gist(gist, mode, None)
+    }
 
-        if gist.is_local() {
-            trace!("Gist {} is locally present but may need updating", gist.uri);
-            let update = match mode {
-                FetchMode::Auto => needs_update(&gist),
-                FetchMode::Always => true,
-                FetchMode::New => false,
-            };
-            if update {
-                try!(update_gist(gist));
-            } else {
-                trace!("No need to update gist {}", gist.uri);
-            }
-        } else {
-            try!(clone_gist(gist));
-        }
+    /// Fetch the gist's repo from GitHub and check out given ref (branch, tag, or commit).
+    fn fetch_gist_at(&self, gist: &Gist, mode: FetchMode, git_ref: &str) -> io::Result<()> {
+        fetch_github_gist(gist, mode, Some(git_ref))
+    }
 
-        Ok(())
+    /// Check whether the gist's local repo hasn't been pulled for too long.
+    fn is_outdated(&self, gist: &Gist) -> bool {
+        is_outdated(gist)
     }
 
     /// Return the URL to gist's HTML website.
@@ -82,44 +78,120 @@ impl Host for GitHub {
             Cow::Borrowed(gist)
         };
 
-        // See if there is an existing URL included in the gist::Info.
-        // Otherwise, build it manually.
-        let url = gist.info(Datum::BrowserUrl).unwrap_or_else(|| {
-            trace!("URL not found in gist info, building it manually");
-            let mut url = Url::parse(HTML_URL).unwrap();
-            url.set_path(&format!("{}/{}", gist.uri.owner, gist.id.as_ref().unwrap()));
-            url.into_string()
-        });
+        let url = browser_url(&gist);
         trace!("Browser URL for {:?}: {}", gist, url);
         Ok(url)
     }
 
+    /// Return the URL to raw content of the gist's main file.
+    fn raw_url(&self, gist: &Gist) -> io::Result<Option<String>> {
+        try!(ensure_github_gist(gist));
+        if let Some(url) = gist.info(Datum::RawUrl) {
+            return Ok(Some(url));
+        }
+        let gist = try!(resolve_gist(gist));
+
+        let info = try!(api::get_gist_info(gist.id.as_ref().unwrap()));
+        Ok(api::gist_raw_url_from_info(&info, &gist.uri.name).map(String::from))
+    }
+
+    /// Return the content of a single-file gist from the gist info, if it's small enough
+    /// for GitHub to include it there in full.
+    fn read_inline_gist(&self, gist: &Gist) -> io::Result<Option<Box<Read>>> {
+        try!(ensure_github_gist(gist));
+        let gist = try!(resolve_gist(gist));
+
+        let info = try!(api::get_gist_info(gist.id.as_ref().unwrap()));
+        Ok(api::gist_inline_content_from_info(&info).map(|content| {
+            trace!("GitHub gist {} has its content included in gist info", gist.uri);
+            Box::new(Cursor::new(content.to_owned().into_bytes())) as Box<Read>
+        }))
+    }
+
+    fn has_inline_gists(&self) -> bool { true }
+
     /// Return a structure with gist metadata.
-    fn gist_info(&self, gist: &Gist) -> io::Result<Option<gist::Info>> {
+    fn gist_info(&self, gist: &Gist, data: &[Datum]) -> io::Result<Option<gist::Info>> {
         try!(ensure_github_gist(gist));
         let gist = try!(resolve_gist(gist));
 
         let id = gist.id.as_ref().unwrap();
         let info = try!(api::get_gist_info(id));
 
-        let result = api::build_gist_info(&info, &[]);
-        Ok(Some(result))
+        let mut result = api::build_gist_info(&info, Some(id), data).to_builder();
+        if data.is_empty() || data.contains(&Datum::LocalCopy) {
+            result.set_opt(Datum::LocalCopy, local_copy_status(&gist, &info));
+        }
+        Ok(Some(result.build()))
+    }
+
+    /// Delete the gist from GitHub.
+    /// This requires a personal access token of the gist's owner.
+    fn delete_remote_gist(&self, gist: &Gist) -> io::Result<()> {
+        try!(ensure_github_gist(gist));
+        let gist = try!(resolve_gist(gist));
+
+        let id = gist.id.as_ref().unwrap();
+        try!(api::delete_gist(id));
+        info!("GitHub gist {} (ID={}) deleted", gist.uri, id);
+        Ok(())
+    }
+
+    fn check_remote_deletion(&self, gist: &Gist) -> io::Result<()> {
+        try!(ensure_github_gist(gist));
+        api::check_delete_access()
+    }
+
+    /// Create a new GitHub gist, owned by the user
+    /// whose access token has been provided in the environment.
+    fn create_gist(&self, filename: &str, content: &str,
+                   description: Option<&str>, public: bool) -> io::Result<Gist> {
+        let info = try!(api::create_gist(filename, content, description, public));
+        let id = try!(info.find("id").and_then(Json::as_str).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData, "no ID in the created GitHub gist's data")));
+        let name = try!(api::gist_name_from_info(&info).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData, "no files in the created GitHub gist's data")));
+        let owner = api::gist_owner_from_info(&info);
+
+        let uri = gist::Uri::new(ID, owner, name).unwrap();
+        info!("GitHub gist {} (ID={}) created", uri, id);
+        Ok(Gist::from_uri(uri).with_id(id))
+    }
+
+    /// List the gists of given GitHub user.
+    fn owner_gists<'o>(&self, owner: &'o str, max_pages: Option<usize>,
+                       since: Option<SystemTime>) -> io::Result<Box<Iterator<Item=Gist> + 'o>> {
+        let gists = match since {
+            Some(time) => api::iter_gists_since(owner, time),
+            None => api::iter_gists(owner),
+        };
+        Ok(match max_pages {
+            Some(count) => Box::new(gists.max_pages(count)),
+            None => Box::new(gists),
+        })
+    }
+
+    /// List all the gists (both public and secret) of the user
+    /// whose access token has been provided in the environment.
+    fn own_gists(&self) -> io::Result<Vec<Gist>> {
+        let login = try!(api::authenticated_user());
+        debug!("Listing all GitHub gists of the authenticated user {}", login);
+        let gists: Vec<_> = api::iter_own_gists(&login).collect();
+        Ok(gists)
+    }
+
+    /// Check if given URL points to a gist's browser HTML page.
+    /// This doesn't talk to the GitHub API.
+    fn recognizes_url(&self, url: &str) -> bool {
+        HTML_URL_RE.is_match(&*canonicalize_url(url))
     }
 
     /// Return a Gist based on URL to its browser HTML page.
     fn resolve_url(&self, url: &str) -> Option<io::Result<Gist>> {
         trace!("Checking if `{}` is a GitHub gist URL", url);
 
-        // Clean up the URL a little, e.g. by converting HTTP to HTTPS.
         let orig_url = url.to_owned();
-        let url: Cow<str> = {
-            let url = url.trim();
-            if url.starts_with("http://") {
-                format!("https://{}", url.trim_left_matches("http://")).into()
-            } else {
-                url.into()
-            }
-        };
+        let url = canonicalize_url(url);
 
         // Check if it matches the pattern of gist page URLs.
         let captures = match HTML_URL_RE.captures(&*url) {
@@ -133,28 +205,113 @@ impl Host for GitHub {
         let id = &captures["id"];
         trace!("URL {} points to a GitHub gist: ID={}", orig_url, id);
 
-        // Obtain gist information using GitHub API.
         // Note that gist owner may be in the URL already, or we may need to get it
         // from gist info along with gist name.
-        let info = try_some!(api::get_gist_info(id));
-        let name = match api::gist_name_from_info(&info) {
-            Some(name) => name,
-            None => {
-                warn!("GitHub gist with ID={} (URL={}) has no files", id, orig_url);
-                return None;
-            },
+        let gist = try_opt!(gist_from_id(id, captures.name("owner").map(|o| o.as_str())));
+        if let Ok(ref gist) = gist {
+            debug!("URL resolves to GitHub gist {} (ID={})",
+                gist.uri, gist.id.as_ref().unwrap());
+        }
+        Some(gist)
+    }
+
+    /// URLs often don't include gist names, which have to be obtained from GitHub API.
+    fn resolves_urls_remotely(&self) -> bool {
+        true
+    }
+
+    /// Resolve a gist given just by its GitHub ID, like `gh:d0f351a97c65679bb911bafe`.
+    fn resolve_shorthand(&self, gist: &Gist) -> Option<io::Result<Gist>> {
+        if gist.uri.host_id != ID || gist.uri.has_owner() || !ID_RE.is_match(&gist.uri.name) {
+            return None;
+        }
+        let id = &gist.uri.name;
+        trace!("Gist {} looks like a bare GitHub gist ID", gist.uri);
+
+        let gist = try_opt!(gist_from_id(id, None));
+        if let Ok(ref gist) = gist {
+            debug!("ID={} resolves to GitHub gist {}", id, gist.uri);
+        }
+        Some(gist)
+    }
+
+    /// Find the owner's gists with names that are close to the name of given gist.
+    fn similar_gists(&self, gist: &Gist) -> io::Result<Vec<Gist>> {
+        try!(ensure_github_gist(gist));
+        if !gist.uri.has_owner() {
+            return Ok(vec![]);
+        }
+        let name = gist.uri.name.to_lowercase();
+        let mut similar: Vec<(usize, Gist)> = api::iter_gists(&gist.uri.owner)
+            .take(MAX_RESOLVE_SCAN)
+            .filter_map(|g| {
+                let distance = edit_distance(&name, &g.uri.name.to_lowercase());
+                if distance <= MAX_SIMILAR_NAME_DISTANCE { Some((distance, g)) } else { None }
+            })
+            .collect();
+        similar.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.uri.name.cmp(&b.1.uri.name)));
+        debug!("Found {} gist(s) of {} with names similar to {}",
+            similar.len(), gist.uri.owner, gist.uri.name);
+        Ok(similar.into_iter().map(|(_, g)| g).collect())
+    }
+}
+
+/// Fetch the gist's repo from GitHub, optionally checking out given Git ref.
+/// See Host::fetch_gist and Host::fetch_gist_at for details.
+fn fetch_github_gist(gist: &Gist, mode: FetchMode, git_ref: Option<&str>) -> io::Result<()> {
+    try!(ensure_github_gist(gist));
+    let gist = try!(resolve_gist(gist));
+
+    if gist.is_local() {
+        trace!("Gist {} is locally present but may need updating", gist.uri);
+        let update = match mode {
+            FetchMode::Auto => needs_update(&gist),
+            FetchMode::Always => true,
+            FetchMode::New => false,
         };
-        let owner = captures.name("owner").map(|o| o.as_str())
-            .unwrap_or_else(|| api::gist_owner_from_info(&info));
+        if update {
+            try!(update_gist(&gist));
+        } else {
+            trace!("No need to update gist {}", gist.uri);
+        }
+    } else {
+        try!(clone_gist(&gist));
+    }
 
-        // Return the resolved gist.
-        let uri = gist::Uri::new(ID, owner, name).unwrap();
-        let gist = Gist::from_uri(uri).with_id(id);
-        debug!("URL resolves to GitHub gist {} (ID={})", gist.uri, gist.id.as_ref().unwrap());
-        Some(Ok(gist))
+    // Without a ref, this makes sure the gist isn't stuck at one given previously.
+    checkout_gist_ref(&gist, git_ref)
+}
+
+
+/// Clean up the gist URL a little, e.g. by converting HTTP to HTTPS.
+fn canonicalize_url(url: &str) -> Cow<str> {
+    let url = url.trim();
+    if url.starts_with("http://") {
+        format!("https://{}", url.trim_left_matches("http://")).into()
+    } else {
+        url.into()
     }
 }
 
+/// Obtain the gist with given GitHub ID using GitHub API.
+/// If the owner isn't provided, it will be retrieved from gist info along with its name.
+///
+/// Returns None if the gist doesn't have any files (and thus cannot be named).
+fn gist_from_id(id: &str, owner: Option<&str>) -> Option<io::Result<Gist>> {
+    let info = try_some!(api::get_gist_info(id));
+    let name = match api::gist_name_from_info(&info) {
+        Some(name) => name,
+        None => {
+            warn!("GitHub gist with ID={} has no files", id);
+            return None;
+        },
+    };
+    let owner = owner.unwrap_or_else(|| api::gist_owner_from_info(&info));
+
+    let uri = gist::Uri::new(ID, owner, name).unwrap();
+    Some(Ok(Gist::from_uri(uri).with_id(id)))
+}
+
 
 /// Base URL to gist HTML pages.
 const HTML_URL: &'static str = "https://gist.github.com";
@@ -164,9 +321,20 @@ lazy_static! {
     static ref HTML_URL_RE: Regex = Regex::new(
         &format!("^{}/{}$", regex::escape(HTML_URL), r#"((?P<owner>[^/]+)/)?(?P<id>[0-9a-fA-F]+)"#)
     ).unwrap();
+
+    /// Regular expression for gist IDs that can be used as shorthand for gist URIs.
+    /// Older, numeric IDs are not included, as they are too similar to gist names.
+    static ref ID_RE: Regex = Regex::new("^[0-9a-f]{20,}$").unwrap();
 }
 
 
+/// Maximum number of owner's gists to look through when resolving a gist by its name.
+/// This prevents paging through the whole of a large account for a nonexistent gist.
+const MAX_RESOLVE_SCAN: usize = 1000;
+
+/// Maximum edit distance between the names of gists for them to be considered similar.
+const MAX_SIMILAR_NAME_DISTANCE: usize = 2;
+
 /// Return a "resolved" Gist that has a GitHub ID associated with it.
 fn resolve_gist(gist: &Gist) -> io::Result<Cow<Gist>> {
     trace!("Resolving GitHub gist: {}", gist.uri);
@@ -189,20 +357,68 @@ fn resolve_gist(gist: &Gist) -> io::Result<Cow<Gist>> {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData, format!("Invalid GitHub gist: {}", gist.uri)));
         }
-        match api::iter_gists(&gist.uri.owner).find(|g| gist.uri == g.uri) {
-            Some(gist) => {
-                debug!("Gist {} found on GitHub with ID={}", gist.uri, gist.id.as_ref().unwrap());
-                Ok(Cow::Owned(gist))
-            },
-            _ => Err(io::Error::new(
-                io::ErrorKind::InvalidData, format!("Gist {} not found", gist.uri))),
+        let mut scanned = 0;
+        for owned_gist in api::iter_gists(&gist.uri.owner).take(MAX_RESOLVE_SCAN) {
+            if gist.uri == owned_gist.uri {
+                debug!("Gist {} found on GitHub with ID={}",
+                    owned_gist.uri, owned_gist.id.as_ref().unwrap());
+                return Ok(Cow::Owned(owned_gist));
+            }
+            scanned += 1;
+        }
+        if scanned >= MAX_RESOLVE_SCAN {
+            warn!("Gave up looking for gist {} after scanning {} of {}'s gists",
+                gist.uri, scanned, gist.uri.owner);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "Gist {} not found within first {} gists of {}; \
+                 try specifying it by its URL instead", gist.uri, scanned, gist.uri.owner)));
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData, format!("Gist {} not found", gist.uri)))
+    }
+}
+
+/// Describe whether the local copy of the gist (if any) is up-to-date
+/// with respect to the gist on GitHub, given as its JSON info.
+fn local_copy_status(gist: &Gist, info: &Json) -> Option<&'static str> {
+    if !gist.is_local() {
+        return None;
+    }
+    let updated_at = try_opt!(api::gist_updated_at_from_info(info));
+    match is_stale(gist, updated_at) {
+        Ok(true) => Some("stale"),
+        Ok(false) => Some("up-to-date"),
+        Err(err) => {
+            warn!("Couldn't determine if local copy of gist {} is stale: {}", gist.uri, err);
+            None
+        },
+    }
+}
+
+/// Return the URL to the HTML page of a gist that has its GitHub ID.
+///
+/// The URL included in gist::Info is preferred, unless it has a different owner
+/// than the gist's URI (e.g. if it was obtained for the gist as anonymous),
+/// in which case it's built manually for the gist's actual owner.
+fn browser_url(gist: &Gist) -> String {
+    if let Some(url) = gist.info(Datum::BrowserUrl) {
+        let url_owner = HTML_URL_RE.captures(&url)
+            .and_then(|c| c.name("owner")).map(|o| o.as_str().to_owned());
+        if !gist.uri.has_owner() || url_owner.as_ref() == Some(&gist.uri.owner) {
+            return url;
         }
+        trace!("URL {} from gist info has a different owner than {}", url, gist.uri);
+    } else {
+        trace!("URL not found in gist info, building it manually");
     }
+    let mut url = Url::parse(HTML_URL).unwrap();
+    url.set_path(&format!("{}/{}", gist.uri.owner, gist.id.as_ref().unwrap()));
+    url.into_string()
 }
 
 /// Obtain the gist ID from its binary path.
 fn id_from_binary_path<P: AsRef<Path>>(path: P) -> io::Result<String> {
-    let path = try!(path.as_ref().canonicalize());
+    let path = try!(binary_source(path.as_ref()));
 
     // Binaries of GitHub gists are expected to be in the form of:
     // ~/gisht/gists/gh/$ID/$NAME. We want the $ID.
@@ -227,7 +443,33 @@ fn ensure_github_gist(gist: &Gist) -> io::Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use super::{HTML_URL, HTML_URL_RE};
+    use gist::{Datum, Gist, InfoBuilder, Uri};
+    use super::{HTML_URL, HTML_URL_RE, ID, ID_RE, browser_url};
+
+    #[test]
+    fn browser_url_with_owner() {
+        let uri = Uri::new(ID, "Octocat", "greet").unwrap();
+        let gist = Gist::new(uri.clone(), "abc123");
+        assert_eq!(HTML_URL.to_owned() + "/Octocat/abc123", browser_url(&gist));
+
+        // URL from the info is used if it agrees on the owner...
+        let info = InfoBuilder::new()
+            .with(Datum::BrowserUrl, &(HTML_URL.to_owned() + "/Octocat/abc123"))
+            .build();
+        let gist = Gist::new(uri.clone(), "abc123").with_info(info);
+        assert_eq!(HTML_URL.to_owned() + "/Octocat/abc123", browser_url(&gist));
+
+        // ...but not if the gist has been resolved anonymously.
+        let info = InfoBuilder::new()
+            .with(Datum::BrowserUrl, &(HTML_URL.to_owned() + "/abc123"))
+            .build();
+        let gist = Gist::new(uri, "abc123").with_info(info.clone());
+        assert_eq!(HTML_URL.to_owned() + "/Octocat/abc123", browser_url(&gist));
+
+        // Without a known owner, the anonymous URL is fine.
+        let gist = Gist::new(Uri::from_name(ID, "abc123").unwrap(), "abc123").with_info(info);
+        assert_eq!(HTML_URL.to_owned() + "/abc123", browser_url(&gist));
+    }
 
     #[test]
     fn html_url_regex() {
@@ -265,4 +507,13 @@ mod tests {
                 "URL was incorrectly deemed a valid gist HTML URL: {}", invalid_url);
         }
     }
+
+    #[test]
+    fn id_regex() {
+        assert!(ID_RE.is_match("d0f351a97c65679bb911bafe"));
+        assert!(ID_RE.is_match("1e2f57a365d782dd36538"));
+        assert!(!ID_RE.is_match("67424258"), "Numeric IDs are too short to be recognized");
+        assert!(!ID_RE.is_match("hello_world"));
+        assert!(!ID_RE.is_match("d0f351a97c65679bb911bafe.sh"));
+    }
 }
@@ -1,29 +1,52 @@
 //! Module handling the local storage of GitHub gists, including updating them.
 
+use std::borrow::Cow;
 use std::fs;
 use std::io;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
 use git2;
 
-use gist::{Datum, Gist};
-use util::{mark_executable, symlink_file};
+use config;
+use gist::Gist;
+use interrupt;
+use util::{link_binary, mark_executable, refresh_binary, unlink_binary};
 use super::{ID, api, git};
 
 
 lazy_static! {
     /// Minimum interval between updating (git-pulling) of gists.
     static ref UPDATE_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+    /// Minimum age of a Git lock file in gist's repo for it to be considered stale.
+    static ref STALE_LOCK_AGE: Duration = Duration::from_secs(60);
 }
 
 /// Check whether given gist needs to be updated.
 ///
+/// If `github.check_remote` is enabled in the configuration, the gist's local HEAD
+/// is compared with the remote one, regardless of when the gist was last updated.
+/// Otherwise (or if that check fails), the gist needs an update
+/// if it hasn't been updated for longer than the usual update interval.
+///
 /// If the time since last update cannot be determined for whatever reason,
 /// the function will assume the update is necessary.
 pub fn needs_update<G: AsRef<Gist>>(gist: G) -> bool {
     let gist = gist.as_ref();
     trace!("Checking if GitHub gist {} requires an update...", gist.uri);
 
+    if config::current().github_check_remote() {
+        match git::is_head_same_as_remote(gist.path(), "origin") {
+            Ok(same) => {
+                debug!("GitHub gist {} is {} its remote", gist.uri,
+                    if same { "up to date with" } else { "different from" });
+                return !same;
+            },
+            Err(err) => warn!("Couldn't compare gist {} with its remote ({}). \
+                               Falling back to checking the last update time.", gist.uri, err),
+        }
+    }
+
     let last = match last_update_time(&gist) {
         Ok(time) => time,
         Err(err) => {
@@ -45,6 +68,24 @@ pub fn needs_update<G: AsRef<Gist>>(gist: G) -> bool {
     }
 }
 
+/// Check whether the local copy of a gist is older than its last update
+/// on GitHub (as given by `updated_at`).
+pub fn is_stale(gist: &Gist, updated_at: SystemTime) -> io::Result<bool> {
+    let last = try!(last_update_time(gist));
+    Ok(last < updated_at)
+}
+
+/// Check whether the local copy of a gist hasn't been updated for longer
+/// than the usual update interval.
+///
+/// Unlike `needs_update`, this assumes the gist is fine if its last update time is unknown.
+pub fn is_outdated(gist: &Gist) -> bool {
+    last_update_time(gist).ok()
+        .and_then(|last| SystemTime::now().duration_since(last).ok())
+        .map(|duration| duration > *UPDATE_INTERVAL)
+        .unwrap_or(false)
+}
+
 /// Determine when was the last time a gist has been updated.
 fn last_update_time(gist: &Gist) -> io::Result<SystemTime> {
     // Git writes .git/FETCH_HEAD at every pull, so just check its mtime.
@@ -61,6 +102,12 @@ pub fn update_gist<G: AsRef<Gist>>(gist: G) -> io::Result<()> {
     assert!(gist.id.is_some(), "Gist {} has unknown GitHub ID!", gist.uri);
     assert!(path.exists(), "Directory for gist {} doesn't exist!", gist.uri);
 
+    // An interrupted update may have left the repository in a bad state.
+    if try!(repair_gist(gist)) {
+        debug!("GitHub gist {} is already up to date after cloning it again", gist.uri);
+        return Ok(());
+    }
+
     trace!("Updating GitHub gist {}...", gist.uri);
     let reflog_msg = Some("gisht-update");
     if let Err(err) = git::pull(&path, "origin", reflog_msg) {
@@ -90,59 +137,303 @@ pub fn update_gist<G: AsRef<Gist>>(gist: G) -> io::Result<()> {
         }
     }
 
+    // If the gist's binary is a copy, it has to be updated separately.
+    try!(refresh_binary(&gist.binary_path()));
+
     debug!("GitHub gist {} successfully updated", gist.uri);
     Ok(())
 }
 
+/// Check out given Git ref (branch, tag, or commit) of an already-downloaded gist.
+///
+/// If the ref isn't known locally, the gist is updated first.
+/// If `git_ref` is None, the gist's default branch is checked out (if it wasn't already).
+pub fn checkout_gist_ref(gist: &Gist, git_ref: Option<&str>) -> io::Result<()> {
+    let path = gist.path();
+    let git_ref = match git_ref {
+        Some(r) => r,
+        None => {
+            if try!(git::checkout_default_branch(&path).map_err(git::to_io_error)) {
+                debug!("GitHub gist {} switched back to its default branch", gist.uri);
+                try!(mark_gist_executable(gist, &path));
+                try!(refresh_binary(&gist.binary_path()));
+            }
+            return Ok(());
+        },
+    };
+
+    if let Err(err) = git::checkout_ref(&path, "origin", git_ref) {
+        if err.code() != git2::ErrorCode::NotFound {
+            return Err(git::to_io_error(err));
+        }
+        debug!("Ref `{}` of GitHub gist {} not found locally, updating the gist...",
+            git_ref, gist.uri);
+        try!(update_gist(Cow::Borrowed(gist)));
+        try!(git::checkout_ref(&path, "origin", git_ref).map_err(git::to_io_error));
+    }
+    debug!("Checked out `{}` of GitHub gist {}", git_ref, gist.uri);
+    try!(mark_gist_executable(gist, &path));
+
+    // If the gist's binary is a copy, it has to reflect the checked out revision, too.
+    refresh_binary(&gist.binary_path())
+}
 
-/// Clone the gist's repo into the proper directory (which must NOT exist).
+/// Make sure the Git repository of a downloaded gist is in a shape that allows to update it.
+///
+/// Stale lock files left behind by an interrupted Git operation are removed,
+/// and if the repository is broken beyond that, the gist is cloned again from scratch.
+/// Returns whether the gist has been cloned again.
+fn repair_gist(gist: &Gist) -> io::Result<bool> {
+    let path = gist.path();
+    for lock in try!(git::remove_stale_locks(&path, *STALE_LOCK_AGE)) {
+        warn!("Removed stale Git lock file {} from gist {}", lock.display(), gist.uri);
+    }
+    if git::is_healthy(&path) {
+        return Ok(false);
+    }
+
+    warn!("Local copy of gist {} is broken, downloading it again...", gist.uri);
+    try!(fs::remove_dir_all(&path));
+    // The binary symlink would now be dangling, and clone_gist() wouldn't recreate it.
+    let binary = gist.binary_path();
+    if fs::symlink_metadata(&binary).is_ok() {
+        try!(unlink_binary(&binary));
+    }
+    try!(clone_gist(Cow::Borrowed(gist)));
+    info!("Broken local copy of gist {} replaced with a fresh clone", gist.uri);
+    Ok(true)
+}
+
+
+/// Clone the gist's repo into the proper directory.
 /// Given Gist object must have the GitHub ID associated with it.
+///
+/// If the directory exists already, it is assumed to contain a partial clone
+/// from an earlier attempt, which is then completed (or replaced with a fresh clone).
 pub fn clone_gist<G: AsRef<Gist>>(gist: G) -> io::Result<()> {
     let gist = gist.as_ref();
     assert!(gist.uri.host_id == ID, "Gist {} is not a GitHub gist!", gist.uri);
     assert!(gist.id.is_some(), "Gist {} has unknown GitHub ID!", gist.uri);
-    assert!(!gist.path().exists(), "Directory for gist {} already exists!", gist.uri);
 
-    // Check if the Gist has a clone URL already in its metadata.
-    // Otherwise, talk to GitHub to obtain the URL that we can clone the gist from
-    // as a Git repository.
-    let clone_url = match gist.info(Datum::RawUrl).clone() {
-        Some(url) => url,
+    // If we're interrupted, the partial clone will be removed.
+    let _guard = interrupt::guard_fetch(gist);
+    let path = gist.path();
+    if !(path.exists() && try!(resume_clone(gist, &path))) {
+        let clone_url = try!(get_clone_url(gist));
+        debug!("Cloning GitHub gist from {}", clone_url);
+        try!(fs::create_dir_all(&path));
+        try!(git::clone(&clone_url, &path));
+    }
+
+    // Make sure the gist's executable is, in fact, executable.
+    let executable = try!(mark_gist_executable(gist, &path));
+
+    // Symlink (or copy) the main/binary file to the binary directory.
+    try!(link_binary(&executable, &gist.binary_path()));
+
+    Ok(())
+}
+
+/// Complete a partial clone of the gist's repo in given directory,
+/// which may have been left behind e.g. by a network error.
+///
+/// Returns whether that succeeded. If it didn't, the directory is removed
+/// so that the gist can be cloned again from scratch.
+fn resume_clone(gist: &Gist, path: &Path) -> io::Result<bool> {
+    warn!("Found partial local copy of gist {}, attempting to complete it...", gist.uri);
+    match git::resume_clone(path, "origin") {
+        Ok(()) => {
+            info!("Partial local copy of gist {} successfully completed", gist.uri);
+            Ok(true)
+        },
+        Err(err) => {
+            warn!("Couldn't complete partial local copy of gist {} ({}), \
+                   downloading it again...", gist.uri, err);
+            try!(fs::remove_dir_all(path));
+            Ok(false)
+        },
+    }
+}
+
+/// Retrieve the URL to clone the gist from as a Git repository.
+fn get_clone_url(gist: &Gist) -> io::Result<String> {
+    // Talk to GitHub to obtain the URL that we can clone the gist from
+    // as a Git repository. (Gist's Datum::RawUrl is the URL of its file instead).
+    trace!("Need to get clone URL from GitHub for gist {}", gist.uri);
+    let info = try!(api::get_gist_info(&gist.id.as_ref().unwrap()));
+    let clone_url = match info.find("git_pull_url").and_then(|u| u.as_str()) {
+        Some(url) => url.to_owned(),
         None => {
-            trace!("Need to get clone URL from GitHub for gist {}", gist.uri);
-            let info = try!(api::get_gist_info(&gist.id.as_ref().unwrap()));
-            let url = match info.find("git_pull_url").and_then(|u| u.as_str()) {
-                Some(url) => url.to_owned(),
-                None => {
-                    error!("Gist info for {} doesn't contain git_pull_url", gist.uri);
-                    return Err(io::Error::new(io::ErrorKind::InvalidData,
-                        format!("Couldn't retrieve git_pull_url for gist {}", gist.uri)));
-                },
-            };
-            trace!("GitHub gist #{} has a git_pull_url=\"{}\"",
-                gist.id.as_ref().unwrap(), url);
-            url
+            error!("Gist info for {} doesn't contain git_pull_url", gist.uri);
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("Couldn't retrieve git_pull_url for gist {}", gist.uri)));
         },
     };
+    trace!("GitHub gist #{} has a git_pull_url=\"{}\"", gist.id.as_ref().unwrap(), clone_url);
+    Ok(clone_url)
+}
 
-    // Create the gist's directory and clone it as a Git repo there.
-    debug!("Cloning GitHub gist from {}", clone_url);
-    let path = gist.path();
-    try!(fs::create_dir_all(&path));
-    try!(git::clone(&clone_url, &path));
-
-    // Make sure the gist's executable is, in fact, executable.
-    let executable = gist.path().join(&gist.uri.name);
+/// Find the gist's executable in the directory of its cloned repo and mark it as executable.
+/// Returns the path to the executable.
+///
+/// This has to be done again after every checkout, as Git writes the files anew
+/// with the permissions recorded in the repo.
+fn mark_gist_executable(gist: &Gist, dir: &Path) -> io::Result<PathBuf> {
+    let executable = try!(find_executable(gist, dir)
+        .and_then(|exe| resolve_executable(dir, exe)));
     try!(mark_executable(&executable));
     trace!("Marked gist file as executable: {}", executable.display());
+    Ok(executable)
+}
 
-    // Symlink the main/binary file to the binary directory.
-    let binary = gist.binary_path();
-    if !binary.exists() {
-        try!(fs::create_dir_all(binary.parent().unwrap()));
-        try!(symlink_file(&executable, &binary));
-        trace!("Created symlink to gist executable: {}", binary.display());
+/// Find the gist's executable in the directory of its cloned repo.
+///
+/// Usually it's just the file named after the gist, but the gist name
+/// may also be a sanitized version of the file's actual name.
+fn find_executable(gist: &Gist, dir: &Path) -> io::Result<PathBuf> {
+    let exact = dir.join(&gist.uri.name);
+    if exact.exists() {
+        return Ok(exact);
+    }
+    for entry in try!(fs::read_dir(dir)) {
+        let path = try!(entry).path();
+        let matches = path.file_name().and_then(|n| n.to_str())
+            .map(|n| api::sanitize_filename(n) == gist.uri.name)
+            .unwrap_or(false);
+        if matches {
+            trace!("Gist {} has executable with unsanitized name: {}",
+                gist.uri, path.display());
+            return Ok(path);
+        }
     }
+    Err(io::Error::new(io::ErrorKind::NotFound,
+        format!("Cannot find the executable of gist {} in {}", gist.uri, dir.display())))
+}
 
-    Ok(())
+/// Resolve the gist's executable found in the directory of its cloned repo
+/// into the regular file it refers to.
+///
+/// Normally this is just the executable itself, but Git repos can also contain symlinks.
+/// Those are followed as long as they point to a file within the gist,
+/// while those that lead outside of it are rejected for security reasons.
+fn resolve_executable(dir: &Path, executable: PathBuf) -> io::Result<PathBuf> {
+    if !try!(fs::symlink_metadata(&executable)).file_type().is_symlink() {
+        if !executable.is_file() {
+            // Most likely a Git submodule, which doesn't get cloned along with the gist.
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "gist's entry `{}` is not a regular file", executable.display())));
+        }
+        return Ok(executable);
+    }
+
+    let target = try!(executable.canonicalize().map_err(|e| io::Error::new(e.kind(),
+        format!("gist's entry `{}` is a broken symlink: {}", executable.display(), e))));
+    if !target.starts_with(try!(dir.canonicalize())) {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!(
+            "gist's entry `{}` is a symlink pointing outside of the gist: {}",
+            executable.display(), target.display())));
+    }
+    if !target.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "gist's entry `{}` is a symlink to something other than a regular file: {}",
+            executable.display(), target.display())));
+    }
+    trace!("Gist's entry {} is a symlink to {}", executable.display(), target.display());
+    Ok(target)
+}
+
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use git2::{Repository, Signature};
+    use gist::{Gist, Uri};
+    use util::{link_binary, symlink_file};
+    use super::super::ID;
+    use super::{checkout_gist_ref, mark_gist_executable, resolve_executable};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("gisht-storage-test-{}", name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    /// Commit a single-file gist repo with given content of its `script.sh`.
+    fn commit_script(repo: &Repository, content: &str) {
+        let path = repo.workdir().unwrap().join("script.sh");
+        let mut file = fs::File::create(&path).unwrap();
+        write!(file, "#!/bin/sh\n{}\n", content).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("script.sh")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::now("gisht", "gisht@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.target())
+            .map(|oid| repo.find_commit(oid).unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, "Commit", &tree, &parents).unwrap();
+    }
+
+    #[test]
+    fn gist_runs_after_ref_checkout() {
+        let gist = Gist::new(Uri::new(ID, "Tester", "script.sh").unwrap(), "checkout-test");
+        let path = gist.path();
+        let _ = fs::remove_dir_all(&path);
+        let _ = fs::remove_file(gist.binary_path());
+        let repo = Repository::init(&path).unwrap();
+        commit_script(&repo, "echo first");
+        let first = repo.head().unwrap().target().unwrap();
+        repo.tag_lightweight("first", &repo.find_object(first, None).unwrap(), false).unwrap();
+        commit_script(&repo, "echo second");
+
+        // This is what clone_gist() leaves behind.
+        let executable = mark_gist_executable(&gist, &path).unwrap();
+        link_binary(&executable, &gist.binary_path()).unwrap();
+
+        checkout_gist_ref(&gist, Some("first")).unwrap();
+        let output = Command::new(gist.binary_path()).output().unwrap();
+        assert_eq!("first\n", String::from_utf8_lossy(&output.stdout));
+
+        checkout_gist_ref(&gist, None).unwrap();
+        let output = Command::new(gist.binary_path()).output().unwrap();
+        assert_eq!("second\n", String::from_utf8_lossy(&output.stdout));
+    }
+
+    #[test]
+    fn executable_symlinked_within_gist() {
+        let dir = temp_dir("executable_symlinked_within_gist");
+        fs::File::create(dir.join("script.sh")).unwrap();
+        symlink_file(dir.join("script.sh"), dir.join("entry")).unwrap();
+
+        let resolved = resolve_executable(&dir, dir.join("entry")).unwrap();
+        assert_eq!(dir.join("script.sh").canonicalize().unwrap(), resolved);
+        let resolved = resolve_executable(&dir, dir.join("script.sh")).unwrap();
+        assert_eq!(dir.join("script.sh"), resolved);
+    }
+
+    #[test]
+    fn executable_symlinked_outside_gist() {
+        let outside = temp_dir("executable_symlinked_outside_gist-target");
+        fs::File::create(outside.join("secret")).unwrap();
+        let dir = temp_dir("executable_symlinked_outside_gist");
+        symlink_file(outside.join("secret"), dir.join("entry")).unwrap();
+
+        let err = resolve_executable(&dir, dir.join("entry")).unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+    }
+
+    #[test]
+    fn executable_not_a_file() {
+        let dir = temp_dir("executable_not_a_file");
+        fs::create_dir(dir.join("submodule")).unwrap();
+
+        let err = resolve_executable(&dir, dir.join("submodule")).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
 }
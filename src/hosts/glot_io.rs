@@ -1,19 +1,23 @@
 //! Module implementing glot.io as gist host.
 
 use std::collections::HashMap;
+use std::error::Error;
 use std::fs;
 use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
 
 use hyper::client::Response;
-use hyper::header::UserAgent;
+use hyper::header::Accept;
 use regex::Regex;
 use serde_json::Value as Json;
 
-use ::USER_AGENT;
 use gist::{self, Datum, Gist};
-use util::{http_client, mark_executable, symlink_file, read_json};
+use interrupt;
+use util::{http_client, http_error, link_binary, mark_executable, read_json,
+           send_following_redirects, user_agent};
 use super::{FetchMode, Host};
 use super::common::util::ID_PLACEHOLDER;
+#[cfg(feature = "zip")] use super::common::util::archive;
 use super::common::util::snippet_handler::SnippetHandler;
 
 
@@ -49,17 +53,18 @@ pub struct Glot {
 
 impl Glot {
     #[inline]
-    pub fn new() -> Self {
-        let handler = SnippetHandler::new(
+    pub fn new() -> Result<Self, Box<Error>> {
+        let handler = try!(SnippetHandler::new(
             ID, NAME, HTML_URL_PATTERN,
-            Regex::new("[0-9a-z]+").unwrap()).unwrap();
-        Glot{handler}
+            try!(Regex::new("[0-9a-z]+"))));
+        Ok(Glot{handler})
     }
 }
 
 impl Host for Glot {
     fn id(&self) -> &'static str { ID }
     fn name(&self) -> &str { NAME }
+    fn category(&self) -> &'static str { "API-based" }
 
     fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()> {
         self.handler.ensure_host_id(gist)?;
@@ -75,23 +80,40 @@ impl Host for Glot {
         Ok(())
     }
 
+    /// Save the first file of the gist (the one that'd be its executable) into given file.
+    fn download_gist_to(&self, gist: &Gist, path: &Path) -> io::Result<u64> {
+        self.handler.ensure_host_id(gist)?;
+        let gist = self.handler.resolve_gist(gist);
+
+        let json = api_get_snippet(gist.id.as_ref().unwrap())?;
+        let content = json.pointer("/files/0/content").and_then(Json::as_str)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData,
+                format!("{} gist {} has no files", NAME, gist.uri)))?;
+        self.handler.save_gist(&*gist, content.as_bytes(), path)
+    }
+
     /// Return the URL to gist's HTML website.
     fn gist_url(&self, gist: &Gist) -> io::Result<String> {
         self.handler.gist_url(gist)
     }
 
     /// Return a structure with gist metadata.
-    fn gist_info(&self, gist: &Gist) -> io::Result<Option<gist::Info>> {
+    fn gist_info(&self, gist: &Gist, data: &[Datum]) -> io::Result<Option<gist::Info>> {
         self.handler.ensure_host_id(gist)?;
         let gist = self.handler.resolve_gist(gist);
 
         let id = gist.id.as_ref().unwrap();
         let json = api_get_snippet(id)?;
 
-        let result = build_gist_info(&json, &[]);
+        let result = build_gist_info(&json, data);
         Ok(Some(result))
     }
 
+    /// Check if given URL points to a snippet's browser HTML page.
+    fn recognizes_url(&self, url: &str) -> bool {
+        self.handler.recognizes_url(url)
+    }
+
     /// Return a Gist based on URL to its browser HTML page.
     fn resolve_url(&self, url: &str) -> Option<io::Result<Gist>> {
         self.handler.resolve_url(url)
@@ -99,6 +121,9 @@ impl Host for Glot {
 }
 
 
+/// "Owner" of anonymous snippets, which don't have any owner in their JSON.
+const ANONYMOUS: &'static str = "anonymous";
+
 /// Pattern of HTML URLs, including the `ID_PLACEHOLDER`.
 const HTML_URL_PATTERN: &'static str = "https://glot.io/snippets/${id}";
 
@@ -113,13 +138,23 @@ fn download_gist(gist: &Gist) -> io::Result<()> {
     assert!(!gist.path().exists(), "Directory for gist {} already exists!", gist.uri);
 
     debug!("Downloading {} gist {}", NAME, gist.uri);
-    let json = api_get_snippet(gist.id.as_ref().unwrap())?;
+    let mut resp = api_get_snippet_response(gist.id.as_ref().unwrap(), None)?;
+
+    // Some deployments may serve the whole snippet as an archive instead of JSON.
+    #[cfg(feature = "zip")]
+    {
+        if archive::is_zip(&resp) {
+            return download_gist_archive(gist, &mut resp);
+        }
+    }
+    let json = read_json(&mut resp)?;
 
     // Put all the files in the gist directory, ensuring that it exists first.
     // TODO: check if the filenames are unique
     let path = gist.path();
     let mut executable = None;
     debug!("Saving gist {} under {}", gist.uri, path.display());
+    let _guard = interrupt::guard_fetch(gist);
     fs::create_dir_all(&path)?;
     let files = json.find("files").and_then(Json::as_array)
         .map(|v| &v[..]).unwrap_or(&[]);
@@ -132,7 +167,7 @@ fn download_gist(gist: &Gist) -> io::Result<()> {
         }
         let name = name.unwrap();
 
-        let filepath = path.join(name);
+        let filepath = path.join(local_filename(name));
         trace!("Writing file {} of gist {} as {}", name, gist.uri, filepath.display());
         let mut file = fs::OpenOptions::new()
             .create(true).write(true).truncate(true)
@@ -150,29 +185,76 @@ fn download_gist(gist: &Gist) -> io::Result<()> {
         // This is speculative, of course,
         // but consistent with the way we're GitHUb gists, for example.
         if i == 0 {
-            executable = Some(path.join(&name));
+            executable = Some(filepath);
+        }
+    }
+
+    link_executable(gist, executable)?;
+    store_language(gist, &json);
+    Ok(())
+}
+
+/// Download given glot.io gist from a response that holds its zip archive.
+#[cfg(feature = "zip")]
+fn download_gist_archive(gist: &Gist, resp: &mut Response) -> io::Result<()> {
+    let path = gist.path();
+    debug!("Extracting archive of gist {} under {}", gist.uri, path.display());
+    let _guard = interrupt::guard_fetch(gist);
+    fs::create_dir_all(&path)?;
+    let files = match archive::extract_zip_response(resp, &path) {
+        Ok(files) => files,
+        Err(e) => {
+            // Don't leave a partial copy behind, lest it be mistaken for the whole gist.
+            if let Err(e) = fs::remove_dir_all(&path) {
+                warn!("Failed to remove partially extracted gist {}: {}", gist.uri, e);
+            }
+            return Err(e);
         }
+    };
+
+    // As with the JSON response, the first file is treated as gist's executable.
+    link_executable(gist, files.into_iter().next())?;
+
+    // The archive doesn't say what language the snippet is in,
+    // so the snippet's JSON has to be requested separately for that.
+    match api_get_snippet(gist.id.as_ref().unwrap()) {
+        Ok(json) => store_language(gist, &json),
+        Err(e) => warn!("Failed to retrieve the language of gist {}: {}", gist.uri, e),
+    }
+    Ok(())
+}
+
+/// Keep the language of the gist's snippet (from glot.io JSON response),
+/// so it can be used to pick an interpreter when the gist is later ran from its local copy.
+fn store_language(gist: &Gist, json: &Json) {
+    let info = build_gist_info(json, &[Datum::Language]);
+    if let Err(e) = gist.clone().with_info(info).store_info() {
+        warn!("Failed to store info of gist {}: {}", gist.uri, e);
     }
+}
 
-    // Deal with the gist's executable so that it's correctly symlinked.
+/// Mark given file of the gist as executable and link it as gist's binary.
+fn link_executable(gist: &Gist, executable: Option<PathBuf>) -> io::Result<()> {
     if let Some(executable) = executable {
         mark_executable(&executable)?;
         trace!("Marked gist file as executable: {}", executable.display());
 
-        // Create a symlink in the binary directory.
-        let binary = gist.binary_path();
-        if !binary.exists() {
-            try!(fs::create_dir_all(binary.parent().unwrap()));
-            try!(symlink_file(&executable, &binary));
-            trace!("Created symlink to gist executable: {}", binary.display());
-        }
+        // Create a symlink (or copy) in the binary directory.
+        try!(link_binary(&executable, &gist.binary_path()));
     } else {
         warn!("Gist {} is completely empty (has no files)", gist.uri);
     }
-
     Ok(())
 }
 
+/// Name under which a gist file is stored inside the gist's directory.
+///
+/// glot.io allows filenames like `a/b`, which would otherwise create subdirectories
+/// (or escape the gist's directory altogether), so any path separators are flattened.
+fn local_filename(name: &str) -> String {
+    name.replace(|c| c == '/' || c == '\\', "_")
+}
+
 
 // Fetching gist info
 
@@ -180,11 +262,17 @@ fn download_gist(gist: &Gist) -> io::Result<()> {
 /// JSON response is described here:
 /// https://github.com/prasmussen/glot-snippets/blob/master/api_docs/get_snippet.md.
 fn api_get_snippet(id: &str) -> io::Result<Json> {
+    // Ask for JSON explicitly, in case the snippet would otherwise be served as an archive.
+    let mut resp = api_get_snippet_response(id, Some(Accept::json()))?;
+    read_json(&mut resp)
+}
+
+/// Send the glot.io API request for the snippet with given ID.
+fn api_get_snippet_response(id: &str, accept: Option<Accept>) -> io::Result<Response> {
     debug!("Getting glot.io snippet with ID={}", id);
     let url = API_URL_PATTERN.replace(ID_PLACEHOLDER, id);
     trace!("Sending GET to {}", url);
-    let mut resp = simple_get(url)?;
-    read_json(&mut resp)
+    simple_get(url, accept)
 }
 
 /// GIven a JSON response from glot.io "Get snippet" request,
@@ -199,7 +287,6 @@ fn build_gist_info(json: &Json, data: &[Datum]) -> gist::Info {
     lazy_static! {
         // Mapping of gist::Info items to keys in the JSON.
         static ref INFO_FIELDS: HashMap<Datum, &'static str> = hashmap!{
-            Datum::Owner => "owner",
             Datum::Description => "title", // close enough
             Datum::Language => "language",
             Datum::RawUrl => "url",
@@ -224,10 +311,18 @@ fn build_gist_info(json: &Json, data: &[Datum]) -> gist::Info {
             // Special-cased data that are more complicated to get.
             match datum {
                 Datum::Id => { result.set(datum, id); }
+                Datum::Owner => {
+                    // Anonymous snippets are common, so a missing owner is expected.
+                    let owner = json.find("owner").and_then(Json::as_str).unwrap_or(ANONYMOUS);
+                    result.set(datum, owner);
+                }
                 Datum::BrowserUrl => {
                     let url = HTML_URL_PATTERN.replace(ID_PLACEHOLDER, id);
                     result.set(datum, &url);
                 }
+                Datum::LocalCopy => {
+                    trace!("Freshness of local copy is not tracked for {} gists", NAME);
+                }
                 _ => {
                     panic!("Unexpected {} gist info data piece: {:?}", NAME, datum);
                 }
@@ -240,20 +335,28 @@ fn build_gist_info(json: &Json, data: &[Datum]) -> gist::Info {
 
 // Utility functions
 
-/// Make a simple GET request to GitHub API.
-fn simple_get<U: ToString>(url: U) -> io::Result<Response> {
+/// Make a simple GET request to glot.io API,
+/// optionally specifying the acceptable content type of the response.
+fn simple_get<U: ToString>(url: U, accept: Option<Accept>) -> io::Result<Response> {
     let url = url.to_string();
     let http = http_client();
-    http.get(&url)
-        .header(UserAgent(USER_AGENT.clone()))
-        .send()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    send_following_redirects(&url, |url| {
+        let request = http.get(url).header(user_agent(None));
+        match accept {
+            Some(ref accept) => request.header(accept.clone()),
+            None => request,
+        }
+    }).map_err(http_error)
 }
 
 
 #[cfg(test)]
 mod tests {
-    use super::{API_URL_PATTERN, HTML_URL_PATTERN, ID_PLACEHOLDER};
+    use std::str::FromStr;
+    use serde_json::Value as Json;
+    use gist::Datum;
+    use super::{ANONYMOUS, API_URL_PATTERN, HTML_URL_PATTERN, ID_PLACEHOLDER,
+                build_gist_info, local_filename};
 
     #[test]
     fn valid_html_url_pattern() {
@@ -264,4 +367,26 @@ mod tests {
     fn valid_api_url_pattern() {
         assert!(API_URL_PATTERN.contains(ID_PLACEHOLDER));
     }
+
+    #[test]
+    fn nested_filename_flattened() {
+        assert_eq!("file.py", local_filename("file.py"));
+        assert_eq!("sub_dir_file.py", local_filename("sub/dir/file.py"));
+        assert_eq!("sub_file.py", local_filename("sub\\file.py"));
+        assert_eq!(".._file.py", local_filename("../file.py"));
+    }
+
+    #[test]
+    fn owner_of_anonymous_snippet() {
+        let json = Json::from_str(r#"{"id": "abc123", "title": "Anonymous snippet"}"#).unwrap();
+        let info = build_gist_info(&json, &[Datum::Owner]);
+        assert_eq!(ANONYMOUS, *info.get(Datum::Owner));
+    }
+
+    #[test]
+    fn owner_of_owned_snippet() {
+        let json = Json::from_str(r#"{"id": "abc123", "owner": "JohnDoe"}"#).unwrap();
+        let info = build_gist_info(&json, &[Datum::Owner]);
+        assert_eq!("JohnDoe", *info.get(Datum::Owner));
+    }
 }
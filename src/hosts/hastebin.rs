@@ -1,5 +1,7 @@
 //! Module implementing hastebin.com as gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -13,7 +15,7 @@ pub type Hastebin = internal::Hastebin<Basic>;
 
 impl Hastebin {
     #[inline]
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, Box<Error>> {
         // Hastebin URLs include a completely optional "extension",
         // so the actual URLs can be something like http://hastebin.com/geuyfgdf.foo,
         // where ".foo" is optional indicator of the syntax highlighting
@@ -26,11 +28,11 @@ impl Hastebin {
         //   (so that the syntax highlighting can be applied to a website
         //    opened via `gisht show hb:ahgfuehg.foo`).
         //
-        let inner = Basic::new(ID, "hastebin.com",
-                               "https://hastebin.com/raw/${id}",
-                               "https://hastebin.com/${id}",
-                               Regex::new("[a-z]+").unwrap()).unwrap();
-        internal::Hastebin{inner: inner}
+        let inner = try!(Basic::new(ID, "hastebin.com",
+                                    "https://hastebin.com/raw/${id}",
+                                    "https://hastebin.com/${id}",
+                                    try!(Regex::new("[a-z]+"))));
+        Ok(internal::Hastebin{inner: inner})
     }
 }
 
@@ -50,6 +52,7 @@ mod internal {
     impl<T: Host> Host for Hastebin<T> {
         fn id(&self) -> &'static str { self.inner.id() }
         fn name(&self) -> &str { self.inner.name() }
+        fn category(&self) -> &'static str { self.inner.category() }
 
         fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()> {
             self.inner.fetch_gist(gist, mode)
@@ -69,11 +72,30 @@ mod internal {
             Ok(url)
         }
 
-        fn gist_info(&self, gist: &Gist) -> io::Result<Option<gist::Info>> {
-            let mut info = try!(self.inner.gist_info(gist)).unwrap_or_else(gist::Info::default);
+        fn raw_url(&self, gist: &Gist) -> io::Result<Option<String>> {
+            self.inner.raw_url(gist)
+        }
 
-            // Deduce the gist language from its extension.
-            if let Some(ref full_id) = gist.info(gist::Datum::Id) {
+        /// Return the gist info, including its ID.
+        ///
+        /// The ID is the full one (with the "extension") if it's known from resolve_url(),
+        /// so that it's exactly what the user pasted. Otherwise it's the stripped ID.
+        fn gist_info(&self, gist: &Gist, data: &[gist::Datum]) -> io::Result<Option<gist::Info>> {
+            let mut info = try!(self.inner.gist_info(gist, data))
+                .unwrap_or_else(gist::Info::default);
+
+            if data.is_empty() || data.contains(&gist::Datum::Id) {
+                let id = gist.info(gist::Datum::Id).or_else(|| gist.id.clone());
+                info = info.to_builder().with_opt(gist::Datum::Id, id.as_ref()).build();
+            }
+
+            // Deduce the gist language from its extension (unless it's not needed).
+            let full_id = if data.is_empty() || data.contains(&gist::Datum::Language) {
+                gist.info(gist::Datum::Id)
+            } else {
+                None
+            };
+            if let Some(ref full_id) = full_id {
                 let extension = full_id.rsplit(".").next().unwrap_or_else(|| {
                     // This could only happen if resolve_url() has put some nonsense in Datum::Id.
                     panic!("Invalid format of Hastebin's gist full ID: {}", full_id);
@@ -84,6 +106,23 @@ mod internal {
             Ok(Some(info))
         }
 
+        /// Check if given URL points to a hastebin.com gist,
+        /// ignoring the optional "extension".
+        fn recognizes_url(&self, url: &str) -> bool {
+            let url_obj = match Url::parse(url) {
+                Ok(u) => u,
+                Err(_) => return false,
+            };
+            let url = match url_obj.path_segments().and_then(|ps| ps.last()) {
+                Some(last) => match last.rfind(".") {
+                    Some(dot_idx) => url.trim_right_matches(&last[dot_idx..]),
+                    None => url,
+                },
+                None => return false,
+            };
+            self.inner.recognizes_url(url)
+        }
+
         /// Resolve given URL as potentially pointing to a hastebin.com gist.
         fn resolve_url(&self, mut url: &str) -> Option<io::Result<Gist>> {
             let url_obj = try_opt!(Url::parse(url).ok());
@@ -124,7 +163,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = Hastebin::new();
+        let host = Hastebin::new().unwrap();
         let html_url: String = host.inner.html_url_origin();
 
         let valid_html_urls: Vec<(/* URL */ String,
@@ -219,8 +258,29 @@ mod tests {
         host.inner.put_gist_with_url(gist.clone(), format!("https://hastebin.com/{}", gist_id));
 
         // Gist info should include language deduced from the extension.
-        let info = host.gist_info(&gist).unwrap().unwrap();
+        let info = host.gist_info(&gist, &[]).unwrap().unwrap();
         assert!(info.has(gist::Datum::Language), "Gist info doesn't include Language");
         assert_eq!("bash", &*info.get(gist::Datum::Language));
     }
+
+    #[test]
+    fn gist_info_includes_full_id() {
+        let host = internal::Hastebin{inner: InMemoryHost::with_id(ID)};
+
+        let gist_id = "foo";
+        let full_gist_id = "foo.bash";
+        let gist = Gist::new(gist::Uri::from_name(ID, gist_id).unwrap(), gist_id);
+        host.inner.put_gist_with_url(gist.clone(), format!("https://hastebin.com/{}", gist_id));
+
+        // Without the full ID, the stripped one is shown.
+        let info = host.gist_info(&gist, &[gist::Datum::Id]).unwrap().unwrap();
+        assert_eq!(gist_id, &*info.get(gist::Datum::Id));
+
+        let gist = gist.with_info(gist::InfoBuilder::new()
+            .with(gist::Datum::Id, full_gist_id)
+            .build());
+        let info = host.gist_info(&gist, &[gist::Datum::Id]).unwrap().unwrap();
+        assert_eq!(full_gist_id, &*info.get(gist::Datum::Id));
+        assert_eq!(gist_id, gist.id.as_ref().unwrap());
+    }
 }
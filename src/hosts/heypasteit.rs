@@ -1,5 +1,7 @@
 //! Module implementing heypasteit.com as gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -9,11 +11,11 @@ use hosts::common::Basic;
 pub const ID: &'static str = "hpi";
 
 /// Create the heypasteit.com Host implementation.
-pub fn create() -> Basic {
+pub fn create() -> Result<Basic, Box<Error>> {
     Basic::new(ID, "heypasteit.com",
                "http://heypasteit.com/download/${id}",
                "http://heypasteit.com/clip/${id}",
-               Regex::new("[0-9A-Z]+").unwrap()).unwrap()
+               try!(Regex::new("[0-9A-Z]+")))
 }
 
 
@@ -23,7 +25,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = create();
+        let host = create().unwrap();
         let html_url: String = host.html_url_origin() + "/clip";
 
         let valid_html_urls: Vec<(/* URL */ String,
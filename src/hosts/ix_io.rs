@@ -1,5 +1,7 @@
 //! Module implementing ix.io as a gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -13,15 +15,15 @@ pub type Ix = internal::Ix<Basic>;
 
 impl Ix {
     #[inline]
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, Box<Error>> {
         // Similarly to Hastebin, ix.io URLs may have the language added
         // to the URL. We will strip it before handing over to the Basic host,
         // and then re-add it when generating HTML URL.
-        let inner = Basic::new(ID, "ix.io",
-                               "http://ix.io/${id}",
-                               "http://ix.io/${id}/",  // Yes, just a slash.
-                               Regex::new("[0-9a-z]+").unwrap()).unwrap();
-        internal::Ix{inner: inner}
+        let inner = try!(Basic::new(ID, "ix.io",
+                                    "http://ix.io/${id}",
+                                    "http://ix.io/${id}/",  // Yes, just a slash.
+                                    try!(Regex::new("[0-9a-z]+"))));
+        Ok(internal::Ix{inner: inner})
     }
 }
 
@@ -44,6 +46,7 @@ mod internal {
     impl<T: Host> Host for Ix<T> {
         fn id(&self) -> &'static str { self.inner.id() }
         fn name(&self) -> &str { self.inner.name() }
+        fn category(&self) -> &'static str { self.inner.category() }
 
         fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()> {
             self.inner.fetch_gist(gist, mode)
@@ -60,8 +63,21 @@ mod internal {
             Ok(url)
         }
 
-        fn gist_info(&self, gist: &Gist) -> io::Result<Option<gist::Info>> {
-            self.inner.gist_info(gist)
+        fn raw_url(&self, gist: &Gist) -> io::Result<Option<String>> {
+            self.inner.raw_url(gist)
+        }
+
+        fn gist_info(&self, gist: &Gist, data: &[Datum]) -> io::Result<Option<gist::Info>> {
+            self.inner.gist_info(gist, data)
+        }
+
+        /// Check if given URL points to an ix.io gist.
+        ///
+        /// Since the URL may have a language part in a few different forms,
+        /// only the domain is checked here.
+        fn recognizes_url(&self, url: &str) -> bool {
+            Url::parse(url).ok()
+                .map_or(false, |u| u.host() == Some(url::Host::Domain("ix.io")))
         }
 
         /// Resolve given URL as potentially pointing to an ix.io gist.
@@ -155,7 +171,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = Ix::new();
+        let host = Ix::new().unwrap();
         let html_url: String = host.inner.html_url_origin();
 
         let valid_html_urls: Vec<(/* URL */ String,
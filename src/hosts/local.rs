@@ -0,0 +1,129 @@
+//! Module implementing a directory of the user's own scripts as a pseudo-host.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use url::Url;
+
+use config;
+use gist::{self, Gist};
+use util::link_binary;
+use super::{FetchMode, Host};
+
+
+/// Local host ID.
+pub const ID: &'static str = "local";
+/// Local host name.
+const NAME: &'static str = "local scripts";
+
+
+/// Pseudo-host for scripts in a local directory (`local.dir` or $GISHT_LOCAL_DIR).
+///
+/// The gist `local:foo` is the script `foo` from that directory,
+/// while `local:bar/foo` is the script `foo` from its `bar` subdirectory.
+/// Since the scripts are already there, "fetching" them merely links them as gist binaries.
+#[derive(Debug)]
+pub struct Local;
+
+impl Host for Local {
+    fn id(&self) -> &'static str { ID }
+    fn name(&self) -> &str { NAME }
+    fn category(&self) -> &'static str { "Local" }
+
+    /// Make the script available as the gist's binary.
+    ///
+    /// The fetch mode doesn't matter, because the script is always up to date.
+    fn fetch_gist(&self, gist: &Gist, _: FetchMode) -> io::Result<()> {
+        let script = try!(script_path_for(gist));
+        if !script.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound,
+                format!("there is no script {}", script.display())));
+        }
+        trace!("Linking local script {} as binary of gist {}", script.display(), gist.uri);
+        link_binary(&script, &gist.binary_path())
+    }
+
+    /// Return the file:// URL of the script.
+    fn gist_url(&self, gist: &Gist) -> io::Result<String> {
+        let script = try!(script_path_for(gist));
+        Url::from_file_path(&script).map(|u| u.into_string()).map_err(|_| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot convert {} to a file:// URL", script.display())))
+    }
+
+    /// Check if given URL is a file:// URL of a script inside the local directory.
+    fn recognizes_url(&self, url: &str) -> bool {
+        self.resolve_url(url).is_some()
+    }
+
+    /// Resolve a file:// URL of a script inside the local directory into its gist.
+    fn resolve_url(&self, url: &str) -> Option<io::Result<Gist>> {
+        let path = try_opt!(Url::parse(url).ok().and_then(|u| u.to_file_path().ok()));
+        let dir = try_opt!(config::current().local_dir());
+        let uri = try_opt!(uri_for_path(&dir, &path));
+        debug!("URL {} points to local script {}", url, uri);
+        Some(Ok(Gist::from_uri(uri)))
+    }
+}
+
+
+/// Return the path to the script which given gist refers to.
+fn script_path_for(gist: &Gist) -> io::Result<PathBuf> {
+    let dir = try!(config::current().local_dir().ok_or_else(|| io::Error::new(
+        io::ErrorKind::NotFound,
+        "directory with local scripts is not configured (set $GISHT_LOCAL_DIR)")));
+    Ok(script_path(&dir, &gist.uri))
+}
+
+/// Return the path to the script in `dir` which given gist URI refers to.
+fn script_path(dir: &Path, uri: &gist::Uri) -> PathBuf {
+    let mut path = dir.to_owned();
+    if uri.has_owner() {
+        path.push(&uri.owner);
+    }
+    path.push(&uri.name);
+    path
+}
+
+/// Return the URI of a gist referring to the script at given path,
+/// provided it's directly inside `dir` or one of its subdirectories.
+fn uri_for_path(dir: &Path, path: &Path) -> Option<gist::Uri> {
+    let relative = try_opt!(path.strip_prefix(dir).ok());
+    let parts: Vec<_> = try_opt!(relative.components().map(|c| match c {
+        Component::Normal(part) => part.to_str(),
+        _ => None,
+    }).collect());
+    match parts.len() {
+        1 => gist::Uri::from_name(ID, parts[0]).ok(),
+        2 => gist::Uri::new(ID, parts[0], parts[1]).ok(),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use gist::Uri;
+    use super::{ID, script_path, uri_for_path};
+
+    #[test]
+    fn script_path_from_uri() {
+        let dir = Path::new("/home/user/scripts");
+        assert_eq!(dir.join("foo.sh"), script_path(dir, &Uri::from_name(ID, "foo.sh").unwrap()));
+        assert_eq!(dir.join("bar").join("foo.sh"),
+                   script_path(dir, &Uri::new(ID, "bar", "foo.sh").unwrap()));
+    }
+
+    #[test]
+    fn uri_from_script_path() {
+        let dir = Path::new("/home/user/scripts");
+        assert_eq!(Some(Uri::from_name(ID, "foo.sh").unwrap()),
+                   uri_for_path(dir, &dir.join("foo.sh")));
+        assert_eq!(Some(Uri::new(ID, "bar", "foo.sh").unwrap()),
+                   uri_for_path(dir, &dir.join("bar").join("foo.sh")));
+        assert_eq!(None, uri_for_path(dir, &dir.join("a").join("b").join("foo.sh")));
+        assert_eq!(None, uri_for_path(dir, Path::new("/etc/passwd")));
+        assert_eq!(None, uri_for_path(dir, dir));
+    }
+}
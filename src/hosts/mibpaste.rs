@@ -1,5 +1,7 @@
 //! Module implementing mibpaste.com as a gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 use select::predicate::{Name, Predicate, Text};
 
@@ -17,13 +19,12 @@ pub const ID: &'static str = "mib";
 /// creating pastes independently. The pastes come from the Mibbit
 /// web IRC client.
 #[inline]
-pub fn create() -> Box<Host> {
-    Box::new(
-        HtmlOnly::new(ID, "Mibbit",
-                      "http://mibpaste.com/${id}",
-                      Regex::new("[0-9a-zA-Z]+").unwrap(),
-                      Name("body").descendant(Text)).unwrap()
-    )
+pub fn create() -> Result<Box<Host>, Box<Error>> {
+    let host = try!(HtmlOnly::new(ID, "Mibbit",
+                                  "http://mibpaste.com/${id}",
+                                  try!(Regex::new("[0-9a-zA-Z]+")),
+                                  Name("body").descendant(Text)));
+    Ok(Box::new(host))
 }
 
 
@@ -44,7 +45,7 @@ mod tests {
         //  and the predicate is last in the struct so we don't care about its size).
         //
         // TODO: eliminate all this unsafe nonsense when `impl Trait` is stable
-        let host_box = create();
+        let host_box = create().unwrap();
         let host: &HtmlOnly<Any> = unsafe {
             &*(traitobject::data(&*host_box as *const _) as *const _)
         };
@@ -14,9 +14,11 @@ mod glot_io;
 mod hastebin;
 mod heypasteit;
 mod ix_io;
+mod local;
 mod lpaste;
 mod mibpaste;
 mod mozilla;
+mod paste_debian_net;
 mod paste_rs;
 mod pastebin;
 mod sprunge;
@@ -24,10 +26,16 @@ mod thepasteb_in;
 
 
 use std::collections::HashMap;
-use std::io;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use super::gist::{self, Gist};
+use super::util::{http_client, http_error, http_get, send_following_redirects, user_agent};
 
 
 /// Represents a gists' host: a (web) service that hosts gists (code snippets).
@@ -37,6 +45,11 @@ pub trait Host : Send + Sync {
     fn id(&self) -> &'static str;
     /// Returns a user-visible name of the gists' host.
     fn name(&self) -> &str;
+    /// Returns a user-visible category of the host (like "Git-based"),
+    /// used to group similar hosts when listing them.
+    fn category(&self) -> &'static str {
+        "Other"
+    }
 
     /// Fetch a current version of the gist if necessary.
     ///
@@ -48,20 +61,187 @@ pub trait Host : Send + Sync {
     /// if its a Git repo).
     fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()>;
 
+    /// Fetch the gist like `fetch_gist` does, and make its local copy correspond
+    /// to given ref (e.g. a branch, tag, or commit) of its repository.
+    ///
+    /// This only makes sense for hosts that store gists as Git repositories.
+    fn fetch_gist_at(&self, _: &Gist, _: FetchMode, git_ref: &str) -> io::Result<()> {
+        // This default indicates the host doesn't have any notion of gist refs.
+        Err(io::Error::new(io::ErrorKind::Other,
+            format!("{} doesn't support fetching gists at a ref (`{}`)", self.name(), git_ref)))
+    }
+
+    /// Check whether the local copy of the gist is so old
+    /// that it would have been updated if it were fetched with `FetchMode::Auto`.
+    ///
+    /// Hosts that never update their gists will always return false.
+    fn is_outdated(&self, _: &Gist) -> bool {
+        false
+    }
+
     /// Return a URL to a HTML page that can display the gist.
     /// This may involve talking to the remote host.
     fn gist_url(&self, gist: &Gist) -> io::Result<String>;
 
+    /// Return a URL to the "raw" content of the gist, if the host provides one.
+    ///
+    /// Such a URL can be used to read the gist directly,
+    /// without storing it in the local gists directory.
+    fn raw_url(&self, _: &Gist) -> io::Result<Option<String>> {
+        // This default indicates there is no direct URL to gist content.
+        Ok(None)
+    }
+
+    /// Return a stream with the content of the gist's binary.
+    ///
+    /// If the host provides a raw URL to the gist, it is streamed from there directly.
+    /// Otherwise, the gist is fetched (if needed) and its local copy is read.
+    fn read_gist(&self, gist: &Gist) -> io::Result<Box<Read>> {
+        if let Some(url) = try!(self.raw_url(gist)) {
+            debug!("Streaming {} gist {} from {}", self.name(), gist.uri, url);
+            let http = http_client();
+            let resp = try!(send_following_redirects(&url, |url| {
+                http_get(&http, url).header(user_agent(None))
+            }).map_err(http_error));
+            if !resp.status.is_success() {
+                return Err(io::Error::new(io::ErrorKind::Other,
+                    format!("HTTP {} when reading gist {} from {}", resp.status, gist.uri, url)));
+            }
+            return Ok(Box::new(resp));
+        }
+
+        debug!("{} has no raw URL for gist {}, reading its local copy", self.name(), gist.uri);
+        try!(self.fetch_gist(gist, FetchMode::New));
+        let file = try!(fs::File::open(gist.binary_path()));
+        Ok(Box::new(file))
+    }
+
+    /// Save the content of the gist's binary into given file, outside of the gists directory.
+    /// Returns the number of bytes written.
+    ///
+    /// Unlike fetching, this doesn't make the file executable nor link it as gist's binary.
+    /// By default, the content is obtained through `read_gist`, so hosts without raw URLs
+    /// should override this to avoid fetching the gist.
+    fn download_gist_to(&self, gist: &Gist, path: &Path) -> io::Result<u64> {
+        let mut content = try!(self.read_gist(gist));
+        let mut file = try!(fs::File::create(path));
+        io::copy(&mut content, &mut file)
+    }
+
+    /// Return a stream with the content of the gist's binary if the host provides it
+    /// along with the gist's metadata, so that the gist doesn't have to be fetched.
+    ///
+    /// Hosts will typically only do this for small gists consisting of a single file.
+    fn read_inline_gist(&self, _: &Gist) -> io::Result<Option<Box<Read>>> {
+        // This default indicates there is no gist content in the metadata.
+        Ok(None)
+    }
+
+    /// Whether the host may return gist content from `read_inline_gist` at all.
+    fn has_inline_gists(&self) -> bool {
+        false
+    }
+
     /// Return a structure with information/metadata about the gist.
     ///
+    /// If `data` is non-empty, only the selected pieces of information
+    /// should be retrieved (and included in the result).
+    ///
     /// Note: The return type for this method is io::Result<Option<Info>>
     /// rather than Option<io::Result<Info>> because the availability of
     /// gist metadata may be gist-specific (i.e. some gists have it,
     /// some don't).
-    fn gist_info(&self, gist: &Gist) -> io::Result<Option<gist::Info>> {
+    fn gist_info(&self, gist: &Gist, data: &[gist::Datum]) -> io::Result<Option<gist::Info>> {
         // This default indicates the host cannot fetch any additional gist metadata
         // (beyond what may already have been fetched when resolving gist URL).
-        Ok(gist.info.clone())
+        Ok(gist.info.clone().map(|i| i.select(data)))
+    }
+
+    /// Delete the gist from the remote host.
+    ///
+    /// This typically requires the user to be authenticated with the host
+    /// as the gist's owner, so not all hosts will support it.
+    fn delete_remote_gist(&self, _: &Gist) -> io::Result<()> {
+        // This default indicates the host doesn't support deleting gists at all.
+        Err(io::Error::new(io::ErrorKind::Other,
+            format!("{} doesn't support deleting gists", self.name())))
+    }
+
+    /// Check whether the gist could be deleted from the remote host, without deleting it
+    /// (e.g. whether the user has provided the credentials that are necessary for that).
+    fn check_remote_deletion(&self, _: &Gist) -> io::Result<()> {
+        // This default indicates the host doesn't support deleting gists at all.
+        Err(io::Error::new(io::ErrorKind::Other,
+            format!("{} doesn't support deleting gists", self.name())))
+    }
+
+    /// Create a new gist on the remote host, consisting of a single file
+    /// with given name and content.
+    ///
+    /// Like deleting, this typically requires the user to be authenticated with the host,
+    /// so not all hosts will support it. Public gists are listed on the host,
+    /// while the others are only accessible to those who know their URL.
+    fn create_gist(&self, _filename: &str, _content: &str,
+                   _description: Option<&str>, _public: bool) -> io::Result<Gist> {
+        // This default indicates the host doesn't support creating gists at all.
+        Err(io::Error::new(io::ErrorKind::Other,
+            format!("{} doesn't support creating gists", self.name())))
+    }
+
+    /// Return a complete gist corresponding to one that's been given
+    /// by a host-specific shorthand (like just the gist's ID).
+    ///
+    /// Note: The return type of this method is an Option (Option<io::Result<Gist>>)
+    /// because the gist may not be recognized as such a shorthand.
+    fn resolve_shorthand(&self, _: &Gist) -> Option<io::Result<Gist>> {
+        // This default indicates the host doesn't recognize any shorthands.
+        None
+    }
+
+    /// List the gists on the remote host whose names are similar to that of given gist
+    /// (which presumably doesn't exist), from the most to the least similar.
+    ///
+    /// This is used to suggest what the user may have meant if they mistyped the gist's name.
+    fn similar_gists(&self, _: &Gist) -> io::Result<Vec<Gist>> {
+        // This default indicates the host has no way of finding similar gists.
+        Ok(vec![])
+    }
+
+    /// List the gists belonging to given owner on the remote host.
+    ///
+    /// The gists aren't downloaded, but they may carry some Info
+    /// (like their description or language).
+    ///
+    /// If the host returns the gists in pages, `max_pages` can limit
+    /// how many of them are fetched. Pages should be fetched lazily,
+    /// as the returned iterator is advanced.
+    ///
+    /// If `since` is given, only the gists updated after that time should be listed.
+    fn owner_gists<'o>(&self, _: &'o str, _max_pages: Option<usize>,
+                       _since: Option<SystemTime>) -> io::Result<Box<Iterator<Item=Gist> + 'o>> {
+        // This default indicates the host cannot list gists by their owner.
+        Err(io::Error::new(io::ErrorKind::Other,
+            format!("{} doesn't support listing gists of an owner", self.name())))
+    }
+
+    /// List all the gists of the user that's authenticated with the remote host,
+    /// including their private gists (if the host has such a concept).
+    ///
+    /// Like with `owner_gists`, the gists aren't downloaded but may carry some Info.
+    fn own_gists(&self) -> io::Result<Vec<Gist>> {
+        // This default indicates the host doesn't support authentication.
+        Err(io::Error::new(io::ErrorKind::Other,
+            format!("{} doesn't support listing gists of the authenticated user", self.name())))
+    }
+
+    /// Check whether given URL looks like it points to a gist hosted by this host.
+    ///
+    /// Unlike `resolve_url`, this should be a cheap check (e.g. a regex match
+    /// or a domain comparison) that never touches the network. It's used to pick
+    /// the host that will then be asked to actually resolve the URL.
+    fn recognizes_url(&self, _: &str) -> bool {
+        // This default matches the one for resolve_url below.
+        false
     }
 
     /// Return a gist corresponding to the given URL.
@@ -74,25 +254,84 @@ pub trait Host : Send + Sync {
         // as pointing to any gist hosted by this host.
         None
     }
+
+    /// Whether `resolve_url` has to talk to the host (e.g. its API) to resolve a URL,
+    /// making it worthwhile to cache the gists that URLs have resolved into.
+    fn resolves_urls_remotely(&self) -> bool {
+        // This default corresponds to URLs being resolved by matching them against a pattern,
+        // which also retains any extra info they carry (like the language of the gist).
+        false
+    }
 }
 // TODO: remove this boilerplate impl when `impl Trait` is stable
 // and we can use it in create() methods of specific hosts
 impl<H: Host + ?Sized> Host for Box<H> {
     fn id(&self) -> &'static str { (&**self).id() }
     fn name(&self) -> &str       { (&**self).name() }
+    fn category(&self) -> &'static str { (&**self).category() }
 
     fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()> {
         (&**self).fetch_gist(gist, mode)
     }
+    fn fetch_gist_at(&self, gist: &Gist, mode: FetchMode, git_ref: &str) -> io::Result<()> {
+        (&**self).fetch_gist_at(gist, mode, git_ref)
+    }
+    fn is_outdated(&self, gist: &Gist) -> bool {
+        (&**self).is_outdated(gist)
+    }
     fn gist_url(&self, gist: &Gist) -> io::Result<String> {
         (&**self).gist_url(gist)
     }
-    fn gist_info(&self, gist: &Gist) -> io::Result<Option<gist::Info>> {
-        (&**self).gist_info(gist)
+    fn raw_url(&self, gist: &Gist) -> io::Result<Option<String>> {
+        (&**self).raw_url(gist)
+    }
+    fn read_gist(&self, gist: &Gist) -> io::Result<Box<Read>> {
+        (&**self).read_gist(gist)
+    }
+    fn download_gist_to(&self, gist: &Gist, path: &Path) -> io::Result<u64> {
+        (&**self).download_gist_to(gist, path)
+    }
+    fn read_inline_gist(&self, gist: &Gist) -> io::Result<Option<Box<Read>>> {
+        (&**self).read_inline_gist(gist)
+    }
+    fn has_inline_gists(&self) -> bool {
+        (&**self).has_inline_gists()
+    }
+    fn gist_info(&self, gist: &Gist, data: &[gist::Datum]) -> io::Result<Option<gist::Info>> {
+        (&**self).gist_info(gist, data)
+    }
+    fn delete_remote_gist(&self, gist: &Gist) -> io::Result<()> {
+        (&**self).delete_remote_gist(gist)
+    }
+    fn check_remote_deletion(&self, gist: &Gist) -> io::Result<()> {
+        (&**self).check_remote_deletion(gist)
+    }
+    fn create_gist(&self, filename: &str, content: &str,
+                   description: Option<&str>, public: bool) -> io::Result<Gist> {
+        (&**self).create_gist(filename, content, description, public)
+    }
+    fn resolve_shorthand(&self, gist: &Gist) -> Option<io::Result<Gist>> {
+        (&**self).resolve_shorthand(gist)
+    }
+    fn similar_gists(&self, gist: &Gist) -> io::Result<Vec<Gist>> {
+        (&**self).similar_gists(gist)
+    }
+    fn owner_gists<'o>(&self, owner: &'o str, max_pages: Option<usize>,
+                       since: Option<SystemTime>) -> io::Result<Box<Iterator<Item=Gist> + 'o>> {
+        (&**self).owner_gists(owner, max_pages, since)
+    }
+    fn own_gists(&self) -> io::Result<Vec<Gist>> {
+        (&**self).own_gists()
+    }
+    fn recognizes_url(&self, url: &str) -> bool {
+        (&**self).recognizes_url(url)
     }
     fn resolve_url(&self, url: &str) -> Option<io::Result<Gist>> {
         (&**self).resolve_url(url)
     }
+    fn resolves_urls_remotely(&self) -> bool {
+        (&**self).resolves_urls_remotely()
+    }
 }
 
 macro_attr! {
@@ -117,36 +356,95 @@ impl Default for FetchMode {
 }
 
 
-/// Mapping of gist host identifiers to Host structs.
+/// Create all the built-in gist hosts, mapped by their identifiers.
+///
+/// If some of the hosts are misconfigured (e.g. have invalid URL patterns),
+/// the returned error describes all of them, not just the first one.
+fn build_hosts() -> Result<HashMap<&'static str, Arc<Host>>, HostsError> {
+    fn arc<H: Host + 'static>(host: H) -> Arc<Host> { Arc::new(host) }
+
+    let results: Vec<(&'static str, Result<Arc<Host>, Box<Error>>)> = vec![
+        (github::ID, Ok(arc(github::GitHub::new()))),
+        (pastebin::ID, pastebin::create().map(arc)),
+        (lpaste::ID, lpaste::create().map(arc)),
+        (heypasteit::ID, heypasteit::create().map(arc)),
+        (bpaste::ID, bpaste::create().map(arc)),
+        (mozilla::ID, mozilla::create().map(arc)),
+        (paste_rs::ID, paste_rs::create().map(arc)),
+        (paste_debian_net::ID, paste_debian_net::create().map(arc)),
+        (hastebin::ID, hastebin::Hastebin::new().map(arc)),
+        (mibpaste::ID, mibpaste::create().map(arc)),
+        (sprunge::ID, sprunge::Sprunge::new().map(arc)),
+        (dpaste_de::ID, dpaste_de::create().map(arc)),
+        (thepasteb_in::ID, thepasteb_in::create().map(arc)),
+        (ix_io::ID, ix_io::Ix::new().map(arc)),
+        (codesend::ID, codesend::create().map(arc)),
+        (glot_io::ID, glot_io::Glot::new().map(arc)),
+        (local::ID, Ok(arc(local::Local))),
+    ];
+
+    let mut hosts = HashMap::new();
+    let mut errors = vec![];
+    for (id, result) in results {
+        match result {
+            Ok(host) => { hosts.insert(id, host); },
+            Err(e) => errors.push((id, e.to_string())),
+        }
+    }
+    if errors.is_empty() { Ok(hosts) } else { Err(HostsError(errors)) }
+}
+
+/// Error when some of the built-in gist hosts couldn't be created.
+/// Contains IDs of those hosts, along with the error messages.
+#[derive(Clone, Debug)]
+pub struct HostsError(Vec<(&'static str, String)>);
+
+impl Error for HostsError {
+    fn description(&self) -> &str { "misconfigured gist hosts" }
+    fn cause(&self) -> Option<&Error> { None }
+}
+
+impl fmt::Display for HostsError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, "{} gist host(s) misconfigured:", self.0.len()));
+        for &(id, ref error) in &self.0 {
+            try!(write!(fmt, "\n  {}: {}", id, error));
+        }
+        Ok(())
+    }
+}
+
 lazy_static! {
-    static ref BUILTIN_HOSTS: HashMap<&'static str, Arc<Host>> = hashmap!{
-        github::ID => Arc::new(github::GitHub::new()) as Arc<Host>,
-        pastebin::ID => Arc::new(pastebin::create()) as Arc<Host>,
-        lpaste::ID => Arc::new(lpaste::create()) as Arc<Host>,
-        heypasteit::ID => Arc::new(heypasteit::create()) as Arc<Host>,
-        bpaste::ID => Arc::new(bpaste::create()) as Arc<Host>,
-        mozilla::ID => Arc::new(mozilla::create()) as Arc<Host>,
-        paste_rs::ID => Arc::new(paste_rs::create()) as Arc<Host>,
-        hastebin::ID => Arc::new(hastebin::Hastebin::new()) as Arc<Host>,
-        mibpaste::ID => Arc::new(mibpaste::create()) as Arc<Host>,
-        sprunge::ID => Arc::new(sprunge::Sprunge::new()) as Arc<Host>,
-        dpaste_de::ID => Arc::new(dpaste_de::create()) as Arc<Host>,
-        thepasteb_in::ID => Arc::new(thepasteb_in::create()) as Arc<Host>,
-        ix_io::ID => Arc::new(ix_io::Ix::new()) as Arc<Host>,
-        codesend::ID => Arc::new(codesend::create()) as Arc<Host>,
-        glot_io::ID => Arc::new(glot_io::Glot::new()) as Arc<Host>,
-    };
+    static ref BUILTIN_HOSTS: Result<HashMap<&'static str, Arc<Host>>, HostsError> = build_hosts();
 }
+
+/// Check that all the built-in gist hosts have been configured correctly.
+///
+/// This should be called before HOSTS are used,
+/// as otherwise any host misconfiguration results in a panic.
+pub fn validate_hosts() -> Result<(), HostsError> {
+    BUILTIN_HOSTS.as_ref().map(|_| ()).map_err(|e| e.clone())
+}
+
+/// Return the mapping of built-in gist host identifiers to Host structs.
+fn builtin_hosts() -> &'static HashMap<&'static str, Arc<Host>> {
+    match *BUILTIN_HOSTS {
+        Ok(ref hosts) => hosts,
+        Err(ref e) => panic!("{}", e),
+    }
+}
+
 #[cfg(not(test))]
 lazy_static! {
-    pub static ref HOSTS: HashMap<&'static str, Arc<Host>> = BUILTIN_HOSTS.clone();
+    /// Mapping of gist host identifiers to Host structs.
+    pub static ref HOSTS: HashMap<&'static str, Arc<Host>> = builtin_hosts().clone();
 }
 #[cfg(test)]
 lazy_static! {
     pub static ref HOSTS: HashMap<&'static str, Arc<Host>> = {
-        use testing::{INMEMORY_HOST_DEFAULT_ID, InMemoryHost};
-        let mut hosts = BUILTIN_HOSTS.clone();
-        hosts.insert(INMEMORY_HOST_DEFAULT_ID, Arc::new(InMemoryHost::new()) as Arc<Host>);
+        use testing::{INMEMORY_HOST, INMEMORY_HOST_DEFAULT_ID};
+        let mut hosts = builtin_hosts().clone();
+        hosts.insert(INMEMORY_HOST_DEFAULT_ID, INMEMORY_HOST.clone() as Arc<Host>);
         hosts
     };
 }
@@ -157,7 +455,22 @@ pub const DEFAULT_HOST_ID: &'static str = github::ID;
 #[cfg(test)]
 mod tests {
     use testing::INMEMORY_HOST_DEFAULT_ID;
-    use super::{DEFAULT_HOST_ID, HOSTS};
+    use super::{DEFAULT_HOST_ID, HOSTS, HostsError, validate_hosts};
+    use super::{github, glot_io, hastebin, ix_io, local, paste_debian_net, sprunge};
+
+    #[test]
+    fn builtin_hosts_valid() {
+        if let Err(e) = validate_hosts() {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn hosts_error_lists_all_hosts() {
+        let error = HostsError(vec![("foo", "bad pattern".into()), ("bar", "bad regex".into())]);
+        assert_eq!("2 gist host(s) misconfigured:\n  foo: bad pattern\n  bar: bad regex",
+                   format!("{}", error));
+    }
 
     #[test]
     fn consistent_hosts() {
@@ -177,4 +490,48 @@ mod tests {
         assert!(HOSTS.contains_key(INMEMORY_HOST_DEFAULT_ID),
             "Test in-memory host ID `{}` doesn't occur among known gist hosts", INMEMORY_HOST_DEFAULT_ID);
     }
+
+    #[test]
+    fn host_categories() {
+        assert_eq!("Git-based", HOSTS[github::ID].category());
+        assert_eq!("API-based", HOSTS[glot_io::ID].category());
+        assert_eq!("Immutable pastebins", HOSTS[sprunge::ID].category());
+        assert_eq!("Local", HOSTS[local::ID].category());
+        assert_eq!("Other", HOSTS[INMEMORY_HOST_DEFAULT_ID].category());
+    }
+
+    #[test]
+    fn urls_recognized_by_single_host() {
+        let urls = vec![
+            ("https://gist.github.com/Xion/d0f351a97c65679bb911bafe", github::ID),
+            ("http://gist.github.com/d0f351a97c65679bb911bafe", github::ID),
+            ("http://sprunge.us/fhdFG36ok?py", sprunge::ID),
+            ("https://hastebin.com/abcdef.py", hastebin::ID),
+            ("http://ix.io/abc/py/", ix_io::ID),
+            ("https://paste.debian.net/1234567/", paste_debian_net::ID),
+        ];
+        for (url, expected_id) in urls {
+            let ids: Vec<_> = HOSTS.iter()
+                .filter(|&(_, host)| host.recognizes_url(url))
+                .map(|(&id, _)| id)
+                .collect();
+            assert_eq!(vec![expected_id], ids, "URL {} recognized by wrong hosts", url);
+        }
+    }
+
+    #[test]
+    fn unknown_url_not_recognized() {
+        let url = "http://example.com/foo/bar";
+        assert!(HOSTS.values().all(|host| !host.recognizes_url(url)),
+            "URL {} incorrectly recognized by some host", url);
+    }
+
+    /// Verify that only the hosts which ask their API about URLs have them cached.
+    #[test]
+    fn urls_resolved_remotely() {
+        for (id, host) in HOSTS.iter() {
+            assert_eq!(*id == github::ID, host.resolves_urls_remotely(),
+                "Unexpected way of resolving URLs for host {}", id);
+        }
+    }
 }
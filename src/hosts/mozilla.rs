@@ -1,5 +1,7 @@
 //! Module implementing Mozilla's pastebin as Basic gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -9,11 +11,11 @@ use hosts::common::Basic;
 pub const ID: &'static str = "moz";
 
 /// Create the pastebin.mozilla.org Host implementation.
-pub fn create() -> Basic {
+pub fn create() -> Result<Basic, Box<Error>> {
     Basic::new(ID, "Mozilla's Pastebin",
                "https://pastebin.mozilla.org/?dl=${id}",
                "https://pastebin.mozilla.org/${id}",
-               Regex::new("[0-9]+").unwrap()).unwrap()
+               try!(Regex::new("[0-9]+")))
 }
 
 
@@ -23,7 +25,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = create();
+        let host = create().unwrap();
         let html_url: String = host.html_url_origin();
 
         let valid_html_urls: Vec<(/* URL */ String,
@@ -0,0 +1,72 @@
+//! Module implementing paste.debian.net as Basic gist host.
+
+use std::error::Error;
+
+use regex::Regex;
+
+use hosts::common::Basic;
+
+
+/// paste.debian.net host ID.
+pub const ID: &'static str = "deb";
+
+/// Create the paste.debian.net Host implementation.
+///
+/// Its pastes are shown with syntax highlighting on their HTML pages,
+/// while the raw content has to be requested separately from /plain/ with a POST form.
+pub fn create() -> Result<Basic, Box<Error>> {
+    let host = try!(Basic::new(ID, "paste.debian.net",
+                               "https://paste.debian.net/plain/${id}",
+                               "https://paste.debian.net/${id}/",
+                               try!(Regex::new("[0-9]+"))));
+    Ok(host.with_raw_post_body("id=${id}&format=plain"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use gist::{Gist, Uri};
+    use hosts::Host;
+    use super::{create, ID};
+
+    #[test]
+    fn raw_content_requested_with_post() {
+        let host = create().unwrap();
+        assert_eq!(Some("id=1234567&format=plain".into()), host.raw_post_body_for("1234567"));
+
+        // As the content can't be simply GET, there is no raw URL to show.
+        let gist = Gist::from_uri(Uri::from_name(ID, "1234567").unwrap()).with_id("1234567");
+        assert_eq!(None, host.raw_url(&gist).unwrap());
+    }
+
+    #[test]
+    fn html_url_regex() {
+        let host = create().unwrap();
+        let html_url: String = host.html_url_origin();
+
+        let valid_html_urls: Vec<(/* URL */ String,
+                                  /* ID */ &'static str)> = vec![
+            (html_url.clone() + "/1/", "1"),                // short
+            (html_url.clone() + "/1234567/", "1234567"),    // long
+        ];
+        let invalid_html_urls: Vec<String> = vec![
+            html_url.clone() + "/a/b/c/",                   // too many path segments
+            html_url.clone() + "/abc/",                     // not a number
+            html_url.clone() + "/plain/1234567",            // raw URL
+            html_url.clone() + "//",                        // ID must not be empty
+            "https://example.com/1234567/".into(),          // wrong domain
+            "foobar".into(),                                // not even an URL
+        ];
+
+        let html_url_re = host.html_url_regex();
+        for (ref valid_url, id) in valid_html_urls {
+            let captures = html_url_re.captures(valid_url)
+                .expect(&format!("Paste's HTML URL was incorrectly deemed invalid: {}", valid_url));
+            assert_eq!(id, &captures["id"]);
+        }
+        for ref invalid_url in invalid_html_urls {
+            assert!(!html_url_re.is_match(invalid_url),
+                "URL was incorrectly deemed a valid gist HTML URL: {}", invalid_url);
+        }
+    }
+}
@@ -1,5 +1,7 @@
 //! Module implementing paste.rs (sic) as a Basic gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -9,12 +11,12 @@ use hosts::common::Basic;
 pub const ID: &'static str = "rs";
 
 /// Create the paste.rs Host implementation.
-pub fn create() -> Basic {
+pub fn create() -> Result<Basic, Box<Error>> {
     // paste.rs service has almost no web UI, so the "raw" and "browser" URLs are identical.
     let url_pattern = "http://paste.rs/${id}";
     Basic::new(ID, "paste.rs",
                url_pattern, url_pattern,
-               Regex::new("[A-Za-z0-9]+").unwrap()).unwrap()
+               try!(Regex::new("[A-Za-z0-9]+")))
 }
 
 
@@ -24,7 +26,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = create();
+        let host = create().unwrap();
         let html_url: String = host.html_url_origin();
 
         let valid_html_urls: Vec<(/* URL */ String,
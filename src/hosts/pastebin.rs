@@ -6,6 +6,8 @@
 //! user names are not a part of pastes' URLs,
 //! which is why it can be implemented as a Basic gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -15,11 +17,11 @@ use hosts::common::Basic;
 pub const ID: &'static str = "pb";
 
 /// Create the Pastebin Host implementation.
-pub fn create() -> Basic {
+pub fn create() -> Result<Basic, Box<Error>> {
     Basic::new(ID, "Pastebin.com",
                "http://pastebin.com/raw/${id}",
                "http://pastebin.com/${id}",
-               Regex::new("[0-9a-zA-Z]+").unwrap()).unwrap()
+               try!(Regex::new("[0-9a-zA-Z]+")))
 }
 
 
@@ -29,7 +31,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = create();
+        let host = create().unwrap();
         let html_url: String = host.html_url_origin();
 
         let valid_html_urls: Vec<(/* URL */ String,
@@ -1,5 +1,7 @@
 //! Module implementing sprunge.us as gist host.
 
+use std::error::Error;
+
 use regex::Regex;
 
 use hosts::common::Basic;
@@ -13,16 +15,16 @@ pub const ID: &'static str = "spr";
 pub type Sprunge = internal::Sprunge<Basic>;
 
 impl Sprunge {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, Box<Error>> {
         // We use the same URL pattern for both raw & HTML URLs,
         // but for the HTML one, we will also try to include a ?<lang> query string,
         // e.g. http://sprunge.us/ABcdEF?py
         let url_pattern = "http://sprunge.us/${id}";
 
-        let inner = Basic::new(ID, "sprunge.us",
-                               url_pattern, url_pattern,
-                               Regex::new("[0-9a-zA-Z]+").unwrap()).unwrap();
-        internal::Sprunge{inner: inner}
+        let inner = try!(Basic::new(ID, "sprunge.us",
+                                    url_pattern, url_pattern,
+                                    try!(Regex::new("[0-9a-zA-Z]+"))));
+        Ok(internal::Sprunge{inner: inner})
     }
 }
 
@@ -45,6 +47,7 @@ mod internal {
     impl<T: Host> Host for Sprunge<T> {
         fn id(&self) -> &'static str { self.inner.id() }
         fn name(&self) -> &str { self.inner.name() }
+        fn category(&self) -> &'static str { self.inner.category() }
 
         fn fetch_gist(&self, gist: &Gist, mode: FetchMode) -> io::Result<()> {
             self.inner.fetch_gist(gist, mode)
@@ -65,8 +68,23 @@ mod internal {
             Ok(url_obj.to_string())
         }
 
-        fn gist_info(&self, gist: &Gist) -> io::Result<Option<gist::Info>> {
-            self.inner.gist_info(gist)
+        fn raw_url(&self, gist: &Gist) -> io::Result<Option<String>> {
+            self.inner.raw_url(gist)
+        }
+
+        fn gist_info(&self, gist: &Gist, data: &[Datum]) -> io::Result<Option<gist::Info>> {
+            self.inner.gist_info(gist, data)
+        }
+
+        /// Check if given URL points to a sprunge.us gist,
+        /// ignoring the optional query string with the language.
+        fn recognizes_url(&self, url: &str) -> bool {
+            let mut url_obj = match Url::parse(url) {
+                Ok(u) => u,
+                Err(_) => return false,
+            };
+            url_obj.set_query(None);
+            self.inner.recognizes_url(url_obj.as_str())
         }
 
         /// Resolve given URL as potentially pointing to a sprunge.us gist.
@@ -109,7 +127,7 @@ mod tests {
 
     #[test]
     fn html_url_regex() {
-        let host = Sprunge::new();
+        let host = Sprunge::new().unwrap();
         let html_url: String = host.inner.html_url_origin();
 
         let valid_html_urls: Vec<(/* URL */ String,
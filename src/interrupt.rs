@@ -0,0 +1,238 @@
+//! Module handling interruptions (like Ctrl+C) while gists are being fetched.
+//!
+//! An interrupted download may leave a partially written gist behind,
+//! which would be later mistaken for a complete local copy.
+//! To prevent that, paths of gists that are being fetched are tracked,
+//! and removed if the program is interrupted in the meantime.
+
+use std::cell::Cell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use exitcode::ExitCode;
+
+use gist::Gist;
+
+
+/// Exit code used when the program is interrupted with SIGINT
+/// (following the shell convention of 128 + signal number).
+pub const INTERRUPTED: ExitCode = 128 + 2;
+
+lazy_static! {
+    /// Paths of gists that are currently being fetched.
+    static ref IN_PROGRESS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+}
+
+thread_local! {
+    /// Number of fetch guards that are currently alive in this thread.
+    /// (Interruptions are handled per thread, as that's how signals are blocked).
+    static GUARD_DEPTH: Cell<usize> = Cell::new(0);
+}
+
+
+/// Mark a gist as being fetched, until the returned guard is dropped.
+///
+/// If the program is interrupted before that happens,
+/// the (partial) local copy of a new gist is removed.
+/// Gists that already have a local copy (i.e. are being re-downloaded) are left intact.
+pub fn guard_fetch(gist: &Gist) -> FetchGuard {
+    let paths = if gist.is_local() {
+        vec![]
+    } else {
+        vec![gist.path(), gist.binary_path()]
+    };
+    FetchGuard::new(paths)
+}
+
+
+/// Guard that keeps a set of paths tracked as "in progress" for as long as it's alive.
+///
+/// Guards may be nested; interruptions are handled until the outermost one is dropped.
+#[derive(Debug)]
+pub struct FetchGuard {
+    paths: Vec<PathBuf>,
+}
+
+impl FetchGuard {
+    fn new(paths: Vec<PathBuf>) -> Self {
+        IN_PROGRESS.lock().unwrap().extend(paths.iter().cloned());
+        GUARD_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        platform::handle_interrupts();
+        FetchGuard{paths: paths}
+    }
+}
+
+impl Drop for FetchGuard {
+    fn drop(&mut self) {
+        {
+            let mut in_progress = IN_PROGRESS.lock().unwrap();
+            for path in &self.paths {
+                if let Some(pos) = in_progress.iter().position(|p| p == path) {
+                    in_progress.remove(pos);
+                }
+            }
+        }
+        let depth = GUARD_DEPTH.with(|depth| { depth.set(depth.get() - 1); depth.get() });
+        if depth == 0 {
+            platform::ignore_interrupts();
+        }
+    }
+}
+
+
+/// Remove all the paths of gists that are still being fetched.
+fn remove_in_progress() {
+    let in_progress = match IN_PROGRESS.lock() {
+        Ok(paths) => paths,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    for path in in_progress.iter() {
+        match remove_path(path) {
+            Ok(true) => debug!("Removed incomplete gist path {}", path.display()),
+            Ok(false) => {},
+            Err(e) => warn!("Failed to remove incomplete gist path {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Remove a file, symlink, or directory tree at given path.
+/// Returns whether anything has been removed.
+fn remove_path(path: &Path) -> io::Result<bool> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if metadata.is_dir() {
+        try!(fs::remove_dir_all(path));
+    } else {
+        try!(fs::remove_file(path));
+    }
+    Ok(true)
+}
+
+
+#[cfg(unix)]
+mod platform {
+    use std::mem;
+    use std::process::exit;
+    use std::ptr;
+    use std::sync::{Once, ONCE_INIT};
+    use std::thread;
+
+    use libc;
+
+    use super::{INTERRUPTED, remove_in_progress};
+
+    /// Start handling SIGINT by removing the gists being fetched, and then exiting.
+    ///
+    /// The signal is blocked in the calling thread, and instead received
+    /// synchronously by a dedicated thread (via sigwait()). This way the cleanup
+    /// doesn't have to be done in a signal handler, where it would be unsafe.
+    pub fn handle_interrupts() {
+        static SPAWN_WAITER: Once = ONCE_INIT;
+
+        // The signal must be blocked before spawning the waiter thread,
+        // so that it inherits the blocked signal mask.
+        let set = sigint_set();
+        unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, ptr::null_mut()); }
+        SPAWN_WAITER.call_once(|| {
+            thread::spawn(move || {
+                let mut signal: libc::c_int = 0;
+                if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+                    warn!("Failed to wait for SIGINT; interruptions won't be handled");
+                    return;
+                }
+                warn!("Interrupted; removing incomplete gists...");
+                remove_in_progress();
+                exit(INTERRUPTED);
+            });
+        });
+    }
+
+    /// Stop handling SIGINT in the calling thread, restoring its default behavior.
+    ///
+    /// Note that this is necessary for gists that are executed later,
+    /// as they would otherwise inherit the blocked signal.
+    pub fn ignore_interrupts() {
+        let set = sigint_set();
+        unsafe { libc::pthread_sigmask(libc::SIG_UNBLOCK, &set, ptr::null_mut()); }
+    }
+
+    /// Whether SIGINT is currently blocked in the calling thread.
+    #[cfg(test)]
+    pub fn interrupts_blocked() -> bool {
+        unsafe {
+            let mut current: libc::sigset_t = mem::zeroed();
+            libc::pthread_sigmask(libc::SIG_BLOCK, ptr::null(), &mut current);
+            libc::sigismember(&current, libc::SIGINT) == 1
+        }
+    }
+
+    fn sigint_set() -> libc::sigset_t {
+        unsafe {
+            let mut set: libc::sigset_t = mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::sigaddset(&mut set, libc::SIGINT);
+            set
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod platform {
+    // TODO: handle Ctrl+C on Windows, too (via SetConsoleCtrlHandler)
+    pub fn handle_interrupts() {}
+    pub fn ignore_interrupts() {}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{FetchGuard, IN_PROGRESS, remove_in_progress};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("gisht-interrupt-test-{}", name))
+    }
+
+    #[test]
+    fn guard_tracks_paths() {
+        let path = temp_path("guard_tracks_paths");
+        {
+            let _guard = FetchGuard::new(vec![path.clone()]);
+            assert!(IN_PROGRESS.lock().unwrap().contains(&path));
+        }
+        assert!(!IN_PROGRESS.lock().unwrap().contains(&path));
+    }
+
+    #[test]
+    fn in_progress_paths_removed() {
+        let dir = temp_path("in_progress_paths_removed");
+        fs::create_dir_all(dir.join("subdir")).unwrap();
+        fs::File::create(dir.join("subdir").join("file")).unwrap();
+
+        let _guard = FetchGuard::new(vec![dir.clone()]);
+        remove_in_progress();
+        assert!(!dir.exists(), "Path of a gist in progress hasn't been removed");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn nested_guards_keep_handling_interrupts() {
+        use super::platform::interrupts_blocked;
+
+        let outer = FetchGuard::new(vec![temp_path("nested_guards_outer")]);
+        {
+            let _inner = FetchGuard::new(vec![temp_path("nested_guards_inner")]);
+        }
+        assert!(interrupts_blocked(), "Inner guard stopped handling interrupts");
+        drop(outer);
+        assert!(!interrupts_blocked(), "Interrupts still handled after all guards are dropped");
+    }
+}
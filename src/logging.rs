@@ -9,7 +9,6 @@ use std::env;
 use std::io;
 
 use ansi_term::{Colour, Style};
-use isatty;
 use log::SetLoggerError;
 use slog::{self, DrainExt, FilterLevel, Level};
 use slog_envlogger::LogBuilder;
@@ -40,37 +39,21 @@ const NEGATIVE_VERBOSITY_LEVELS: &'static [FilterLevel] = &[
 
 /// Initialize logging with given verbosity.
 /// The verbosity value has the same meaning as in args::Options::verbosity.
-pub fn init(verbosity: isize) -> Result<(), SetLoggerError> {
-    let istty = cfg!(unix) && isatty::stderr_isatty();
-    let stderr = slog_stream::stream(io::stderr(), LogFormat{tty: istty});
-
-    // Determine the log filtering level based on verbosity.
-    // If the argument is excessive, log that but clamp to the highest/lowest log level.
-    let mut verbosity = verbosity;
-    let mut excessive = false;
-    let level = if verbosity >= 0 {
-        if verbosity >= POSITIVE_VERBOSITY_LEVELS.len() as isize {
-            excessive = true;
-            verbosity = POSITIVE_VERBOSITY_LEVELS.len() as isize - 1;
-        }
-        POSITIVE_VERBOSITY_LEVELS[verbosity as usize]
-    } else {
-        verbosity = -verbosity;
-        if verbosity >= NEGATIVE_VERBOSITY_LEVELS.len() as isize {
-            excessive = true;
-            verbosity = NEGATIVE_VERBOSITY_LEVELS.len() as isize - 1;
-        }
-        NEGATIVE_VERBOSITY_LEVELS[verbosity as usize]
-    };
+///
+/// If `colors` is true, log messages will be styled for output to a terminal.
+pub fn init(verbosity: isize, colors: bool) -> Result<(), SetLoggerError> {
+    let stderr = slog_stream::stream(io::stderr(), LogFormat{tty: colors});
+    let (level, excessive) = filter_level(verbosity);
 
     // Include universal logger options, like the level.
     let mut builder = LogBuilder::new(stderr);
     builder = builder.filter(None, level);
 
     // Make some of the libraries less chatty.
+    let library_level = library_filter_level(level);
     builder = builder
-        .filter(Some("html5ever"), FilterLevel::Info)
-        .filter(Some("hyper"), FilterLevel::Info);
+        .filter(Some("html5ever"), library_level)
+        .filter(Some("hyper"), library_level);
 
     // Include any additional config from environmental variables.
     // This will override the options above if necessary,
@@ -92,6 +75,32 @@ pub fn init(verbosity: isize) -> Result<(), SetLoggerError> {
     Ok(())
 }
 
+/// Determine the log filtering level based on verbosity.
+///
+/// If the verbosity is excessive, it's clamped to the highest/lowest log level
+/// and the second returned value is true.
+fn filter_level(verbosity: isize) -> (FilterLevel, bool) {
+    let levels = if verbosity >= 0 { POSITIVE_VERBOSITY_LEVELS }
+                 else { NEGATIVE_VERBOSITY_LEVELS };
+    let index = verbosity.abs() as usize;
+    if index >= levels.len() {
+        (levels[levels.len() - 1], true)
+    } else {
+        (levels[index], false)
+    }
+}
+
+/// Determine the log filtering level for chatty libraries (like hyper),
+/// given the level for the rest of the application.
+///
+/// These libraries never log anything more detailed than Info
+/// (unless overridden by $RUST_LOG), but they must also respect
+/// the lower level when the application is told to be quiet.
+fn library_filter_level(level: FilterLevel) -> FilterLevel {
+    const MAX_LIBRARY_LEVEL: FilterLevel = FilterLevel::Info;
+    if level.as_usize() < MAX_LIBRARY_LEVEL.as_usize() { level } else { MAX_LIBRARY_LEVEL }
+}
+
 
 // Log formatting
 
@@ -174,7 +183,8 @@ lazy_static! {
 mod tests {
     use slog::FilterLevel;
     use super::{DEFAULT_LEVEL, DEFAULT_FILTER_LEVEL,
-                NEGATIVE_VERBOSITY_LEVELS, POSITIVE_VERBOSITY_LEVELS};
+                NEGATIVE_VERBOSITY_LEVELS, POSITIVE_VERBOSITY_LEVELS,
+                filter_level, library_filter_level};
 
     /// Check that default logging level is defined consistently.
     #[test]
@@ -192,4 +202,29 @@ mod tests {
         assert!(NEGATIVE_VERBOSITY_LEVELS.contains(&FilterLevel::Off),
             "Verbosity levels don't allow to turn logging off completely");
     }
+
+    #[test]
+    fn filter_levels() {
+        assert_eq!((DEFAULT_FILTER_LEVEL, false), filter_level(0));
+        assert_eq!((FilterLevel::Trace, false), filter_level(2));
+        assert_eq!((FilterLevel::Trace, true), filter_level(10));
+        assert_eq!((FilterLevel::Off, true), filter_level(-10));
+
+        // -qq should hide warnings (and anything less severe).
+        let (level, _) = filter_level(-2);
+        assert!(level.as_usize() < FilterLevel::Warning.as_usize(),
+            "-qq doesn't silence warnings (level: {:?})", level);
+    }
+
+    /// Check that chatty libraries are never more verbose than the application itself.
+    #[test]
+    fn library_filter_levels() {
+        for verbosity in -5..5 {
+            let (level, _) = filter_level(verbosity);
+            let library_level = library_filter_level(level);
+            assert!(library_level.as_usize() <= level.as_usize(),
+                "Libraries more verbose ({:?}) than application ({:?})", library_level, level);
+            assert!(library_level.as_usize() <= FilterLevel::Info.as_usize());
+        }
+    }
 }
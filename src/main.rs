@@ -16,6 +16,7 @@
              extern crate isatty;
              extern crate itertools;
 #[macro_use] extern crate lazy_static;
+             extern crate libc;
 #[macro_use] extern crate macro_attr;
 #[macro_use] extern crate maplit;
              extern crate regex;
@@ -25,10 +26,12 @@
              extern crate slog_envlogger;
              extern crate slog_stdlog;
              extern crate slog_stream;
+             extern crate tempfile;
              extern crate time;
 #[macro_use] extern crate try_opt;
              extern crate url;
              extern crate webbrowser;
+#[cfg(feature = "zip")] extern crate zip;
 
 // `slog` must precede `log` in declarations here, because we want to simultaneously:
 // * use the standard `log` macros
@@ -36,7 +39,6 @@
 #[macro_use] extern crate slog;
 #[macro_use] extern crate log;
 
-#[cfg(test)] extern crate tempfile;
 #[cfg(test)] extern crate traitobject;
 
 
@@ -45,19 +47,22 @@ mod util;
 
 mod args;
 mod commands;
+mod config;
+mod encoding;
 mod ext;
+mod failure;
 mod gist;
 mod hosts;
+mod interrupt;
 mod logging;
 
 #[cfg(test)]
 mod testing;
 
 
-use std::env;
 use std::error::Error;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::PathBuf;
 use std::process::exit;
 
@@ -66,8 +71,10 @@ use exitcode::ExitCode;
 use log::LogLevel::*;
 
 use args::{ArgsError, Command, GistArg, Locality, Options};
+use encoding::Encoding;
 use commands::*;
-use gist::Gist;
+use failure::{exit_code_for, Failure};
+use gist::{Datum, Gist};
 use hosts::FetchMode;
 
 
@@ -87,19 +94,30 @@ lazy_static! {
 }
 
 lazy_static! {
-    /// User-Agent header that the program uses for all outgoing HTTP requests.
-    static ref USER_AGENT: String = match *VERSION {
-        Some(version) => format!("{}/{}", *NAME, version),
-        None => String::from(*NAME),
-    };
+    /// User-Agent header for outgoing HTTP requests, unless overridden by the configuration.
+    /// Use util::user_agent to obtain the one that's actually used.
+    static ref USER_AGENT: String = config::current().user_agent().map(String::from)
+        .unwrap_or_else(|| match *VERSION {
+            Some(version) => format!("{}/{}", *NAME, version),
+            None => String::from(*NAME),
+        });
 }
 
+#[cfg(not(test))]
 lazy_static! {
     /// Main application's directory.
-    static ref APP_DIR: PathBuf =
-        env::home_dir().unwrap_or_else(env::temp_dir).join(&format!(".{}", *NAME));
-    // TODO: use the app_dirs crate to get this in a more portable way
+    /// This is configurable (e.g. via $GISHT_HOME), so it mustn't be used before
+    /// the configuration has been loaded.
+    static ref APP_DIR: PathBuf = config::current().app_dir();
+}
+#[cfg(test)]
+lazy_static! {
+    /// Application directory used in tests,
+    /// so that they don't interfere with any actual gists of the user.
+    static ref APP_DIR: PathBuf = ::std::env::temp_dir().join(&format!(".{}-test", *NAME));
+}
 
+lazy_static! {
     /// Directory where gist sources are stored.
     ///
     /// Subdirectories are structured in a host-specific way,
@@ -112,18 +130,52 @@ lazy_static! {
     /// Subdirectories are structured by host & the remaining part of gist URI,
     /// e.g. `~/.gisht/bin/gh/Octocat/foo` (a symlink) for `gh:Octocat/foo`.
     static ref BIN_DIR: PathBuf = APP_DIR.join("bin");
+
+    /// Directory recording the gist files that gist binaries are copies of,
+    /// if they aren't symlinks (see --no-symlink).
+    ///
+    /// Each record is a file with the same relative path as the binary,
+    /// e.g. `~/.gisht/sources/gh/Octocat/foo` for `~/.gisht/bin/gh/Octocat/foo`.
+    static ref BINARY_SOURCES_DIR: PathBuf = APP_DIR.join("sources");
+
+    /// File with glob patterns of gist URIs that bulk operations should ignore,
+    /// e.g. `~/.gisht/ignore`.
+    static ref IGNORE_FILE: PathBuf = APP_DIR.join("ignore");
+
+    /// File caching the gists that browser URLs have resolved into,
+    /// e.g. `~/.gisht/url_cache`.
+    static ref URL_CACHE_FILE: PathBuf = APP_DIR.join("url_cache");
 }
 
 
 fn main() {
+    // Hosts are used when parsing args already, so misconfigured ones must be caught early.
+    if let Err(e) = hosts::validate_hosts() {
+        writeln!(&mut io::stderr(), "{}", e).unwrap();
+        exit(exit_code_for(Failure::Config));
+    }
+
     let opts = args::parse().unwrap_or_else(|e| {
         print_args_error(e).unwrap();
-        exit(exitcode::USAGE);
+        exit(exit_code_for(Failure::Usage));
     });
 
-    logging::init(opts.verbosity).unwrap();
+    logging::init(opts.verbosity, opts.color.for_stderr()).unwrap();
     log_signature();
 
+    if opts.no_symlink {
+        util::disable_symlinks();
+    }
+    util::set_http_timeouts(opts.connect_timeout, opts.read_timeout);
+
+    match config::load(opts.config.as_ref().map(|p| p.as_path())) {
+        Ok(cfg) => config::set(cfg),
+        Err(e) => {
+            error!("{}", e);
+            exit(exit_code_for(Failure::Config));
+        },
+    }
+
     ensure_app_dir(&opts).unwrap_or_else(|e| exit(e));
 
     let exit_code = run(opts);
@@ -172,12 +224,12 @@ fn ensure_app_dir(opts: &Options) -> Result<(), ExitCode> {
     }
 
     // If the first run is interactive, display a warning about executing untrusted code.
-    if isatty::stderr_isatty() && !opts.quiet() {
+    if util::stderr_isatty() && !opts.quiet() {
         trace!("Displaying warning about executing untrusted code...");
-        let should_continue = display_warning().unwrap();
+        let should_continue = display_warning(opts.color.for_stderr()).unwrap();
         if !should_continue {
             debug!("Warning not acknowledged -- exiting.");
-            return Err(exitcode::TEMPFAIL);
+            return Err(exit_code_for(Failure::Cancelled));
         }
         trace!("Warning acknowledged.");
     } else {
@@ -188,7 +240,7 @@ fn ensure_app_dir(opts: &Options) -> Result<(), ExitCode> {
     if let Err(err) = fs::create_dir_all(&*APP_DIR) {
         error!("Failed to create application directory ({}): {}",
             APP_DIR.display(), err);
-        return Err(exitcode::OSFILE);
+        return Err(exit_code_for(Failure::AppDir));
     }
     debug!("Application directory ({}) created successfully.", APP_DIR.display());
     Ok(())
@@ -198,22 +250,105 @@ fn ensure_app_dir(opts: &Options) -> Result<(), ExitCode> {
 /// Entry point for running the actual program logic
 /// once the command line has been parsed.
 fn run(opts: Options) -> ExitCode {
+    if opts.run_from_stdin {
+        return run_stdin_script(opts.gist_args.as_ref().unwrap(), &opts.run_env);
+    }
+    if let Some(&GistArg::Data(ref data)) = opts.gist.as_ref() {
+        if opts.command == Command::Run {
+            // Explicit --language takes precedence over the declared MIME type.
+            let language = opts.run_language.as_ref().map(|l| l as &str)
+                .or_else(|| data.language());
+            return run_data_script(&data.content, language,
+                                   opts.gist_args.as_ref().unwrap(), &opts.run_env);
+        }
+    }
     if opts.command.takes_gist() {
         let gist = match decode_gist(&opts) {
             Ok(g) => g,
             Err(code) => return code,
         };
+        // Local gists start without any info, but some of it (like the language)
+        // may have been stored when they were downloaded.
+        let gist = if opts.command == Command::Run { gist.with_stored_info() } else { gist };
+        let gist = match opts.run_language {
+            Some(ref language) => override_language(gist, language),
+            None => gist,
+        };
         match opts.command {
-            Command::Run => run_gist(&gist, opts.gist_args.as_ref().unwrap()),
-            Command::Which => print_binary_path(&gist),
-            Command::Print => print_gist(&gist),
-            Command::Open => open_gist(&gist),
-            Command::Info => show_gist_info(&gist),
+            Command::Run => {
+                // Gists required by this one are fetched the same way it was,
+                // except that with --cached, nothing is fetched at all.
+                let fetch_mode = match opts.locality {
+                    None => Some(FetchMode::Auto),
+                    Some(Locality::Local) => None,
+                    Some(Locality::Remote) => Some(FetchMode::Always),
+                };
+                if let Some(mode) = fetch_mode {
+                    if let Err(code) = fetch_requirements(&gist, mode, opts.run_requires_depth) {
+                        return code;
+                    }
+                }
+                if opts.run_confirm && util::stderr_isatty() {
+                    match confirm_run(&gist, opts.color.for_stderr()) {
+                        Ok(true) => {},
+                        Ok(false) => {
+                            debug!("Running gist {} not confirmed -- exiting.", gist.uri);
+                            return exit_code_for(Failure::Cancelled);
+                        },
+                        Err(e) => {
+                            error!("Failed to preview gist {}: {}", gist.uri, e);
+                            return exit_code_for(Failure::Io);
+                        },
+                    }
+                }
+                run_gist(&gist, opts.gist_args.as_ref().unwrap(), &opts.run_env,
+                         !opts.run_no_guess)
+            },
+            Command::Which => print_binary_path(&gist, opts.print0, opts.which_json,
+                                                &mut io::stdout()),
+            Command::Print if opts.print_remote => with_pager(|out| print_remote_gist(&gist, out)),
+            Command::Print if opts.print_all_files =>
+                with_pager(|out| print_all_gist_files(&gist, out)),
+            Command::Print if !gist.is_local() => with_pager(|out| print_new_gist(&gist, out)),
+            Command::Print => with_pager(|out| print_gist(&gist, out)),
+            Command::Open => open_gist(&gist, opts.open_raw, &mut io::stdout()),
+            Command::Info => show_gist_info(&gist, opts.info_fields.as_ref().unwrap(),
+                &opts.info_redact, opts.locality == Some(Locality::Local),
+                opts.color.for_stdout(), opts.info_relative_time, &mut io::stdout()),
+            Command::Export => export_gist(&gist, opts.export_dir.as_ref().unwrap(),
+                                           opts.export_force),
+            Command::Download => download_gist(&gist, opts.download_path.as_ref().unwrap(),
+                                               opts.download_force),
+            Command::Purge => purge_gist(&gist, opts.purge_remote, opts.purge_dry_run,
+                                          &mut io::stdout()),
             _ => unreachable!(),
         }
     } else {
         match opts.command {
-            Command::Hosts => list_hosts(),
+            Command::List => list_gists(opts.list_json, opts.print0, !opts.no_host_prefix,
+                                        &mut io::stdout()),
+            Command::Search => search_gists(opts.search_pattern.as_ref().unwrap(),
+                                            opts.search_name_only, opts.print0,
+                                            !opts.no_host_prefix, opts.search_jobs,
+                                            &mut io::stdout()),
+            Command::OwnerGists => {
+                let owner = opts.owner.as_ref().unwrap();
+                let host = &*hosts::HOSTS[&owner.host_id as &str];
+                list_owner_gists(host, &owner.owner, opts.owner_max_pages, opts.owner_since,
+                                 !opts.no_host_prefix, &mut io::stdout())
+            },
+            Command::Create => {
+                let host = &*hosts::HOSTS[hosts::DEFAULT_HOST_ID];
+                create_gist(host, opts.create_file.as_ref().unwrap(),
+                            opts.create_description.as_ref().map(|d| d as &str),
+                            opts.create_public, &mut io::stdout())
+            },
+            Command::Hosts => list_hosts(&mut io::stdout()),
+            Command::Doctor => if opts.doctor_list_interpreters {
+                list_interpreters(&mut io::stdout())
+            } else {
+                diagnose(&mut io::stdout())
+            },
             _ => unreachable!(),
         }
     }
@@ -224,26 +359,37 @@ fn run(opts: Options) -> ExitCode {
 /// This may include fetching a fresh gist from a host, or updating it.
 /// If an error occurred, returns the corresponding exit code.
 fn decode_gist(opts: &Options) -> Result<Gist, ExitCode> {
-    if opts.gist.is_none() {
+    if opts.gist.is_none() && opts.run_find.is_none() {
         error!("No gist provided. Try --help?");
-        return Err(exitcode::USAGE);
+        return Err(exit_code_for(Failure::Usage));
     }
 
-    let gist = match opts.gist.as_ref().unwrap() {
-        &GistArg::Uri(ref uri) => {
+    let mut gist = match opts.gist.as_ref() {
+        None => {
+            let query = opts.run_find.as_ref().unwrap();
+            debug!("Gist to be found by description matching `{}`", query);
+            try!(find_own_gist(query))
+        },
+        Some(&GistArg::Uri(ref uri)) => {
             debug!("Gist {} specified as the argument", uri);
-            Gist::from_uri(uri.clone())
+            try!(resolve_shorthand(Gist::from_uri(uri.clone()), opts.locality.as_ref()))
         },
-        &GistArg::BrowserUrl(ref url) => {
+        Some(&GistArg::BrowserUrl(ref url)) => {
             debug!("Gist URL `{}` specified as the argument", url);
             let url = url.as_str();
-            let maybe_gist = try!(gist_from_url(url));
+            // With --fetch, the URL is resolved anew rather than taken from the cache.
+            let use_cache = opts.locality != Some(Locality::Remote);
+            let maybe_gist = try!(gist_from_url(url, opts.follow_redirect, use_cache));
             let gist = try!(maybe_gist.ok_or_else(|| {
                 error!("URL doesn't point to any gist service: {}", url);
-                exitcode::UNAVAILABLE
+                exit_code_for(Failure::Host)
             }));
             gist
         },
+        Some(&GistArg::Data(_)) => {
+            error!("Scripts given as data: URIs can only be ran");
+            return Err(exit_code_for(Failure::Usage));
+        },
     };
 
     let is_local = gist.is_local();
@@ -253,80 +399,308 @@ fn decode_gist(opts: &Options) -> Result<Gist, ExitCode> {
         trace!("Gist {} hasn't been downloaded yet", gist.uri);
     }
 
-    // Depending on the locality options, fetch a new or updated version of the gist,
-    // or perhaps even error out if it doesn't exist.
+    // There is no point fetching the gist just to remove it,
+    // and printing or downloading it straight from remote host doesn't need a local copy either.
+    if opts.command == Command::Purge || opts.command == Command::Download || opts.print_remote {
+        return Ok(gist);
+    }
+    // Similarly, a small gist may be printed from its metadata if the host includes
+    // its content there, as long as the user doesn't require a particular copy of the gist
+    // (or all of its files).
+    if opts.command == Command::Print && !is_local && gist.uri.host().has_inline_gists()
+        && !opts.print_all_files && opts.locality.is_none()
+        && opts.git_ref.is_none() && opts.encoding.is_none() {
+        return Ok(gist);
+    }
+
+    // Depending on the locality options, fetch a new or updated version of the gist
+    // (at a particular ref, if requested), or perhaps even error out if it doesn't exist.
+    let fetch = |gist: &Gist, mode| match opts.git_ref {
+        Some(ref git_ref) => gist.uri.host().fetch_gist_at(gist, mode, git_ref),
+        None => gist.uri.host().fetch_gist(gist, mode),
+    };
     match opts.locality {
         None => {
             debug!("Possibly fetching or updating gist {}...", gist.uri);
             let fetch_mode = if is_local { FetchMode::Auto } else { FetchMode::New };
-            if let Err(err) = gist.uri.host().fetch_gist(&gist, fetch_mode) {
-                error!("Failed to download/update gist {}: {}", gist.uri, err);
-                return Err(exitcode::IOERR);
+            if let Err(err) = fetch(&gist, fetch_mode) {
+                gist::forget_gist_urls(&gist);
+                if !opts.run_fuzzy || is_local {
+                    error!("Failed to download/update gist {}: {}", gist.uri, err);
+                    return Err(exit_code_for(Failure::Io));
+                }
+                gist = try!(fuzzy_match_gist(&gist, err));
+                if let Err(err) = fetch(&gist, FetchMode::New) {
+                    error!("Failed to download gist {}: {}", gist.uri, err);
+                    return Err(exit_code_for(Failure::Io));
+                }
             }
         },
         Some(Locality::Local) => {
             if !is_local {
                 error!("Gist {} is not available locally -- exiting.", gist.uri);
-                return Err(exitcode::NOINPUT);
+                return Err(exit_code_for(Failure::NotLocal));
+            }
+            if gist.uri.host().is_outdated(&gist) {
+                warn!("Local copy of gist {} hasn't been updated in a while; \
+                       use --fetch to get the latest version", gist.uri);
             }
         },
         Some(Locality::Remote) => {
             debug!("Forcing update of gist {}...", gist.uri);
-            if let Err(err) = gist.uri.host().fetch_gist(&gist, FetchMode::Always) {
+            if let Err(err) = fetch(&gist, FetchMode::Always) {
+                gist::forget_gist_urls(&gist);
                 error!("Failed to update gist {}: {}", gist.uri, err);
-                return Err(exitcode::IOERR);
+                return Err(exit_code_for(Failure::Io));
             }
         },
     }
 
+    // If requested, convert the freshly downloaded gist to UTF-8.
+    if let Some(encoding) = opts.encoding {
+        if !is_local || opts.locality == Some(Locality::Remote) {
+            try!(transcode_gist(&gist, encoding));
+        }
+    }
+
     Ok(gist)
 }
 
+/// Convert the gist's binary from given encoding into UTF-8.
+fn transcode_gist(gist: &Gist, encoding: Encoding) -> Result<(), ExitCode> {
+    let executable = try!(util::binary_source(&gist.binary_path()).map_err(|err| {
+        error!("Failed to locate the binary of gist {}: {}", gist.uri, err);
+        exit_code_for(Failure::Io)
+    }));
+
+    // Changing files in a Git repository would prevent it from being updated later.
+    let in_repo = executable.parent().map(|d| d.join(".git").exists()).unwrap_or(false);
+    if in_repo {
+        warn!("Gist {} is a Git repository and won't be converted from {}",
+            gist.uri, encoding.name());
+        return Ok(());
+    }
+
+    debug!("Converting gist {} from {} to UTF-8...", gist.uri, encoding.name());
+    try!(encoding::transcode_file(&executable, encoding).map_err(|err| {
+        error!("Failed to convert gist {} from {}: {}", gist.uri, encoding.name(), err);
+        exit_code_for(Failure::BadInput)
+    }));
+    util::refresh_binary(&gist.binary_path()).map_err(|err| {
+        error!("Failed to update the binary of gist {}: {}", gist.uri, err);
+        exit_code_for(Failure::Io)
+    })
+}
+
+/// Find the gist that the user most likely meant if the given one couldn't be fetched
+/// (as enabled by --fuzzy), by asking its host for gists with similar names.
+///
+/// If there is exactly one such gist and the session is interactive,
+/// the user is asked whether to use it instead. Otherwise, it's just suggested.
+fn fuzzy_match_gist(gist: &Gist, err: io::Error) -> Result<Gist, ExitCode> {
+    let mut similar = gist.uri.host().similar_gists(gist).unwrap_or_else(|e| {
+        debug!("Couldn't find gists similar to {}: {}", gist.uri, e);
+        vec![]
+    });
+    if similar.len() == 1 && util::stderr_isatty() {
+        return match confirm_similar_gist(gist, &similar[0]) {
+            Ok(true) => Ok(similar.remove(0)),
+            Ok(false) => {
+                debug!("Similar gist {} not accepted -- exiting.", similar[0].uri);
+                Err(exit_code_for(Failure::Cancelled))
+            },
+            Err(e) => {
+                error!("Failed to confirm running gist {}: {}", similar[0].uri, e);
+                Err(exit_code_for(Failure::Io))
+            },
+        };
+    }
+
+    if similar.is_empty() {
+        error!("Failed to download/update gist {}: {}", gist.uri, err);
+    } else {
+        let uris: Vec<_> = similar.iter().map(|g| g.uri.to_string()).collect();
+        error!("Failed to download/update gist {}: {} (did you mean {}?)",
+            gist.uri, err, uris.join(" or "));
+    }
+    Err(exit_code_for(Failure::Io))
+}
+
+/// Ask the user whether a gist with a similar name should be used instead of the given one.
+fn confirm_similar_gist(gist: &Gist, similar: &Gist) -> io::Result<bool> {
+    try!(write!(&mut io::stderr(), "Gist {} not found. Run {} instead? [{}/N]: ",
+        gist.uri, similar.uri, YES));
+    let mut answer = String::with_capacity(YES.len());
+    try!(io::stdin().read_line(&mut answer));
+    Ok(answer.trim().to_lowercase() == YES)
+}
+
+/// Resolve a gist that may've been given by a host-specific shorthand
+/// (like a bare GitHub gist ID), unless it's already available locally as is.
+fn resolve_shorthand(gist: Gist, locality: Option<&Locality>) -> Result<Gist, ExitCode> {
+    if gist.is_local() || locality == Some(&Locality::Local) {
+        return Ok(gist);
+    }
+    match gist.uri.host().resolve_shorthand(&gist) {
+        Some(Ok(resolved)) => {
+            debug!("Gist {} resolved to {}", gist.uri, resolved.uri);
+            Ok(resolved)
+        },
+        Some(Err(err)) => {
+            error!("Error asking {} to resolve gist {}: {}",
+                gist.uri.host().name(), gist.uri, err);
+            Err(exit_code_for(Failure::Io))
+        },
+        None => Ok(gist),
+    }
+}
+
+/// Make the gist report given language, so that it's used when guessing its interpreter.
+fn override_language(gist: Gist, language: &str) -> Gist {
+    if !is_known_language(language) {
+        warn!("Unknown language `{}`, it will be treated as a file extension instead",
+            language);
+    }
+    debug!("Overriding the language of gist {} to {}", gist.uri, language);
+    let info = gist.info_builder().with(Datum::Language, language).build();
+    gist.with_info(info)
+}
+
+/// Find the single gist of the user authenticated with the default host (GitHub)
+/// whose description contains given query (case-insensitively).
+///
+/// If more than one gist matches, they are all listed and an error is returned.
+fn find_own_gist(query: &str) -> Result<Gist, ExitCode> {
+    let host = &hosts::HOSTS[hosts::DEFAULT_HOST_ID];
+    let gists = try!(host.own_gists().map_err(|err| {
+        error!("Failed to list your {} gists: {}", host.name(), err);
+        exit_code_for(Failure::Host)
+    }));
+    debug!("Searching {} {} gist(s) for description matching `{}`",
+        gists.len(), host.name(), query);
+
+    let needle = query.to_lowercase();
+    let mut matching: Vec<_> = gists.into_iter().filter(|g| {
+        g.info(Datum::Description).map(|d| d.to_lowercase().contains(&needle)).unwrap_or(false)
+    }).collect();
+    match matching.len() {
+        0 => {
+            error!("None of your {} gists has a description matching `{}`", host.name(), query);
+            Err(exit_code_for(Failure::Usage))
+        },
+        1 => {
+            let gist = matching.remove(0);
+            info!("Found gist {} by its description", gist.uri);
+            Ok(gist)
+        },
+        count => {
+            error!("{} of your {} gists have a description matching `{}`:",
+                count, host.name(), query);
+            for gist in &matching {
+                let _ = writeln!(&mut io::stderr(), "  {}  {}",
+                    gist.uri, gist.info(Datum::Description).unwrap_or_default());
+            }
+            Err(exit_code_for(Failure::Usage))
+        },
+    }
+}
+
 /// Ask each of the known gist hosts if they can resolve this URL into a gist.
-fn gist_from_url(url: &str) -> Result<Option<Gist>, ExitCode> {
-    let mut gists = Vec::new();
-
-    for (id, host) in &*hosts::HOSTS {
-        if let Some(res) = host.resolve_url(url) {
-            let gist = try!(res.map_err(|err| {
-                error!("Error asking {} to resolve gist from URL `{}`: {}",
-                    host.name(), url, err);
-                exitcode::IOERR
-            }));
-            trace!("URL `{}` identified as `{}` ({}) gist", url, id, host.name());
-            gists.push(gist);
+///
+/// If `follow_redirect` is true, a URL pointing to a known URL shortener
+/// is expanded first (by following a single redirect).
+///
+/// Gists that URLs resolve into are cached for a while (see `url_cache_ttl` config)
+/// if the host had to be asked about them, and if `use_cache` is true,
+/// a recently resolved URL is looked up there first.
+fn gist_from_url(url: &str, follow_redirect: bool,
+                 use_cache: bool) -> Result<Option<Gist>, ExitCode> {
+    let expanded_url = if follow_redirect {
+        try!(util::expand_short_url(url).map_err(|err| {
+            error!("Failed to expand shortened URL `{}`: {}", url, err);
+            exit_code_for(Failure::Host)
+        }))
+    } else {
+        None
+    };
+    let url = match expanded_url {
+        Some(ref expanded) => {
+            debug!("URL `{}` expanded to `{}`", url, expanded);
+            expanded.as_str()
+        },
+        None => url,
+    };
+
+    // Strip the parts of URL (like fragment or tracking params) that hosts should ignore.
+    let canonical = util::canonical_url(url);
+    if canonical != url {
+        debug!("URL `{}` canonicalized to `{}`", url, canonical);
+    }
+    let url = &*canonical;
+
+    if use_cache {
+        // (Only resolving URLs of some hosts is costly enough to be cached,
+        // so entries for others may only be stale leftovers).
+        let cached = gist::cached_gist_for_url(url)
+            .and_then(|g| if g.uri.host().resolves_urls_remotely() { Some(g) } else { None });
+        if let Some(gist) = cached {
+            debug!("URL `{}` identified as gist {} (cached)", url, gist.uri);
+            return Ok(Some(gist));
         }
     }
 
+    // Find the host that recognizes the URL first, so that only that one is asked
+    // to actually resolve it (which may involve talking to the host's API).
+    let mut matching: Vec<_> = hosts::HOSTS.iter()
+        .filter(|&(_, host)| host.recognizes_url(url))
+        .collect();
+
     // If more that one host matches, it's an inconsistency in host definitions.
     // Since we cannot determine with host "wins", we can only bail.
-    if gists.len() > 1 {
-        let hosts_csv = gists.into_iter().map(|gist| {
-            let host = gist.uri.host();
-            format!("{} ({})", host.name(), host.id())
+    if matching.len() > 1 {
+        let hosts_csv = matching.into_iter().map(|(id, host)| {
+            format!("{} ({})", host.name(), id)
         }).collect::<Vec<_>>().join(", ");
         error!("Multiple matching hosts for URL `{}`: {}", url, hosts_csv);
-        return Err(exitcode::CONFIG);
+        return Err(exit_code_for(Failure::Config));
     }
 
-    Ok(gists.pop())
+    let (_, host) = match matching.pop() {
+        Some(m) => m,
+        None => return Ok(None),
+    };
+    let gist = match host.resolve_url(url) {
+        Some(res) => try!(res.map_err(|err| {
+            error!("Error asking {} to resolve gist from URL `{}`: {}",
+                host.name(), url, err);
+            exit_code_for(Failure::Io)
+        })),
+        None => return Ok(None),
+    };
+    // Show the canonical URI with -v, so that it's possible to confirm
+    // the URL was interpreted correctly before the gist gets fetched.
+    debug!("URL `{}` identified as {} gist {}", url, host.name(), gist.uri);
+    if host.resolves_urls_remotely() {
+        gist::cache_gist_for_url(url, &gist);
+    }
+    Ok(Some(gist))
 }
 
 
 /// Display warning about executing untrusted code and ask the user to continue.
 /// Returns whether the user decided to continue.
-fn display_warning() -> io::Result<bool> {
-    try!(writeln!(&mut io::stderr(), "{}", format_warning_message()));
+fn display_warning(colors: bool) -> io::Result<bool> {
+    try!(writeln!(&mut io::stderr(), "{}", format_warning_message(colors)));
 
-    try!(write!(&mut io::stderr(), "{}", format_warning_ack_prompt()));
+    try!(write!(&mut io::stderr(), "{}", format_warning_ack_prompt(colors)));
     let mut answer = String::with_capacity(YES.len());
     try!(io::stdin().read_line(&mut answer));
 
     Ok(answer.trim().to_lowercase() == YES)
 }
 
-/// Return the formatted warning message, incl. coloring if the terminal supports it.
-fn format_warning_message() -> String {
+/// Return the formatted warning message, incl. coloring if `colors` is true.
+fn format_warning_message(colors: bool) -> String {
     const PREFIX: &'static str = "WARNING";
     const WARNING: &'static [&'static str] = &[
         "${app} is used to download & run code from remote sources.",
@@ -338,20 +712,57 @@ fn format_warning_message() -> String {
         "",
     ];
     let prefix_style =
-        if cfg!(unix) { Colour::Yellow.bold() } else { Style::default() };
+        if colors { Colour::Yellow.bold() } else { Style::default() };
     format!("{}: {}", prefix_style.paint(PREFIX),
         WARNING.join(util::LINESEP).replace("${app}", *NAME))
 }
 
 /// Return the formatted prompt for warning acknowledgment.
-fn format_warning_ack_prompt() -> String {
+fn format_warning_ack_prompt(colors: bool) -> String {
     const ACK_PROMPT: &'static str = "Do you wish to continue?";
-    if cfg!(unix) {
-        format!("{} [{}/{}]: ", Style::new().bold().paint(ACK_PROMPT),
+    format_prompt(ACK_PROMPT, colors)
+}
+
+/// Return the formatted yes/no prompt (where "no" is the default).
+fn format_prompt(prompt: &str, colors: bool) -> String {
+    if colors {
+        format!("{} [{}/{}]: ", Style::new().bold().paint(prompt),
             YES, Colour::Green.paint("N"))
     } else {
-        format!("{} [{}/{}]: ", ACK_PROMPT, YES, "N")
+        format!("{} [{}/{}]: ", prompt, YES, "N")
+    }
+}
+
+/// Show the first few lines of the gist and ask the user whether to run it (see --confirm).
+/// Returns whether the user decided to run the gist.
+fn confirm_run(gist: &Gist, colors: bool) -> io::Result<bool> {
+    const PREVIEW_LINES: usize = 20;
+
+    let mut stderr = io::stderr();
+    let header = format!("--- {} ---", gist.uri);
+    let header_style = if colors { Style::new().bold() } else { Style::default() };
+    try!(writeln!(&mut stderr, "{}", header_style.paint(header)));
+
+    let file = try!(fs::File::open(gist.binary_path()));
+    let mut lines = BufReader::new(file).lines();
+    for line in lines.by_ref().take(PREVIEW_LINES) {
+        match line {
+            Ok(line) => try!(writeln!(&mut stderr, "{}", line)),
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidData => {
+                try!(writeln!(&mut stderr, "(binary content)"));
+                break;
+            },
+            Err(e) => return Err(e),
+        }
     }
+    if lines.next().is_some() {
+        try!(writeln!(&mut stderr, "..."));
+    }
+
+    try!(write!(&mut stderr, "{}", format_prompt("Run this gist?", colors)));
+    let mut answer = String::with_capacity(YES.len());
+    try!(io::stdin().read_line(&mut answer));
+    Ok(answer.trim().to_lowercase() == YES)
 }
 
 const YES: &'static str = "y";
@@ -2,12 +2,16 @@
 
 #![allow(dead_code)]
 
-use std::io;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
 use std::string::FromUtf8Error;
 use std::sync::RwLock;
+use std::time::SystemTime;
 
 use gist::{self, Gist};
 use hosts::{FetchMode, Host};
+use util::link_binary;
 
 
 pub const INMEMORY_HOST_DEFAULT_ID: &'static str = "mem";
@@ -18,6 +22,8 @@ struct StoredGist {
     gist: Option<Gist>,
     url: Option<String>,
     content: Option<Vec<u8>>,
+    /// Names & content of the files of a multi-file gist.
+    files: Option<Vec<(String, Vec<u8>)>>,
 }
 
 impl StoredGist {
@@ -27,17 +33,18 @@ impl StoredGist {
             gist: Some(gist),
             url: Some(url),
             content: Some(content.into_bytes()),
+            files: None,
         }
     }
 
     #[inline]
     pub fn with_gist(gist: Gist) -> Self {
-        StoredGist{gist: Some(gist), url: None, content: None}
+        StoredGist{gist: Some(gist), url: None, content: None, files: None}
     }
 
     #[inline]
     pub fn with_gist_url(gist: Gist, url: String) -> Self {
-        StoredGist{gist: Some(gist), url: Some(url), content: None}
+        StoredGist{gist: Some(gist), url: Some(url), content: None, files: None}
     }
 
     #[inline]
@@ -46,12 +53,18 @@ impl StoredGist {
             gist: Some(gist),
             url: None,
             content: Some(content.into_bytes()),
+            files: None,
         }
     }
 
+    #[inline]
+    pub fn with_gist_files(gist: Gist, files: Vec<(String, Vec<u8>)>) -> Self {
+        StoredGist{gist: Some(gist), url: None, content: None, files: Some(files)}
+    }
+
     #[inline]
     pub fn with_broken_url(url: String) -> Self {
-        StoredGist{gist: None, url: Some(url), content: None}
+        StoredGist{gist: None, url: Some(url), content: None, files: None}
     }
 }
 impl From<Gist> for StoredGist {
@@ -98,6 +111,9 @@ impl StoredGist {
 /// * gist URI format:: mem:$OWNER/$NAME
 /// * HTML URL format:: memory://html/id/$ID or memory://html/uri/$OWNER/$NAME
 ///
+/// The only exception is fetching gists that have content,
+/// which stores said content in the gists' directory like a regular host would.
+///
 pub struct InMemoryHost {
     id: &'static str,
     gists: RwLock<Vec<StoredGist>>,  // lock due to Host: Sync requirement
@@ -131,9 +147,12 @@ impl Host for InMemoryHost {
         let gists = self.gists.read().unwrap();
         match gists.iter().find(|sg| sg.gist.as_ref() == Some(gist)) {
             Some(sg) => {
-                if sg.content.is_some() {
-                    // Gist has content, so we "downloaded" it.
-                    Ok(())
+                if let Some(ref files) = sg.files {
+                    // Multi-file gists are "downloaded" into a directory of their own.
+                    store_gist_files(gist, files)
+                } else if let Some(ref content) = sg.content {
+                    // Gist has content, so we "download" it.
+                    store_gist(gist, content)
                 } else {
                     // This isn't something we'd expect a regular gist host to ever signal
                     // (since none would make a distinction between "no content" and
@@ -148,6 +167,17 @@ impl Host for InMemoryHost {
         }
     }
 
+    fn download_gist_to(&self, gist: &Gist, path: &Path) -> io::Result<u64> {
+        let gists = self.gists.read().unwrap();
+        let content = try!(gists.iter().find(|sg| sg.gist.as_ref() == Some(gist))
+            .and_then(|sg| sg.content.as_ref())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound,
+                format!("Cannot find content of {:?}", gist))));
+        let mut file = try!(fs::File::create(path));
+        try!(file.write_all(content));
+        Ok(content.len() as u64)
+    }
+
     fn gist_url(&self, gist: &Gist) -> io::Result<String> {
         let gists = self.gists.read().unwrap();
         if let Some(stored_gist) = gists.iter().find(|sg| sg.gist.as_ref() == Some(gist)) {
@@ -158,6 +188,36 @@ impl Host for InMemoryHost {
         Err(io::Error::new(io::ErrorKind::NotFound, format!("Cannot find {:?}", gist)))
     }
 
+    fn gist_info(&self, gist: &Gist, data: &[gist::Datum]) -> io::Result<Option<gist::Info>> {
+        let gists = self.gists.read().unwrap();
+        match gists.iter().find(|sg| sg.gist.as_ref() == Some(gist)) {
+            Some(sg) => Ok(sg.gist.as_ref()
+                .and_then(|g| g.info.clone()).map(|i| i.select(data))),
+            None => Err(io::Error::new(io::ErrorKind::NotFound,
+                format!("Cannot find {:?}", gist))),
+        }
+    }
+
+    /// List the gists of given owner, treating them all as a single page.
+    /// Stored gists have no update time, so `since` doesn't filter them.
+    fn owner_gists<'o>(&self, owner: &'o str, max_pages: Option<usize>,
+                       _since: Option<SystemTime>) -> io::Result<Box<Iterator<Item=Gist> + 'o>> {
+        if max_pages == Some(0) {
+            return Ok(Box::new(Vec::new().into_iter()));
+        }
+        let gists = self.gists.read().unwrap();
+        let owned: Vec<_> = gists.iter()
+            .filter_map(|sg| sg.gist.clone())
+            .filter(|g| g.uri.owner == owner)
+            .collect();
+        Ok(Box::new(owned.into_iter()))
+    }
+
+    fn recognizes_url(&self, url: &str) -> bool {
+        let gists = self.gists.read().unwrap();
+        gists.iter().any(|sg| sg.url.as_ref().map(String::as_str) == Some(url))
+    }
+
     fn resolve_url(&self, url: &str) -> Option<io::Result<Gist>> {
         let gists = self.gists.read().unwrap();
         let stored_gist = try_opt!(gists.iter()
@@ -200,6 +260,32 @@ impl InMemoryHost {
         gists.push(StoredGist::with_gist_url(gist, url));
     }
 
+    /// Put a gist into the collection of in-memory gists, together with its content.
+    /// The content will be stored on disk when the gist is fetched.
+    pub fn put_gist_with_content<C: ToString>(&self, gist: Gist, content: C) {
+        let mut gists = self.gists.write().unwrap();
+        if gists.iter().find(|sg| sg.gist.as_ref() == Some(&gist)).is_some() {
+            panic!("Tried to put duplicate gist {:?}", gist);
+        }
+        gists.push(StoredGist::with_gist_content(gist, content.to_string()));
+    }
+
+    /// Put a multi-file gist into the collection of in-memory gists,
+    /// together with the names & content of its files.
+    /// The files will be stored in the gist's directory when the gist is fetched,
+    /// and the first one will become the gist's binary.
+    pub fn put_gist_with_files<N, C>(&self, gist: Gist, files: Vec<(N, C)>)
+        where N: ToString, C: ToString
+    {
+        let files = files.into_iter()
+            .map(|(n, c)| (n.to_string(), c.to_string().into_bytes())).collect();
+        let mut gists = self.gists.write().unwrap();
+        if gists.iter().find(|sg| sg.gist.as_ref() == Some(&gist)).is_some() {
+            panic!("Tried to put duplicate gist {:?}", gist);
+        }
+        gists.push(StoredGist::with_gist_files(gist, files));
+    }
+
     /// Put a URL into gist collection that doesn't correspond to any gist.
     /// The URL will cause an error when resolved.
     pub fn put_broken_url<U: ToString>(&self, url: U) {
@@ -242,3 +328,30 @@ impl InMemoryHost {
         self.gists.read().unwrap().len()
     }
 }
+
+
+/// Store the content of an in-memory gist in the gist's local directory,
+/// and link it as the gist's binary.
+fn store_gist(gist: &Gist, content: &[u8]) -> io::Result<()> {
+    let path = gist.path();
+    try!(fs::create_dir_all(path.parent().unwrap()));
+    let mut file = try!(fs::File::create(&path));
+    try!(file.write_all(content));
+
+    link_binary(&path, &gist.binary_path())
+}
+
+/// Store the files of a multi-file in-memory gist in the gist's own directory,
+/// and link the first one as the gist's binary.
+fn store_gist_files(gist: &Gist, files: &[(String, Vec<u8>)]) -> io::Result<()> {
+    let path = gist.path();
+    try!(fs::create_dir_all(&path));
+    for &(ref name, ref content) in files {
+        let mut file = try!(fs::File::create(path.join(name)));
+        try!(file.write_all(content));
+    }
+    match files.first() {
+        Some(&(ref name, _)) => link_binary(&path.join(name), &gist.binary_path()),
+        None => Ok(()),
+    }
+}
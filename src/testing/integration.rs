@@ -0,0 +1,497 @@
+//! Integration tests that run the program's commands against the in-memory host.
+
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::{self, FromStr};
+
+use exitcode;
+use serde_json::Value as Json;
+
+use args::parse_from_argv;
+use commands::{download_gist, gist_env, list_gists, list_hosts, list_owner_gists,
+               print_all_gist_files, print_binary_path, print_gist, print_remote_gist,
+               purge_gist, search_gists, show_gist_info};
+use gist::{Datum, Gist, InfoBuilder, Uri};
+use super::{INMEMORY_HOST, INMEMORY_HOST_DEFAULT_ID};
+use super::super::decode_gist;
+
+
+/// Owner of all the gists used in integration tests.
+const OWNER: &'static str = "IntegrationTest";
+
+
+#[test]
+fn print_existing_gist() {
+    const CONTENT: &'static str = "#!/bin/sh\necho 'Hello, world!'\n";
+    let uri = put_gist("print_existing_gist", CONTENT);
+
+    let opts = parse_from_argv(vec!["gisht", "print", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = print_gist(&gist, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    assert_eq!(CONTENT, str::from_utf8(&output).unwrap());
+}
+
+#[test]
+fn print_existing_gist_remotely() {
+    const CONTENT: &'static str = "#!/bin/sh\necho 'Hello, remote world!'\n";
+    let uri = put_gist("print_existing_gist_remotely", CONTENT);
+
+    let opts = parse_from_argv(vec!["gisht", "print", "--remote", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = print_remote_gist(&gist, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    assert_eq!(CONTENT, str::from_utf8(&output).unwrap());
+}
+
+#[test]
+fn print_all_files_of_existing_gist() {
+    const CONTENT: &'static str = "#!/bin/sh\necho 'Hello, all files!'\n";
+    let uri = put_gist("print_all_files_of_existing_gist", CONTENT);
+
+    let opts = parse_from_argv(vec!["gisht", "print", "--all-files", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = print_all_gist_files(&gist, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    let output = str::from_utf8(&output).unwrap();
+    assert!(output.starts_with("==> ") && output.ends_with(&format!(" <==\n{}", CONTENT)),
+        "Unexpected output of all gist files: {}", output);
+}
+
+#[test]
+fn print_all_files_of_multi_file_gist() {
+    let uri = Uri::new(INMEMORY_HOST_DEFAULT_ID, OWNER, "print_all_files_of_multi_file_gist")
+        .unwrap();
+    INMEMORY_HOST.put_gist_with_files(Gist::from_uri(uri.clone()), vec![
+        ("main.sh", "#!/bin/sh\n. ./lib.sh\n"),
+        ("lib.sh", "greet() { echo hi; }\n"),
+        ("README", "Multi-file gist\n"),
+    ]);
+    let uri = format!("{}", uri);
+
+    let opts = parse_from_argv(vec!["gisht", "print", "--all-files", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = print_all_gist_files(&gist, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    assert_eq!("==> README <==\nMulti-file gist\n\n\
+                ==> lib.sh <==\ngreet() { echo hi; }\n\n\
+                ==> main.sh <==\n#!/bin/sh\n. ./lib.sh\n",
+               str::from_utf8(&output).unwrap());
+}
+
+#[test]
+fn print_missing_gist() {
+    let uri = format!("{}:{}/print_missing_gist", INMEMORY_HOST_DEFAULT_ID, OWNER);
+
+    let opts = parse_from_argv(vec!["gisht", "print", &*uri]).unwrap();
+    assert_eq!(Err(exitcode::IOERR), decode_gist(&opts).map(|_| ()));
+}
+
+#[test]
+fn print_missing_gist_locally() {
+    let uri = format!("{}:{}/print_missing_gist_locally", INMEMORY_HOST_DEFAULT_ID, OWNER);
+
+    let opts = parse_from_argv(vec!["gisht", "--cached", "print", &*uri]).unwrap();
+    assert_eq!(Err(exitcode::NOINPUT), decode_gist(&opts).map(|_| ()));
+}
+
+#[test]
+fn download_existing_gist() {
+    const CONTENT: &'static str = "name,value\nfoo,42\n";
+    let uri = put_gist("download_existing_gist", CONTENT);
+    let path = env::temp_dir().join("gisht-download_existing_gist.csv");
+    let _ = fs::remove_file(&path);
+
+    let opts = parse_from_argv(vec![
+        "gisht", "download", "-o", path.to_str().unwrap(), &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    assert_eq!(exitcode::OK, download_gist(&gist, &path, false));
+
+    let mut saved = String::new();
+    fs::File::open(&path).unwrap().read_to_string(&mut saved).unwrap();
+    assert_eq!(CONTENT, saved);
+    assert!(!gist.is_local(), "Downloaded gist unexpectedly linked as a binary");
+    // Existing file isn't overwritten without being forced to.
+    assert_eq!(exitcode::USAGE, download_gist(&gist, &path, false));
+    assert_eq!(exitcode::OK, download_gist(&gist, &path, true));
+}
+
+#[test]
+fn run_env_points_to_gist_dir() {
+    let uri = put_gist("run_env_points_to_gist_dir", "#!/bin/sh\necho \"$GISHT_GIST_DIR\"\n");
+
+    let opts = parse_from_argv(vec!["gisht", "run", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let env = gist_env(&gist);
+    let dir = env.iter().find(|&&(ref k, _)| k == "GISHT_GIST_DIR")
+        .map(|&(_, ref v)| PathBuf::from(v)).unwrap();
+
+    assert!(dir.is_dir(), "GISHT_GIST_DIR is not a directory: {}", dir.display());
+    assert!(dir.join("run_env_points_to_gist_dir").is_file(),
+        "GISHT_GIST_DIR doesn't contain the gist file: {}", dir.display());
+}
+
+#[test]
+fn which_existing_gist() {
+    let uri = put_gist("which_existing_gist", "");
+
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = print_binary_path(&gist, false, false, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    let expected = format!("{}", gist.binary_path().display());
+    assert_eq!(expected, str::from_utf8(&output).unwrap().trim());
+}
+
+#[test]
+fn which_existing_gist_as_json() {
+    let uri = put_gist("which_existing_gist_as_json", "");
+
+    let opts = parse_from_argv(vec!["gisht", "which", "--json", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, print_binary_path(&gist, false, true, &mut output));
+
+    let json = Json::from_str(str::from_utf8(&output).unwrap()).unwrap();
+    assert_eq!(gist.binary_path().to_str(), json.find("binary_path").and_then(Json::as_str));
+    assert_eq!(gist.path().to_str(), json.find("dir").and_then(Json::as_str));
+    assert_eq!(Some(true), json.find("exists").and_then(Json::as_bool));
+    assert_eq!(Some(&Json::Null), json.find("id"));
+}
+
+#[test]
+fn info_existing_gist() {
+    let uri = put_gist_with_info("info_existing_gist", "Some gist");
+
+    let opts = parse_from_argv(vec!["gisht", "info", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = show_gist_info(&gist, opts.info_fields.as_ref().unwrap(),
+                                   &opts.info_redact, false, false, false, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    let output = str::from_utf8(&output).unwrap();
+    assert!(output.contains(OWNER), "Gist info lacks the owner: {}", output);
+    assert!(output.contains("Some gist"), "Gist info lacks the description: {}", output);
+}
+
+#[test]
+fn info_existing_gist_selected_fields() {
+    let uri = put_gist_with_info("info_existing_gist_selected_fields", "Another gist");
+
+    let opts = parse_from_argv(vec![
+        "gisht", "info", "--fields", "description", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = show_gist_info(&gist, opts.info_fields.as_ref().unwrap(),
+                                   &opts.info_redact, false, false, false, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    let output = str::from_utf8(&output).unwrap();
+    assert!(output.contains("Another gist"), "Gist info lacks the description: {}", output);
+    assert!(!output.contains(OWNER), "Gist info unexpectedly has the owner: {}", output);
+}
+
+#[test]
+fn info_existing_gist_redacted_owner() {
+    let uri = put_gist_with_info("info_existing_gist_redacted_owner", "Shared gist");
+
+    let opts = parse_from_argv(vec!["gisht", "info", "--redact", "owner", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = show_gist_info(&gist, opts.info_fields.as_ref().unwrap(),
+                                   &opts.info_redact, false, false, false, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    let output = str::from_utf8(&output).unwrap();
+    assert!(output.contains("Shared gist"), "Gist info lacks the description: {}", output);
+    assert!(!output.contains(OWNER), "Gist info unexpectedly has the owner: {}", output);
+}
+
+#[test]
+fn info_cached_gist_offline() {
+    let uri = put_gist_with_info("info_cached_gist_offline", "Offline gist");
+    let opts = parse_from_argv(vec!["gisht", "info", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();  // fetch the gist
+
+    let opts = parse_from_argv(vec!["gisht", "--cached", "info", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    let exit_code = show_gist_info(&gist, opts.info_fields.as_ref().unwrap(),
+                                   &opts.info_redact, true, false, false, &mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    let output = str::from_utf8(&output).unwrap();
+    assert!(output.contains("Last update"), "Gist info lacks the update time: {}", output);
+    assert!(!output.contains(OWNER), "Gist info came from the host: {}", output);
+}
+
+#[test]
+fn info_missing_gist() {
+    let uri = format!("{}:{}/info_missing_gist", INMEMORY_HOST_DEFAULT_ID, OWNER);
+
+    let opts = parse_from_argv(vec!["gisht", "info", &*uri]).unwrap();
+    assert_eq!(Err(exitcode::IOERR), decode_gist(&opts).map(|_| ()));
+}
+#[test]
+fn purge_existing_gist() {
+    let uri = put_gist("purge_existing_gist", "echo 'Goodbye!'");
+
+    // Fetch the gist first, as purging doesn't do that.
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    assert!(gist.is_local());
+
+    let opts = parse_from_argv(vec!["gisht", "purge", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let exit_code = purge_gist(&gist, opts.purge_remote, opts.purge_dry_run, &mut Vec::new());
+
+    assert_eq!(exitcode::OK, exit_code);
+    assert!(!gist.is_local(), "Gist binary still exists after purge");
+    assert!(!gist.path().exists(), "Gist source still exists after purge");
+}
+
+#[test]
+fn purge_gist_with_stored_info() {
+    // Multi-file gists (like the glot.io ones) keep their info next to their directory.
+    let uri = Uri::new(INMEMORY_HOST_DEFAULT_ID, OWNER, "purge_gist_with_stored_info").unwrap();
+    INMEMORY_HOST.put_gist_with_files(Gist::from_uri(uri.clone()), vec![
+        ("main.sh", "echo 'Goodbye!'\n"),
+    ]);
+    let uri = format!("{}", uri);
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let info = InfoBuilder::new().with(Datum::Language, "Shell").build();
+    gist.clone().with_info(info).store_info().unwrap();
+    assert!(gist.meta_path().exists());
+
+    let opts = parse_from_argv(vec!["gisht", "purge", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let exit_code = purge_gist(&gist, opts.purge_remote, opts.purge_dry_run, &mut Vec::new());
+
+    assert_eq!(exitcode::OK, exit_code);
+    assert!(!gist.meta_path().exists(), "Stored gist info still exists after purge");
+}
+
+#[test]
+fn purge_gist_dry_run() {
+    let uri = put_gist("purge_gist_dry_run", "echo 'Not yet!'");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    let opts = parse_from_argv(vec!["gisht", "purge", "--dry-run", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, purge_gist(&gist, false, true, &mut output));
+
+    let output = str::from_utf8(&output).unwrap();
+    let binary = format!("{}", gist.binary_path().display());
+    assert!(output.lines().any(|l| l == binary), "Gist binary not listed: {}", output);
+    assert!(gist.is_local(), "Gist removed despite --dry-run");
+}
+
+#[test]
+fn purge_remote_gist_unsupported() {
+    let uri = put_gist("purge_remote_gist_unsupported", "echo 'Not here!'");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    // The in-memory host can't delete gists, so this should fail before asking to confirm
+    // (which would otherwise block on reading the answer from stdin).
+    let opts = parse_from_argv(vec!["gisht", "purge", "--remote", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let exit_code = purge_gist(&gist, opts.purge_remote, opts.purge_dry_run, &mut Vec::new());
+    assert_eq!(exitcode::UNAVAILABLE, exit_code);
+    assert!(gist.is_local(), "Local copy removed despite failed remote deletion");
+}
+
+#[test]
+fn purge_missing_gist() {
+    let uri = format!("{}:{}/purge_missing_gist", INMEMORY_HOST_DEFAULT_ID, OWNER);
+
+    let opts = parse_from_argv(vec!["gisht", "purge", &*uri]).unwrap();
+    let gist = decode_gist(&opts).unwrap();
+    let exit_code = purge_gist(&gist, opts.purge_remote, opts.purge_dry_run, &mut Vec::new());
+    assert_eq!(exitcode::NOINPUT, exit_code);
+}
+#[test]
+fn list_local_gists() {
+    let uri = put_gist("list_local_gists", "");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, list_gists(false, false, true, &mut output));
+    let output = str::from_utf8(&output).unwrap();
+    assert!(output.lines().any(|l| l == uri), "Local gist not listed: {}", output);
+}
+
+#[test]
+fn list_local_gists_print0() {
+    let uri = put_gist("list_local_gists_print0", "");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, list_gists(false, true, true, &mut output));
+    let output = str::from_utf8(&output).unwrap();
+    assert!(!output.contains('\n'), "Newline in NUL-separated output: {:?}", output);
+    assert!(output.split('\0').any(|u| u == uri), "Local gist not listed: {:?}", output);
+}
+
+#[test]
+fn list_local_gists_without_host_prefix() {
+    let uri = put_gist("list_local_gists_without_host_prefix", "");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, list_gists(false, false, false, &mut output));
+    let output = str::from_utf8(&output).unwrap();
+    let expected = format!("{}/list_local_gists_without_host_prefix", OWNER);
+    assert!(output.lines().any(|l| l == expected), "Local gist not listed: {}", output);
+}
+
+#[test]
+fn list_local_gists_as_json() {
+    let uri = put_gist("list_local_gists_as_json", "");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, list_gists(true, false, true, &mut output));
+    let json = Json::from_str(str::from_utf8(&output).unwrap()).unwrap();
+    let gist = json.as_array().unwrap().iter()
+        .find(|g| g.find("name").and_then(Json::as_str) == Some("list_local_gists_as_json"))
+        .expect("Local gist not listed in JSON output");
+    assert_eq!(Some(INMEMORY_HOST_DEFAULT_ID), gist.find("host").and_then(Json::as_str));
+    assert_eq!(Some(OWNER), gist.find("owner").and_then(Json::as_str));
+    assert!(gist.find("id").unwrap().is_null());
+}
+
+#[test]
+fn search_local_gists() {
+    let uri = put_gist("search_local_gists", "first line\nneedle_6f9c in a haystack\n");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, search_gists("needle_6f9c", false, false, true, 1, &mut output));
+    let output = str::from_utf8(&output).unwrap();
+    assert_eq!(format!("{}:2:needle_6f9c in a haystack\n", uri), output);
+}
+
+#[test]
+fn search_local_gists_name_only() {
+    let uri = put_gist("search_local_gists_name_only", "needle_a41e\nneedle_a41e again\n");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, search_gists("needle_a41e", true, false, true, 1, &mut output));
+    let output = str::from_utf8(&output).unwrap();
+    assert_eq!(format!("{}\n", uri), output);
+}
+
+#[test]
+fn search_local_gists_print0() {
+    let uri = put_gist("search_local_gists_print0", "needle_5e2a\n");
+    let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+    decode_gist(&opts).unwrap();
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, search_gists("needle_5e2a", true, true, true, 1, &mut output));
+    let output = str::from_utf8(&output).unwrap();
+    assert_eq!(format!("{}\0", uri), output);
+}
+
+#[test]
+fn search_local_gists_in_parallel() {
+    for i in 0..8 {
+        let uri = put_gist(&format!("search_local_gists_in_parallel_{}", i), "needle_d07b\n");
+        let opts = parse_from_argv(vec!["gisht", "which", &*uri]).unwrap();
+        decode_gist(&opts).unwrap();
+    }
+
+    let mut sequential_output = Vec::new();
+    assert_eq!(exitcode::OK,
+               search_gists("needle_d07b", true, false, true, 1, &mut sequential_output));
+    let mut parallel_output = Vec::new();
+    assert_eq!(exitcode::OK,
+               search_gists("needle_d07b", true, false, true, 4, &mut parallel_output));
+    assert_eq!(8, str::from_utf8(&parallel_output).unwrap().lines().count());
+    assert_eq!(sequential_output, parallel_output);
+}
+
+#[test]
+fn list_owner_gists_remotely() {
+    let uri = put_gist_with_info("list_owner_gists_remotely", "Owned gist");
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, list_owner_gists(&**INMEMORY_HOST, OWNER, None, None, true,
+                                              &mut output));
+    let output = str::from_utf8(&output).unwrap();
+    let line = output.lines().find(|l| l.starts_with(&*uri))
+        .expect("Owner's gist not listed");
+    assert!(line.contains("Owned gist"), "Listed gist lacks the description: {}", line);
+}
+
+#[test]
+fn list_owner_gists_with_no_pages() {
+    put_gist_with_info("list_owner_gists_with_no_pages", "Unlisted gist");
+
+    let mut output = Vec::new();
+    assert_eq!(exitcode::OK, list_owner_gists(&**INMEMORY_HOST, OWNER, Some(0), None, true,
+                                              &mut output));
+    assert!(output.is_empty(), "Gists listed despite the page limit: {:?}",
+        String::from_utf8_lossy(&output));
+}
+
+#[test]
+fn hosts() {
+    let mut output = Vec::new();
+    let exit_code = list_hosts(&mut output);
+
+    assert_eq!(exitcode::OK, exit_code);
+    let output = str::from_utf8(&output).unwrap();
+    assert!(output.lines().any(|l| l.trim_left().starts_with(INMEMORY_HOST_DEFAULT_ID)),
+        "In-memory host not listed among gist hosts: {}", output);
+    assert!(output.lines().any(|l| l == "Other:"),
+        "In-memory host's category not listed: {}", output);
+}
+
+
+// Utility functions
+
+/// Put a gist with given name & content into the in-memory host.
+/// Returns the gist URI as string, suitable for passing as command line argument.
+fn put_gist(name: &str, content: &str) -> String {
+    let uri = Uri::new(INMEMORY_HOST_DEFAULT_ID, OWNER, name).unwrap();
+    INMEMORY_HOST.put_gist_with_content(Gist::from_uri(uri.clone()), content);
+    format!("{}", uri)
+}
+
+/// Put a gist with some info (incl. given description) into the in-memory host.
+/// Returns the gist URI as string, suitable for passing as command line argument.
+fn put_gist_with_info(name: &str, description: &str) -> String {
+    let uri = Uri::new(INMEMORY_HOST_DEFAULT_ID, OWNER, name).unwrap();
+    let info = InfoBuilder::new()
+        .with(Datum::Owner, OWNER)
+        .with(Datum::Description, description)
+        .build();
+    let gist = Gist::from_uri(uri.clone()).with_info(info);
+    INMEMORY_HOST.put_gist_with_content(gist, "");
+    format!("{}", uri)
+}
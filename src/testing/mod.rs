@@ -2,5 +2,20 @@
 
 mod inmemory_host;
 
+mod integration;
+
+
+use std::sync::Arc;
 
 pub use self::inmemory_host::{INMEMORY_HOST_DEFAULT_ID, InMemoryHost};
+
+
+lazy_static! {
+    /// The in-memory gist host that's registered in hosts::HOSTS
+    /// under INMEMORY_HOST_DEFAULT_ID.
+    ///
+    /// Tests can put gists in it to have them available to the program.
+    /// Since tests run in parallel, they should use unique gist URIs
+    /// rather than calling InMemoryHost::reset on this instance.
+    pub static ref INMEMORY_HOST: Arc<InMemoryHost> = Arc::new(InMemoryHost::new());
+}
@@ -1,15 +1,29 @@
 //! Utility module.
 
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashSet;
+use std::env;
 use std::fs;
-use std::io::{self, Read};
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT};
+use std::time::Duration;
 
-use hyper::client::{Client, Response};
-use hyper::header::ContentLength;
-use hyper::net::HttpsConnector;
+use hyper;
+use hyper::client::{Client, RedirectPolicy, RequestBuilder, Response};
+use hyper::header::{Authorization, Basic, ContentLength, Location, UserAgent};
+use hyper::net::{HttpStream, HttpsConnector, NetworkConnector};
+use hyper::status::StatusCode;
 use hyper_native_tls::NativeTlsClient;
+use isatty;
 use serde_json::Value as Json;
+use url::Url;
+
+use ::{BIN_DIR, BINARY_SOURCES_DIR, USER_AGENT};
+use config;
 
 
 /// Like try!(), but returns Some(Err(err)) in case of error.
@@ -52,7 +66,135 @@ pub fn symlink_file<S, D>(src: S, dst: D) -> io::Result<()>
 }
 
 
-/// Mark a given file path as executable for all users..
+/// Whether gist binaries should be copies of gist files rather than symlinks,
+/// as requested by the --no-symlink flag.
+static NO_SYMLINK: AtomicBool = ATOMIC_BOOL_INIT;
+
+/// Make gist binaries copies of gist files rather than symlinks to them
+/// (see `link_binary`).
+pub fn disable_symlinks() {
+    NO_SYMLINK.store(true, Ordering::SeqCst);
+}
+
+/// Whether gist binaries are copies of gist files, as requested by either
+/// the --no-symlink flag or the configuration (e.g. $GISHT_NO_SYMLINK).
+fn symlinks_disabled() -> bool {
+    NO_SYMLINK.load(Ordering::SeqCst) || config::current().no_symlink()
+}
+
+/// Make the gist's `binary` point to its `executable` file.
+///
+/// Normally this is a symlink, created unless the binary exists already.
+/// If symlinks are disabled, the binary is a copy of the executable instead,
+/// which is made anew every time so that it reflects the updates of the gist.
+pub fn link_binary(executable: &Path, binary: &Path) -> io::Result<()> {
+    try!(fs::create_dir_all(binary.parent().unwrap()));
+    if !symlinks_disabled() {
+        // A copy made while symlinks were disabled would otherwise never be updated.
+        if binary.exists() && !try!(is_symlink(binary)) {
+            try!(unlink_binary(binary));
+            trace!("Removed the copy of gist executable in {}", binary.display());
+        }
+        if !binary.exists() {
+            match symlink_file(executable, binary) {
+                Ok(()) => trace!("Created symlink to gist executable: {}", binary.display()),
+                // Another process (fetching the same gist) may have created it in the meantime.
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists
+                    && symlink_points_to(binary, executable) => {
+                    debug!("Symlink to gist executable created concurrently: {}",
+                        binary.display());
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        return Ok(());
+    }
+
+    // A symlink would be overwritten through, i.e. we'd copy the file onto itself.
+    if try!(is_symlink(binary)) {
+        try!(fs::remove_file(binary));
+    }
+    try!(fs::copy(executable, binary));
+    if let Some(record) = binary_source_record(binary) {
+        try!(fs::create_dir_all(record.parent().unwrap()));
+        let mut file = try!(fs::File::create(&record));
+        try!(file.write_all(executable.to_string_lossy().as_bytes()));
+    }
+    trace!("Copied gist executable to {}", binary.display());
+    Ok(())
+}
+
+/// Whether `link` is a symlink pointing to given `target`.
+fn symlink_points_to(link: &Path, target: &Path) -> bool {
+    match fs::read_link(link) {
+        Ok(ref path) if path == target => true,
+        Ok(path) => match (path.canonicalize(), target.canonicalize()) {
+            (Ok(p), Ok(t)) => p == t,
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// Make the gist's `binary` reflect its (updated) executable file,
+/// provided it's a copy of it rather than a symlink (see `link_binary`).
+pub fn refresh_binary(binary: &Path) -> io::Result<()> {
+    if !binary.exists() || try!(is_symlink(binary)) {
+        return Ok(());
+    }
+    let source = try!(binary_source(binary));
+    if source != try!(binary.canonicalize()) {
+        try!(fs::copy(&source, binary));
+        trace!("Refreshed the copy of gist executable in {}", binary.display());
+    }
+    Ok(())
+}
+
+/// Return the (canonical) path to the gist file that given gist binary
+/// is either a symlink to, or a copy of (see `link_binary`).
+///
+/// If it's unknown what file the binary is a copy of, the binary itself is returned.
+pub fn binary_source(binary: &Path) -> io::Result<PathBuf> {
+    if !try!(is_symlink(binary)) {
+        if let Some(record) = binary_source_record(binary) {
+            let mut source = String::new();
+            match fs::File::open(&record).and_then(|mut f| f.read_to_string(&mut source)) {
+                Ok(_) => return Path::new(source.trim_right()).canonicalize(),
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {},
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    binary.canonicalize()
+}
+
+/// Remove the gist's `binary`, along with the record of its source file (if any).
+pub fn unlink_binary(binary: &Path) -> io::Result<()> {
+    try!(fs::remove_file(binary));
+    if let Some(record) = binary_source_record(binary) {
+        if record.exists() {
+            try!(fs::remove_file(&record));
+        }
+    }
+    Ok(())
+}
+
+/// Path to the file that records what gist file is given gist binary a copy of.
+fn binary_source_record(binary: &Path) -> Option<PathBuf> {
+    binary.strip_prefix(&*BIN_DIR).ok().map(|rel| BINARY_SOURCES_DIR.join(rel))
+}
+
+fn is_symlink(path: &Path) -> io::Result<bool> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => Ok(metadata.file_type().is_symlink()),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+
+/// Mark a given file path as executable, giving it the mode from `exec_mode` config
+/// (or $GISHT_EXEC_MODE), which by default makes it executable for all users.
 pub fn mark_executable<P: AsRef<Path>>(path: P) -> io::Result<()> {
     let metadata = try!(fs::metadata(path.as_ref()));
     if !metadata.is_file() {
@@ -65,7 +207,7 @@ pub fn mark_executable<P: AsRef<Path>>(path: P) -> io::Result<()> {
     if cfg!(unix) {
           use std::os::unix::fs::PermissionsExt;
           let mut perms = metadata.permissions();
-          perms.set_mode(0o755);
+          perms.set_mode(config::current().exec_mode());
           return fs::set_permissions(path, perms);
     }
 
@@ -73,20 +215,759 @@ pub fn mark_executable<P: AsRef<Path>>(path: P) -> io::Result<()> {
 }
 
 
+/// List all files under given directory, descending into its subdirectories.
+///
+/// Symlinks are followed, but directories that have already been visited
+/// (like the targets of self-referential symlinks) are skipped.
+/// Subdirectories are only descended into until `max_depth` is reached,
+/// where the entries of `root` itself are at depth 1.
+///
+/// Anything that isn't a directory (including broken symlinks) counts as a file.
+pub fn walk_files<P: AsRef<Path>>(root: P, max_depth: usize) -> io::Result<Vec<PathBuf>> {
+    let root = root.as_ref();
+    let mut visited = HashSet::new();
+    visited.insert(try!(root.canonicalize()));
+
+    let mut files = Vec::new();
+    try!(walk_dir(root, 1, max_depth, &mut visited, &mut files));
+    Ok(files)
+}
+
+fn walk_dir(dir: &Path, depth: usize, max_depth: usize,
+            visited: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in try!(fs::read_dir(dir)) {
+        let path = try!(entry).path();
+        if !path.is_dir() {
+            files.push(path);
+            continue;
+        }
+        if depth >= max_depth {
+            trace!("Not descending into {} beyond depth {}", path.display(), max_depth);
+            continue;
+        }
+        if !visited.insert(try!(path.canonicalize())) {
+            warn!("Skipping {} which points to an already visited directory", path.display());
+            continue;
+        }
+        try!(walk_dir(&path, depth + 1, max_depth, visited, files));
+    }
+    Ok(())
+}
+
+
+/// Return the User-Agent header to use for outgoing HTTP requests.
+///
+/// This is the program's name & version, unless configured otherwise (e.g. by $GISHT_USER_AGENT).
+/// Hosts may append a `suffix` to it, e.g. if they need a contact URL.
+pub fn user_agent(suffix: Option<&str>) -> UserAgent {
+    UserAgent(build_user_agent(&*USER_AGENT, suffix))
+}
+
+fn build_user_agent(base: &str, suffix: Option<&str>) -> String {
+    match suffix {
+        Some(suffix) => format!("{} {}", base, suffix),
+        None => base.to_owned(),
+    }
+}
+
+
+/// Find an executable with given name in one of the directories on $PATH.
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let paths = try_opt!(env::var_os("PATH"));
+    env::split_paths(&paths)
+        .map(|dir| dir.join(binary))
+        .find(|path| path.is_file())
+}
+
+
+/// Compute the edit (Levenshtein) distance between two strings,
+/// i.e. the number of characters that need to be inserted, deleted, or substituted
+/// to turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Distances between the prefixes of `a` processed so far and every prefix of `b`.
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = cmp::min(substitution, cmp::min(row[j], row[j + 1]) + 1);
+        }
+    }
+    row[b.len()]
+}
+
+
+/// Environment variable that makes standard streams be treated as terminals.
+const ASSUME_TTY_ENV_VAR: &'static str = "GISHT_ASSUME_TTY";
+/// Environment variable that makes standard streams be treated as not being terminals.
+/// This takes precedence over $GISHT_ASSUME_TTY.
+const NO_TTY_ENV_VAR: &'static str = "GISHT_NO_TTY";
+
+/// Whether standard output is a terminal,
+/// unless overridden with $GISHT_ASSUME_TTY or $GISHT_NO_TTY.
+pub fn stdout_isatty() -> bool {
+    tty_override().unwrap_or_else(isatty::stdout_isatty)
+}
+
+/// Whether standard error output is a terminal,
+/// unless overridden with $GISHT_ASSUME_TTY or $GISHT_NO_TTY.
+///
+/// This also decides whether the user can be asked questions interactively.
+pub fn stderr_isatty() -> bool {
+    tty_override().unwrap_or_else(isatty::stderr_isatty)
+}
+
+/// Whether the terminal detection is overridden through the environment, and how.
+fn tty_override() -> Option<bool> {
+    let is_set = |var| env::var_os(var).map(|v| !v.is_empty()).unwrap_or(false);
+    resolve_tty_override(is_set(ASSUME_TTY_ENV_VAR), is_set(NO_TTY_ENV_VAR))
+}
+
+fn resolve_tty_override(assume_tty: bool, no_tty: bool) -> Option<bool> {
+    if no_tty {
+        Some(false)
+    } else if assume_tty {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+
+/// Return the number of CPUs currently available to the process (at least 1).
+#[cfg(unix)]
+pub fn cpu_count() -> usize {
+    use libc;
+    let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    if count > 0 { count as usize } else { 1 }
+}
+
+/// Return the number of CPUs currently available to the process (at least 1).
+#[cfg(not(unix))]
+pub fn cpu_count() -> usize {
+    env::var("NUMBER_OF_PROCESSORS").ok()
+        .and_then(|n| n.parse().ok())
+        .map(|n: usize| n.max(1))
+        .unwrap_or(1)
+}
+
+
+/// Timeouts (in seconds) for HTTP connections, as given by the --connect-timeout
+/// and --read-timeout flags. Zero means the flag wasn't given.
+static CONNECT_TIMEOUT_SECS: AtomicUsize = ATOMIC_USIZE_INIT;
+static READ_TIMEOUT_SECS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Set the timeouts for establishing HTTP connections and reading responses,
+/// overriding those from the configuration (e.g. $GISHT_TIMEOUT).
+pub fn set_http_timeouts(connect: Option<Duration>, read: Option<Duration>) {
+    let secs = |d: Option<Duration>| d.map(|d| d.as_secs() as usize).unwrap_or(0);
+    CONNECT_TIMEOUT_SECS.store(secs(connect), Ordering::SeqCst);
+    READ_TIMEOUT_SECS.store(secs(read), Ordering::SeqCst);
+}
+
+/// Timeout for establishing HTTP connections, if any.
+fn connect_timeout() -> Option<Duration> {
+    match CONNECT_TIMEOUT_SECS.load(Ordering::SeqCst) {
+        0 => config::current().connect_timeout(),
+        secs => Some(Duration::from_secs(secs as u64)),
+    }
+}
+
+/// Timeout for reading HTTP responses, if any.
+fn read_timeout() -> Option<Duration> {
+    match READ_TIMEOUT_SECS.load(Ordering::SeqCst) {
+        0 => config::current().read_timeout(),
+        secs => Some(Duration::from_secs(secs as u64)),
+    }
+}
+
 /// Create a TLS-capable HTTP Hyper client.
+///
+/// The client handles both http:// and https:// URLs, so it should be used for all requests.
+/// It observes the connect & read timeouts,
+/// whether given by the command line flags or the configuration.
 pub fn http_client() -> Client {
     let ssl = NativeTlsClient::new().unwrap();
-    let connector = HttpsConnector::new(ssl);
-    Client::with_connector(connector)
+    let connector = HttpsConnector::with_connector(
+        ssl, TimeoutConnector{timeout: connect_timeout()});
+    let mut client = Client::with_connector(connector);
+    client.set_read_timeout(read_timeout());
+    // Redirects are followed by send_following_redirects(), which knows where they came from.
+    client.set_redirect_policy(RedirectPolicy::FollowNone);
+    client
+}
+
+/// Maximum number of redirects that send_following_redirects() will follow.
+const MAX_REDIRECTS: usize = 10;
+
+/// Send a request to given URL, following the redirects it may result in.
+///
+/// The request is built by calling `request` with the URL,
+/// both for the original one and for every redirect target.
+/// If a redirect isn't allowed (see `is_redirect_allowed`), or there are too many of them,
+/// the redirect response itself is returned.
+pub fn send_following_redirects<'c, F>(url: &str, mut request: F) -> hyper::Result<Response>
+    where F: FnMut(&str) -> RequestBuilder<'c>
+{
+    let origin = try!(Url::parse(url));
+    let mut url = origin.clone();
+    let mut redirects = 0;
+    loop {
+        let resp = try!(request(url.as_str()).send());
+        if !resp.status.is_redirection() {
+            return Ok(resp);
+        }
+        let target = resp.headers.get::<Location>().map(|l| url.join(&l.0));
+        let target = match target {
+            Some(Ok(target)) => target,
+            _ => {
+                debug!("Redirect ({}) from {} has no valid Location: header", resp.status, url);
+                return Ok(resp);
+            },
+        };
+        if redirects == MAX_REDIRECTS {
+            warn!("Not following redirect to {} after {} redirects", target, redirects);
+            return Ok(resp);
+        }
+        if !is_redirect_allowed(&origin, &target) {
+            return Ok(resp);
+        }
+        debug!("Following redirect ({}) from {} to {}", resp.status, url, target);
+        url = target;
+        redirects += 1;
+    }
+}
+
+/// Whether a request to `origin` URL should follow a redirect to `target` URL.
+///
+/// To make sure an HTTPS request is never silently downgraded, redirects from it
+/// to plain HTTP URLs are only followed if `allow_http_redirects` is enabled
+/// in the configuration. Redirects of plain HTTP requests are always followed.
+fn is_redirect_allowed(origin: &Url, target: &Url) -> bool {
+    let is_downgrade = origin.scheme() == "https" && target.scheme() == "http";
+    if !is_downgrade || config::current().allow_http_redirects() {
+        return true;
+    }
+    warn!("Not following redirect from {} to insecure URL {} \
+           (enable allow_http_redirects in the configuration to allow it)", origin, target);
+    false
+}
+
+/// Connector of HTTP streams that gives up connecting after a timeout.
+#[derive(Debug)]
+struct TimeoutConnector {
+    timeout: Option<Duration>,
+}
+
+impl NetworkConnector for TimeoutConnector {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, _: &str) -> hyper::Result<HttpStream> {
+        let timeout = match self.timeout {
+            Some(t) => t,
+            None => return Ok(HttpStream(try!(TcpStream::connect((host, port))))),
+        };
+
+        // Try all the addresses the host resolves to, like TcpStream::connect does.
+        let mut last_error = None;
+        for addr in try!((host, port).to_socket_addrs()) {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Ok(HttpStream(stream)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(match last_error {
+            Some(ref e) if e.kind() == io::ErrorKind::TimedOut => io::Error::new(
+                io::ErrorKind::TimedOut, format!("connecting to {}:{} timed out after {}s",
+                    host, port, timeout.as_secs())),
+            Some(e) => e,
+            None => io::Error::new(io::ErrorKind::InvalidInput,
+                format!("could not resolve {} to any address", host)),
+        }.into())
+    }
+}
+
+/// Convert an error from sending an HTTP request into an io::Error,
+/// telling apart the timeouts (of either connecting, or reading the response).
+pub fn http_error(err: hyper::Error) -> io::Error {
+    match err {
+        // Connection timeouts already come from TimeoutConnector with a message.
+        hyper::Error::Io(e) => if e.kind() == io::ErrorKind::TimedOut && e.get_ref().is_some() {
+            e
+        } else if is_os_timeout(&e) {
+            let secs = read_timeout().map(|t| t.as_secs()).unwrap_or(0);
+            io::Error::new(io::ErrorKind::TimedOut,
+                format!("reading the response timed out after {}s", secs))
+        } else {
+            io::Error::new(io::ErrorKind::Other, e)
+        },
+        e => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
+
+/// Whether the OS error signifies that a socket operation has timed out.
+/// (On Unix, reads past their timeout fail with EAGAIN rather than ETIMEDOUT).
+fn is_os_timeout(err: &io::Error) -> bool {
+    err.get_ref().is_none() &&
+        (err.kind() == io::ErrorKind::TimedOut || err.kind() == io::ErrorKind::WouldBlock)
+}
+
+/// Start a GET request to given URL.
+///
+/// If the user's ~/.netrc has credentials for the URL's host,
+/// they are sent along with the request using HTTP basic auth
+/// (but only over https://, as basic auth sends the password in plain text).
+pub fn http_get<'c>(http: &'c Client, url: &str) -> RequestBuilder<'c> {
+    let request = http.get(url);
+    match netrc_auth(url) {
+        Some(auth) => {
+            trace!("Using .netrc credentials of user `{}` for {}", auth.username, url);
+            request.header(Authorization(auth))
+        },
+        None => request,
+    }
+}
+
+lazy_static! {
+    /// Content of the user's ~/.netrc file, if it exists.
+    static ref NETRC: Option<String> = {
+        let path = try_opt!(env::home_dir()).join(".netrc");
+        let mut content = String::new();
+        match fs::File::open(&path).and_then(|mut f| f.read_to_string(&mut content)) {
+            Ok(_) => Some(content),
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    warn!("Couldn't read {}: {}", path.display(), err);
+                }
+                None
+            },
+        }
+    };
+}
+
+/// Find the HTTP basic auth credentials for given URL in the user's ~/.netrc.
+fn netrc_auth(url: &str) -> Option<Basic> {
+    let netrc = try_opt!(NETRC.as_ref());
+    let url = try_opt!(Url::parse(url).ok());
+    if url.scheme() != "https" {
+        trace!("Not using .netrc credentials for insecure URL {}", url);
+        return None;
+    }
+    let host = try_opt!(url.host_str());
+    netrc_credentials(netrc, host)
+}
+
+/// Find the credentials for given host in the content of a .netrc file.
+///
+/// Only the entries for the exact `machine` are considered.
+/// The `default` entry is ignored, since its credentials are rarely meant for pastebins.
+fn netrc_credentials(netrc: &str, host: &str) -> Option<Basic> {
+    // Entries are pairs of the machine name (None for `default`) and its credentials.
+    let mut entries: Vec<(Option<&str>, Basic)> = vec![];
+
+    let mut tokens = netrc.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => {
+                let machine = tokens.next().unwrap_or("");
+                entries.push((Some(machine), Basic{username: String::new(), password: None}));
+            },
+            "default" => {
+                entries.push((None, Basic{username: String::new(), password: None}));
+            },
+            "login" | "password" | "account" => {
+                let value = tokens.next().map(String::from);
+                if let Some(&mut (_, ref mut creds)) = entries.last_mut() {
+                    match token {
+                        "login" => creds.username = value.unwrap_or_default(),
+                        "password" => creds.password = value,
+                        _ => {},
+                    }
+                }
+            },
+            // Macro definitions are conventionally at the end of the file,
+            // and we have no use for them (or anything that follows).
+            "macdef" => break,
+            _ => { trace!("Unexpected token in .netrc: {}", token); },
+        }
+    }
+
+    let position = entries.iter()
+        .position(|&(m, _)| m.map(|m| m.eq_ignore_ascii_case(host)).unwrap_or(false));
+    position.map(|i| entries.swap_remove(i).1)
+}
+
+/// Domains of known URL shortening services.
+const URL_SHORTENERS: &'static [&'static str] = &[
+    "bit.ly", "git.io", "goo.gl", "is.gd", "t.co", "tinyurl.com",
+];
+
+/// Expand given URL if it points to a known URL shortener.
+///
+/// Only a single redirect is followed, and only if it leads to another
+/// HTTP(S) URL. Returns None if the URL isn't a shortened one.
+pub fn expand_short_url(url: &str) -> io::Result<Option<String>> {
+    let url_obj = try!(Url::parse(url)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)));
+    let is_shortened = url_obj.host_str()
+        .map(|h| h.trim_left_matches("www."))
+        .map(|h| URL_SHORTENERS.contains(&h))
+        .unwrap_or(false);
+    if !is_shortened {
+        trace!("URL {} doesn't point to a known URL shortener", url);
+        return Ok(None);
+    }
+
+    let http = http_client();
+    debug!("Expanding shortened URL {}", url);
+    let resp = try!(http.head(url)
+        .header(user_agent(None))
+        .send()
+        .map_err(http_error));
+    if !resp.status.is_redirection() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "shortened URL {} didn't redirect anywhere (HTTP {})", url, resp.status)));
+    }
+
+    let location = try!(resp.headers.get::<Location>().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, format!(
+            "redirect from {} has no Location: header", url))
+    }));
+    let target = try!(url_obj.join(&location.0)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)));
+    if !["http", "https"].contains(&target.scheme()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "shortened URL {} redirects to a non-HTTP URL: {}", url, target)));
+    }
+    trace!("URL {} expanded to {}", url, target);
+    Ok(Some(target.into_string()))
+}
+
+/// Query parameters that are only used for tracking, and never identify a gist.
+const TRACKING_PARAMS: &'static [&'static str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_ga",
+];
+/// Prefix of tracking query parameters like utm_source.
+const TRACKING_PARAM_PREFIX: &'static str = "utm_";
+
+/// Strip the parts of given URL that never affect which gist it points to,
+/// i.e. the fragment (like #L3) and known tracking query parameters.
+///
+/// Other query parameters are preserved exactly, as some hosts give them meaning.
+pub fn canonical_url(url: &str) -> Cow<str> {
+    let without_fragment = url.splitn(2, '#').next().unwrap();
+    let mut parts = without_fragment.splitn(2, '?');
+    let base = parts.next().unwrap();
+    let query = parts.next();
+
+    let kept_params: Vec<_> = query.map(|q| q.split('&').filter(|param| {
+        let key = param.splitn(2, '=').next().unwrap();
+        !(TRACKING_PARAMS.contains(&key) || key.starts_with(TRACKING_PARAM_PREFIX))
+    }).collect()).unwrap_or_else(Vec::new);
+    let stripped_params = query.map(|q| q.split('&').count()).unwrap_or(0) > kept_params.len();
+
+    if !stripped_params {
+        return if without_fragment.len() == url.len() { Cow::Borrowed(url) }
+               else { Cow::Borrowed(without_fragment) };
+    }
+    if kept_params.is_empty() {
+        Cow::Borrowed(base)
+    } else {
+        Cow::Owned(format!("{}?{}", base, kept_params.join("&")))
+    }
+}
+
+/// Decode a string in (standard, padded or not) base64 encoding.
+/// Whitespace is ignored. Returns None if the string isn't valid base64.
+pub fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(s.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    let mut padding = 0;
+    for c in s.bytes().filter(|c| !(*c as char).is_whitespace()) {
+        let value = match c {
+            b'A'...b'Z' => c - b'A',
+            b'a'...b'z' => c - b'a' + 26,
+            b'0'...b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => { padding += 1; continue; },
+            _ => return None,
+        };
+        if padding > 0 {
+            return None;  // Padding is only allowed at the end.
+        }
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // Leftover bits must be zero and fewer than a full byte.
+    if padding > 2 || bits >= 6 || buffer != 0 {
+        return None;
+    }
+    Some(result)
 }
 
 /// Read HTTP response from hyper and parse it as JSON.
+///
+/// Responses without any content (like `204 No Content`) result in an error,
+/// as all callers expect some JSON to be returned.
 pub fn read_json(response: &mut Response) -> io::Result<Json> {
     let mut body = match response.headers.get::<ContentLength>() {
         Some(&ContentLength(l)) => String::with_capacity(l as usize),
         _ => String::new(),
     };
-    response.read_to_string(&mut body).unwrap();
-    Json::from_str(&body)
+    if response.status != StatusCode::NoContent {
+        try!(response.read_to_string(&mut body));
+    }
+    parse_json_body(&body)
+}
+
+/// Parse the body of an HTTP response as JSON.
+fn parse_json_body(body: &str) -> io::Result<Json> {
+    if body.trim().is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "host returned no content"));
+    }
+    Json::from_str(body)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::path::PathBuf;
+    use hyper;
+    use url::Url;
+    use super::{build_user_agent, canonical_url, decode_base64, edit_distance, expand_short_url,
+                find_in_path, http_error, is_redirect_allowed, netrc_credentials, parse_json_body,
+                resolve_tty_override, walk_files};
+    #[cfg(unix)]
+    use super::{is_symlink, link_binary, symlink_file, symlink_points_to};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("gisht-util-test-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn find_in_path_missing() {
+        assert_eq!(None, find_in_path("gisht-surely-nonexistent-binary"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_in_path_existing() {
+        let sh = find_in_path("sh").expect("`sh` not found on $PATH");
+        assert!(sh.ends_with("sh"));
+    }
+
+    #[test]
+    fn insecure_redirects_not_followed() {
+        let https = Url::parse("https://example.com/foo").unwrap();
+        let http = Url::parse("http://example.com/bar").unwrap();
+        assert!(is_redirect_allowed(&https, &https));
+        assert!(!is_redirect_allowed(&https, &http));
+        assert!(is_redirect_allowed(&http, &http));
+        assert!(is_redirect_allowed(&http, &https));
+    }
+
+    #[test]
+    fn user_agent_suffix() {
+        assert_eq!("gisht/1.0", build_user_agent("gisht/1.0", None));
+        assert_eq!("gisht/1.0 (+https://example.com)",
+                   build_user_agent("gisht/1.0", Some("(+https://example.com)")));
+    }
+
+    #[test]
+    fn canonical_url_keeps_clean_urls() {
+        let url = "https://gist.github.com/Octocat/1234abcd";
+        assert_eq!(url, canonical_url(url));
+        assert_eq!("http://sprunge.us/ABcdEF?py", canonical_url("http://sprunge.us/ABcdEF?py"));
+    }
+
+    #[test]
+    fn canonical_url_strips_fragment() {
+        assert_eq!("https://gist.github.com/Octocat/1234abcd",
+                   canonical_url("https://gist.github.com/Octocat/1234abcd#file-foo-sh-L3"));
+        assert_eq!("http://sprunge.us/ABcdEF?py", canonical_url("http://sprunge.us/ABcdEF?py#L3"));
+    }
+
+    #[test]
+    fn canonical_url_strips_tracking_params() {
+        assert_eq!("https://gist.github.com/Octocat/1234abcd", canonical_url(
+            "https://gist.github.com/Octocat/1234abcd?utm_source=twitter&utm_medium=social"));
+        assert_eq!("http://sprunge.us/ABcdEF?py",
+                   canonical_url("http://sprunge.us/ABcdEF?fbclid=xyz&py#L3"));
+        assert_eq!("http://example.com/foo?ref=bar&lang=py",
+                   canonical_url("http://example.com/foo?ref=bar&gclid=1&lang=py"));
+    }
+
+    #[test]
+    fn walk_files_respects_max_depth() {
+        let dir = temp_dir("walk_files_respects_max_depth");
+        fs::create_dir_all(dir.join("a").join("b")).unwrap();
+        fs::File::create(dir.join("top")).unwrap();
+        fs::File::create(dir.join("a").join("middle")).unwrap();
+        fs::File::create(dir.join("a").join("b").join("bottom")).unwrap();
+
+        let mut files = walk_files(&dir, 2).unwrap();
+        files.sort();
+        assert_eq!(vec![dir.join("a").join("middle"), dir.join("top")], files);
+        assert_eq!(3, walk_files(&dir, 3).unwrap().len());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walk_files_skips_symlink_loops() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir("walk_files_skips_symlink_loops");
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::File::create(dir.join("a").join("file")).unwrap();
+        symlink(&dir, dir.join("a").join("loop")).unwrap();
+
+        let files = walk_files(&dir, usize::max_value()).unwrap();
+        assert_eq!(vec![dir.join("a").join("file")], files);
+    }
+
+    #[test]
+    fn decode_base64_valid() {
+        assert_eq!(Some(vec![]), decode_base64(""));
+        assert_eq!(Some(b"f".to_vec()), decode_base64("Zg=="));
+        assert_eq!(Some(b"fo".to_vec()), decode_base64("Zm8="));
+        assert_eq!(Some(b"foo".to_vec()), decode_base64("Zm9v"));
+        assert_eq!(Some(b"foob".to_vec()), decode_base64("Zm9vYg"));
+        assert_eq!(Some(b"print(42)\n".to_vec()), decode_base64("cHJp bnQo\nNDIpCg=="));
+        assert_eq!(Some(vec![0xfb, 0xff]), decode_base64("+/8="));
+    }
+
+    #[test]
+    fn decode_base64_invalid() {
+        for invalid in &["Z", "Zm9v!", "Zg==Zg==", "Zh==", "Zm9vY==="] {
+            assert_eq!(None, decode_base64(invalid), "`{}` unexpectedly decoded", invalid);
+        }
+    }
+
+    #[test]
+    fn netrc_credentials_for_machine() {
+        let netrc = "machine example.com login foo password bar\n\
+                     machine paste.example.org\n  login baz\n  password qux\n\
+                     default login anonymous password guest\n";
+
+        let creds = netrc_credentials(netrc, "paste.example.org").unwrap();
+        assert_eq!("baz", creds.username);
+        assert_eq!(Some("qux".to_owned()), creds.password);
+
+        let creds = netrc_credentials(netrc, "example.com").unwrap();
+        assert_eq!("foo", creds.username);
+
+        assert!(netrc_credentials(netrc, "other.example.net").is_none());
+    }
+
+    #[test]
+    fn netrc_credentials_without_match() {
+        let netrc = "machine example.com login foo password bar\n\
+                     macdef init\nmachine other.example.net login baz\n";
+        assert!(netrc_credentials(netrc, "other.example.net").is_none());
+        assert!(netrc_credentials("", "example.com").is_none());
+    }
+
+    #[test]
+    fn expand_short_url_ignores_regular_urls() {
+        let url = "https://gist.github.com/Octocat/1234abcd";
+        assert_eq!(None, expand_short_url(url).unwrap());
+    }
+
+    #[test]
+    fn expand_short_url_rejects_invalid_urls() {
+        assert!(expand_short_url("foobar").is_err());
+    }
+
+    #[test]
+    fn edit_distance_of_strings() {
+        assert_eq!(0, edit_distance("", ""));
+        assert_eq!(0, edit_distance("hello", "hello"));
+        assert_eq!(5, edit_distance("", "hello"));
+        assert_eq!(1, edit_distance("hello", "helo"));
+        assert_eq!(1, edit_distance("hello", "hallo"));
+        assert_eq!(2, edit_distance("hello", "ehllo"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+        assert_eq!(2, edit_distance("żółw", "żółwie"));
+        assert_eq!(1, edit_distance("żółw", "żołw"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_target_check() {
+        let dir = temp_dir("symlink_target_check");
+        let executable = dir.join("gist.sh");
+        fs::File::create(&executable).unwrap();
+        let binary = dir.join("gist");
+        symlink_file(&executable, &binary).unwrap();
+
+        assert!(symlink_points_to(&binary, &executable));
+        assert!(symlink_points_to(&binary, &dir.join(".").join("gist.sh")));
+        assert!(!symlink_points_to(&binary, &dir.join("other.sh")));
+        assert!(!symlink_points_to(&executable, &executable));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copied_binary_replaced_with_symlink() {
+        let dir = temp_dir("copied_binary_replaced_with_symlink");
+        let executable = dir.join("gist.sh");
+        fs::File::create(&executable).unwrap().write_all(b"echo new").unwrap();
+        // A stale copy, as left behind by an earlier run with --no-symlink.
+        let binary = dir.join("gist");
+        fs::File::create(&binary).unwrap().write_all(b"echo old").unwrap();
+
+        link_binary(&executable, &binary).unwrap();
+        assert!(is_symlink(&binary).unwrap());
+        assert!(symlink_points_to(&binary, &executable));
+    }
+
+    #[test]
+    fn http_error_timeouts() {
+        let read_timeout = http_error(hyper::Error::Io(io::ErrorKind::WouldBlock.into()));
+        assert_eq!(io::ErrorKind::TimedOut, read_timeout.kind());
+        assert!(read_timeout.to_string().contains("reading"));
+
+        let connect_timeout = http_error(hyper::Error::Io(io::Error::new(
+            io::ErrorKind::TimedOut, "connecting to example.com:443 timed out after 5s")));
+        assert_eq!(io::ErrorKind::TimedOut, connect_timeout.kind());
+        assert!(connect_timeout.to_string().contains("connecting"));
+
+        let other = http_error(hyper::Error::Io(io::ErrorKind::ConnectionRefused.into()));
+        assert_eq!(io::ErrorKind::Other, other.kind());
+    }
+
+    #[test]
+    fn empty_json_body() {
+        for body in &["", "  \n"] {
+            let err = parse_json_body(body).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+            assert!(err.to_string().contains("no content"));
+        }
+        assert!(parse_json_body("{}").unwrap().is_object());
+        assert_eq!(io::ErrorKind::InvalidInput, parse_json_body("{").unwrap_err().kind());
+    }
+
+    #[test]
+    fn tty_overrides() {
+        assert_eq!(None, resolve_tty_override(false, false));
+        assert_eq!(Some(true), resolve_tty_override(true, false));
+        assert_eq!(Some(false), resolve_tty_override(false, true));
+        assert_eq!(Some(false), resolve_tty_override(true, true));
+    }
+}